@@ -18,4 +18,32 @@ pub struct Cli {
     /// 启动后不自动打开浏览器
     #[arg(long)]
     pub no_browser: bool,
+
+    /// 单个上传文件的大小上限（MB）
+    #[arg(long, default_value_t = 100)]
+    pub max_upload_mb: usize,
+
+    /// 图片 / 二维码上传及 data URI 解码请求的请求体上限（MB），批量上传时按总量计算；其余接口为 2MB
+    #[arg(long, default_value_t = 1024)]
+    pub max_body_mb: usize,
+
+    /// 图片解码后的像素上限（百万像素），用于拦截解压炸弹
+    #[arg(long, default_value_t = 100)]
+    pub max_megapixels: u64,
+
+    /// 同时执行的图片处理任务数，0 表示按 CPU 核数
+    #[arg(long, default_value_t = 0)]
+    pub image_workers: usize,
+
+    /// 图片处理任务的最大排队数，队列满时返回 503
+    #[arg(long, default_value_t = 64)]
+    pub image_queue: usize,
+
+    /// 单个图片处理请求的超时（秒），含排队时间
+    #[arg(long, default_value_t = 120)]
+    pub image_timeout_secs: u64,
+
+    /// 每种 DNS 服务器配置缓存的应答条数，0 表示关闭缓存
+    #[arg(long, default_value_t = 1024)]
+    pub dns_cache_entries: usize,
 }
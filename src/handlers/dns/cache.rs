@@ -0,0 +1,223 @@
+//! 按服务器配置复用的查询客户端，以及按记录 TTL 过期的应答缓存。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::Json;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{Name, RecordType};
+use serde::Serialize;
+use tokio::time::Instant;
+
+use super::client::{DnsClient, Exchange, ResolverSettings};
+use crate::handlers::error::AppError;
+
+/// 同时保留的客户端（服务器配置）数量上限。
+const MAX_CLIENTS: usize = 64;
+/// 缓存时间上限，避免超长 TTL 的记录一直不刷新。
+const MAX_TTL: u32 = 3600;
+
+/// DNS 接口共享的状态。
+#[derive(Clone)]
+pub struct DnsConfig {
+    pub clients: Arc<ClientPool>,
+}
+
+/// 以服务器配置为键复用客户端：系统 DNS 配置只在首次使用时读取，
+/// 同一配置的查询共享一份应答缓存。超出上限时淘汰最久未使用的客户端。
+pub struct ClientPool {
+    clients: Mutex<HashMap<ResolverSettings, Pooled>>,
+    /// 每个客户端缓存的应答条数，0 表示不缓存
+    cache_entries: usize,
+}
+
+struct Pooled {
+    client: DnsClient,
+    last_used: Instant,
+}
+
+impl ClientPool {
+    pub fn new(cache_entries: usize) -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            cache_entries,
+        }
+    }
+
+    /// 取出与配置对应的客户端，不存在时新建。
+    /// 新建（可能读取系统 DNS 配置）在锁外进行，插入前再查一次，并发新建时以先插入者为准。
+    pub(super) fn client(&self, settings: &ResolverSettings) -> Result<DnsClient, AppError> {
+        if let Some(client) = self.lookup(settings) {
+            return Ok(client);
+        }
+        let mut client = DnsClient::new(settings)?;
+        if self.cache_entries > 0 {
+            client = client.with_cache(Arc::new(AnswerCache::new(self.cache_entries)));
+        }
+
+        let mut clients = lock(&self.clients);
+        let now = Instant::now();
+        if let Some(pooled) = clients.get_mut(settings) {
+            pooled.last_used = now;
+            return Ok(pooled.client.clone());
+        }
+        if clients.len() >= MAX_CLIENTS {
+            let oldest = clients.iter().min_by_key(|(_, p)| p.last_used).map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                clients.remove(&key);
+            }
+        }
+        clients.insert(settings.clone(), Pooled { client: client.clone(), last_used: now });
+        Ok(client)
+    }
+
+    fn lookup(&self, settings: &ResolverSettings) -> Option<DnsClient> {
+        let mut clients = lock(&self.clients);
+        let pooled = clients.get_mut(settings)?;
+        pooled.last_used = Instant::now();
+        Some(pooled.client.clone())
+    }
+
+    /// 丢弃全部客户端与缓存，之后的查询会重新读取系统 DNS 配置。返回 (客户端数, 缓存条数)。
+    fn flush(&self) -> (usize, usize) {
+        let clients = std::mem::take(&mut *lock(&self.clients));
+        let entries = clients.values().map(|p| p.client.clear_cache()).sum();
+        (clients.len(), entries)
+    }
+}
+
+/// 一个客户端的应答缓存，键为 (名称, 记录类型)。
+pub(super) struct AnswerCache {
+    entries: Mutex<HashMap<(Name, RecordType), Cached>>,
+    capacity: usize,
+}
+
+struct Cached {
+    exchange: Exchange,
+    stored: Instant,
+    expires: Instant,
+}
+
+impl AnswerCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// 取出未过期的应答，记录的 TTL 减去已缓存的时长。
+    pub(super) fn get(&self, name: &Name, record_type: RecordType) -> Option<Exchange> {
+        let mut entries = lock(&self.entries);
+        let key = (name.clone(), record_type);
+        let now = Instant::now();
+        let cached = entries.get(&key)?;
+        if cached.expires <= now {
+            entries.remove(&key);
+            return None;
+        }
+        let age = now.duration_since(cached.stored).as_secs() as u32;
+        let mut exchange = cached.exchange.clone();
+        let response = &mut *exchange.response;
+        for record in response.answers.iter_mut().chain(response.authorities.iter_mut()) {
+            record.decrement_ttl(age);
+        }
+        exchange.elapsed = Duration::ZERO;
+        exchange.cached = true;
+        Some(exchange)
+    }
+
+    /// 按应答中最小的 TTL 缓存；否定应答按 SOA 的否定缓存时间，服务器失败等不缓存。
+    pub(super) fn put(&self, name: &Name, record_type: RecordType, exchange: &Exchange) {
+        let response = &exchange.response;
+        let ttl = match response.metadata.response_code {
+            ResponseCode::NoError if !response.answers.is_empty() => response.answers.iter().map(|r| r.ttl).min(),
+            ResponseCode::NoError | ResponseCode::NXDomain => response.negative_ttl(),
+            _ => None,
+        };
+        let Some(ttl) = ttl.map(|ttl| ttl.min(MAX_TTL)).filter(|&ttl| ttl > 0) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut entries = lock(&self.entries);
+        if entries.len() >= self.capacity {
+            entries.retain(|_, cached| cached.expires > now);
+        }
+        if entries.len() >= self.capacity {
+            // 仍然满时淘汰最先过期的一条
+            if let Some(key) = entries.iter().min_by_key(|(_, cached)| cached.expires).map(|(key, _)| key.clone()) {
+                entries.remove(&key);
+            }
+        }
+        entries.insert(
+            (name.clone(), record_type),
+            Cached {
+                exchange: exchange.clone(),
+                stored: now,
+                expires: now + Duration::from_secs(ttl.into()),
+            },
+        );
+    }
+
+    /// 清空缓存，返回清除的条数。
+    pub(super) fn clear(&self) -> usize {
+        let mut entries = lock(&self.entries);
+        let count = entries.len();
+        entries.clear();
+        count
+    }
+}
+
+/// 某个任务持锁时 panic 不影响被保护的数据本身，继续使用。
+pub(super) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Serialize)]
+pub struct FlushResponse {
+    /// 丢弃的客户端（服务器配置）数
+    pub clients: usize,
+    /// 清除的缓存应答条数
+    pub entries: usize,
+}
+
+/// 清空应答缓存并丢弃复用的客户端。
+pub async fn flush_cache(State(cfg): State<DnsConfig>) -> Json<FlushResponse> {
+    let (clients, entries) = cfg.clients.flush();
+    Json(FlushResponse { clients, entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(i: usize) -> ResolverSettings {
+        ResolverSettings { nameservers: vec![format!("192.0.2.1:{}", 1000 + i)], ..Default::default() }
+    }
+
+    fn pooled(pool: &ClientPool) -> Vec<ResolverSettings> {
+        lock(&pool.clients).keys().cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_client() {
+        let pool = ClientPool::new(0);
+        for i in 0..MAX_CLIENTS {
+            pool.client(&settings(i)).ok().unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // 再次使用最早的配置，淘汰的应是第二个
+        pool.client(&settings(0)).ok().unwrap();
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        pool.client(&settings(MAX_CLIENTS)).ok().unwrap();
+
+        let kept = pooled(&pool);
+        assert_eq!(kept.len(), MAX_CLIENTS);
+        assert!(kept.contains(&settings(0)));
+        assert!(!kept.contains(&settings(1)));
+        assert!(kept.contains(&settings(MAX_CLIENTS)));
+    }
+}
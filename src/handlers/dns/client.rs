@@ -0,0 +1,474 @@
+//! 按请求指定的服务器、传输协议与报文选项直接发起 DNS 查询（UDP / TCP / DoT / DoH）。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hickory_resolver::config::{ConnectionConfig, ProtocolConfig, ResolverOpts};
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use hickory_resolver::net::NetError;
+use hickory_resolver::net::xfer::{DnsHandle, FirstAnswer};
+use hickory_resolver::proto::op::{DnsRequest, DnsRequestOptions, DnsResponse, Query};
+use hickory_resolver::proto::rr::{Name, RecordType};
+use hickory_resolver::{ConnectionProvider, PoolContext, TlsConfig};
+use serde::Deserialize;
+
+use super::cache::{lock, AnswerCache};
+use crate::handlers::error::AppError;
+
+/// 单个服务器的查询超时。
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// 最多允许指定的服务器数量。
+const MAX_NAMESERVERS: usize = 8;
+
+/// 已建立的连接句柄，可并发发送多个请求。
+type Connection = <TokioRuntimeProvider as ConnectionProvider>::Conn;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Udp,
+    Tcp,
+    #[serde(alias = "tls")]
+    Dot,
+    #[serde(alias = "https")]
+    Doh,
+}
+
+/// 查询使用的服务器与报文选项，各接口通过 `#[serde(flatten)]` 复用。
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct ResolverSettings {
+    /// 服务器地址（`IP` 或 `IP:端口`，IPv6 带端口时写作 `[::1]:53`），为空时使用系统配置
+    pub nameservers: Vec<String>,
+    pub transport: Transport,
+    /// DoT / DoH 握手与证书校验使用的主机名，缺省为服务器 IP
+    pub tls_server_name: Option<String>,
+    pub doh_path: String,
+    /// 跳过 DoT / DoH 证书校验（自签名的测试服务器）
+    pub tls_insecure: bool,
+    pub recursion_desired: bool,
+    /// EDNS UDP 缓冲区大小，0 表示不携带 EDNS
+    pub edns_buffer_size: u16,
+    /// 设置 EDNS 的 DO 位，要求服务器返回 DNSSEC 记录
+    pub dnssec_ok: bool,
+    /// 设置 CD 位，要求验证型解析器不做验证、照常返回数据（用于排查 DNSSEC 故障）
+    pub checking_disabled: bool,
+}
+
+impl Default for ResolverSettings {
+    fn default() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            transport: Transport::Udp,
+            tls_server_name: None,
+            doh_path: "/dns-query".to_string(),
+            tls_insecure: false,
+            recursion_desired: true,
+            edns_buffer_size: 1232,
+            dnssec_ok: false,
+            checking_disabled: false,
+        }
+    }
+}
+
+/// 一次成功的交互：响应报文、应答的服务器、最终使用的协议与总耗时。
+#[derive(Clone)]
+pub struct Exchange {
+    pub response: DnsResponse,
+    pub server: SocketAddr,
+    pub transport: &'static str,
+    pub elapsed: Duration,
+    /// UDP 响应带 TC 位（随后已改用 TCP 重试）
+    pub truncated: bool,
+    /// 取自应答缓存（此时 `elapsed` 为 0）
+    pub cached: bool,
+}
+
+pub enum QueryError {
+    Timeout,
+    Failed(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("查询超时"),
+            Self::Failed(e) => write!(f, "查询失败: {e}"),
+        }
+    }
+}
+
+/// 按配置好的服务器列表依次查询：收到任何响应即返回，网络错误或超时才换下一台。
+#[derive(Clone)]
+pub struct DnsClient {
+    servers: Vec<SocketAddr>,
+    transport: Transport,
+    tls_server_name: Option<Arc<str>>,
+    doh_path: Arc<str>,
+    options: DnsRequestOptions,
+    checking_disabled: bool,
+    cx: Arc<PoolContext>,
+    provider: TokioRuntimeProvider,
+    /// 按（服务器, 协议）复用的连接，克隆的客户端共享；TCP / DoT / DoH 不必每次查询都握手
+    connections: Arc<Mutex<HashMap<(SocketAddr, &'static str), Connection>>>,
+    cache: Option<Arc<AnswerCache>>,
+    /// 不读缓存，但查询结果仍写回
+    refresh: bool,
+}
+
+impl DnsClient {
+    pub fn new(settings: &ResolverSettings) -> Result<Self, AppError> {
+        if settings.nameservers.len() > MAX_NAMESERVERS {
+            return Err(AppError::bad_request(format!("服务器最多 {MAX_NAMESERVERS} 个")));
+        }
+        if settings.dnssec_ok && settings.edns_buffer_size == 0 {
+            return Err(AppError::bad_request("DO 位需要 EDNS，缓冲区大小不能为 0"));
+        }
+        if settings.edns_buffer_size != 0 && settings.edns_buffer_size < 512 {
+            return Err(AppError::bad_request("EDNS 缓冲区大小不能小于 512"));
+        }
+
+        let servers = if settings.nameservers.is_empty() {
+            if matches!(settings.transport, Transport::Dot | Transport::Doh) {
+                return Err(AppError::bad_request("DoT / DoH 需要指定服务器"));
+            }
+            system_nameservers()?
+        } else {
+            let default_port = match settings.transport {
+                Transport::Udp | Transport::Tcp => 53,
+                Transport::Dot => 853,
+                Transport::Doh => 443,
+            };
+            settings
+                .nameservers
+                .iter()
+                .map(|s| parse_nameserver(s, default_port))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut options = DnsRequestOptions::default();
+        options.recursion_desired = settings.recursion_desired;
+        options.use_edns = settings.edns_buffer_size != 0;
+        options.edns_payload_len = settings.edns_buffer_size.max(512);
+        options.edns_set_dnssec_ok = settings.dnssec_ok;
+
+        let mut opts = ResolverOpts::default();
+        opts.timeout = QUERY_TIMEOUT;
+        let mut tls = TlsConfig::new().map_err(|e| anyhow::anyhow!("初始化 TLS 配置失败: {e}"))?;
+        if settings.tls_insecure {
+            tls.insecure_skip_verify();
+        }
+
+        Ok(Self {
+            servers,
+            transport: settings.transport,
+            tls_server_name: settings
+                .tls_server_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(Into::into),
+            doh_path: settings.doh_path.as_str().into(),
+            options,
+            checking_disabled: settings.checking_disabled,
+            cx: Arc::new(PoolContext::new(opts, tls)),
+            provider: TokioRuntimeProvider::default(),
+            connections: Arc::default(),
+            cache: None,
+            refresh: false,
+        })
+    }
+
+    pub(super) fn with_cache(mut self, cache: Arc<AnswerCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// 跳过缓存直接查询服务器，结果仍会更新缓存。
+    pub fn refresh(mut self) -> Self {
+        self.refresh = true;
+        self
+    }
+
+    /// 清空应答缓存，返回清除的条数。
+    pub(super) fn clear_cache(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.clear())
+    }
+
+    /// 配置的服务器列表。
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// 查询一个名称的一种记录类型，启用缓存时优先使用未过期的应答。
+    pub async fn query(&self, name: &Name, record_type: RecordType) -> Result<Exchange, QueryError> {
+        if let Some(cache) = self.cache.as_ref().filter(|_| !self.refresh) {
+            if let Some(exchange) = cache.get(name, record_type) {
+                return Ok(exchange);
+            }
+        }
+        let mut last_error = QueryError::Failed("没有可用的服务器".to_string());
+        for addr in &self.servers {
+            match self.query_at(*addr, name, record_type).await {
+                Ok(exchange) => {
+                    if let Some(cache) = &self.cache {
+                        cache.put(name, record_type, &exchange);
+                    }
+                    return Ok(exchange);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// 以本客户端的协议与报文选项向指定服务器查询。UDP 响应被截断（TC）时改用 TCP 重试。
+    pub async fn query_at(&self, addr: SocketAddr, name: &Name, record_type: RecordType) -> Result<Exchange, QueryError> {
+        let started = tokio::time::Instant::now();
+        let mut protocol = self.protocol(addr);
+        let mut result = self.exchange(addr, &protocol, name, record_type).await;
+        let truncated = matches!(&result, Ok(r) if r.metadata.truncation);
+        if truncated && protocol == ProtocolConfig::Udp {
+            protocol = ProtocolConfig::Tcp;
+            result = self.exchange(addr, &protocol, name, record_type).await;
+        }
+        result.map(|response| Exchange {
+            response,
+            server: addr,
+            transport: transport_name(&protocol),
+            elapsed: started.elapsed(),
+            truncated,
+            cached: false,
+        })
+    }
+
+    fn protocol(&self, addr: SocketAddr) -> ProtocolConfig {
+        // DoT / DoH 的握手主机名缺省为服务器 IP
+        let server_name = || {
+            self.tls_server_name
+                .clone()
+                .unwrap_or_else(|| addr.ip().to_string().into())
+        };
+        match self.transport {
+            Transport::Udp => ProtocolConfig::Udp,
+            Transport::Tcp => ProtocolConfig::Tcp,
+            Transport::Dot => ProtocolConfig::Tls { server_name: server_name() },
+            Transport::Doh => ProtocolConfig::Https {
+                server_name: server_name(),
+                path: self.doh_path.clone(),
+            },
+        }
+    }
+
+    async fn exchange(
+        &self,
+        addr: SocketAddr,
+        protocol: &ProtocolConfig,
+        name: &Name,
+        record_type: RecordType,
+    ) -> Result<DnsResponse, QueryError> {
+        let mut request = DnsRequest::from_query(Query::query(name.clone(), record_type), self.options);
+        request.metadata.checking_disabled = self.checking_disabled;
+        let key = (addr, transport_name(protocol));
+        let attempt = async {
+            let pooled = lock(&self.connections).get(&key).cloned();
+            if let Some(conn) = pooled {
+                match conn.send(request.clone()).first_answer().await {
+                    Ok(response) => return Ok(response),
+                    Err(e) => {
+                        // 连接可能已被对端关闭：丢弃后新建连接重试一次，超时则不再重试
+                        lock(&self.connections).remove(&key);
+                        if matches!(e, NetError::Timeout) {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+            let mut config = ConnectionConfig::new(protocol.clone());
+            config.port = addr.port();
+            let conn = self
+                .provider
+                .new_connection(addr.ip(), &config, &self.cx)?
+                .await?;
+            let result = conn.clone().send(request).first_answer().await;
+            if result.is_ok() {
+                lock(&self.connections).insert(key, conn);
+            }
+            result
+        };
+        match tokio::time::timeout(QUERY_TIMEOUT, attempt).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(NetError::Timeout)) => Err(QueryError::Timeout),
+            Ok(Err(e)) => Err(QueryError::Failed(format!("{addr}: {e}"))),
+            Err(_) => Err(QueryError::Timeout),
+        }
+    }
+}
+
+fn transport_name(protocol: &ProtocolConfig) -> &'static str {
+    match protocol {
+        ProtocolConfig::Udp => "udp",
+        ProtocolConfig::Tcp => "tcp",
+        ProtocolConfig::Tls { .. } => "dot",
+        ProtocolConfig::Https { .. } => "doh",
+    }
+}
+
+/// 解析 `IP`、`IP:端口` 或 `[IPv6]:端口`。
+fn parse_nameserver(input: &str, default_port: u16) -> Result<SocketAddr, AppError> {
+    let input = input.trim();
+    if let Ok(addr) = input.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let ip = input.trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, default_port))
+        .map_err(|_| AppError::bad_request(format!("无效的服务器地址: {input}（应为 IP 或 IP:端口）")))
+}
+
+/// 读取系统 DNS 配置中的服务器。
+fn system_nameservers() -> Result<Vec<SocketAddr>, AppError> {
+    let (config, _) = hickory_resolver::system_conf::read_system_conf()
+        .map_err(|e| anyhow::anyhow!("读取系统 DNS 配置失败: {e}"))?;
+    let mut addrs: Vec<SocketAddr> = Vec::new();
+    for ns in config.name_servers() {
+        let port = ns
+            .connections
+            .iter()
+            .find(|c| c.protocol == ProtocolConfig::Udp)
+            .map_or(53, |c| c.port);
+        let addr = SocketAddr::new(ns.ip, port);
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    if addrs.is_empty() {
+        return Err(AppError::bad_request("系统 DNS 配置中没有服务器"));
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    use hickory_resolver::proto::op::Message;
+    use hickory_resolver::proto::rr::rdata::A;
+    use hickory_resolver::proto::rr::{RData, Record};
+
+    use crate::handlers::dns::stub::{self, Stub};
+
+    /// 应答 192.0.2.1；`truncate_udp` 时 UDP 只回带 TC 位的空响应。
+    fn answer(request: &Message, transport: &str, truncate_udp: bool) -> Message {
+        let mut reply = stub::reply(request);
+        if truncate_udp && transport == "udp" {
+            reply.metadata.truncation = true;
+        } else {
+            let name = request.queries[0].name().clone();
+            reply.add_answer(Record::from_rdata(name, 300, RData::A(A(Ipv4Addr::new(192, 0, 2, 1)))));
+        }
+        reply
+    }
+
+    fn settings(nameservers: Vec<String>) -> ResolverSettings {
+        ResolverSettings { nameservers, ..Default::default() }
+    }
+
+    /// 用给定配置查询 example.com 的 A 记录。
+    async fn query(settings: &ResolverSettings) -> Exchange {
+        let client = DnsClient::new(settings).unwrap_or_else(|e| panic!("{}", e.message));
+        let name = Name::from_ascii("example.com.").unwrap();
+        client.query(&name, RecordType::A).await.unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    #[test]
+    fn parses_nameserver_ports() {
+        assert_eq!(parse_nameserver("192.0.2.1", 53).ok(), "192.0.2.1:53".parse().ok());
+        assert_eq!(parse_nameserver(" 192.0.2.1:5353 ", 53).ok(), "192.0.2.1:5353".parse().ok());
+        assert_eq!(parse_nameserver("[2001:db8::1]", 853).ok(), "[2001:db8::1]:853".parse().ok());
+        assert_eq!(parse_nameserver("[2001:db8::1]:8053", 53).ok(), "[2001:db8::1]:8053".parse().ok());
+        assert!(parse_nameserver("dns.example", 53).is_err());
+    }
+
+    #[tokio::test]
+    async fn honours_port_and_header_options() {
+        let stub = Stub::start(&[1], |_, transport, request| Some(answer(request, transport, false))).await;
+        let addr = stub.addr(1);
+        let mut custom = settings(vec![addr.to_string()]);
+        custom.recursion_desired = false;
+        custom.edns_buffer_size = 1400;
+        custom.dnssec_ok = true;
+        custom.checking_disabled = true;
+        let exchange = query(&custom).await;
+        assert_eq!(exchange.server, addr);
+        assert_eq!((exchange.transport, exchange.truncated), ("udp", false));
+        assert_eq!(exchange.response.answers.len(), 1);
+
+        let no_edns = ResolverSettings { edns_buffer_size: 0, ..settings(vec![addr.to_string()]) };
+        query(&no_edns).await;
+
+        let received = stub.received();
+        let [custom, plain] = received.as_slice() else {
+            panic!("应收到 2 个请求");
+        };
+        let (custom, plain) = (&custom.request, &plain.request);
+        assert!(!custom.metadata.recursion_desired);
+        assert!(custom.metadata.checking_disabled);
+        let edns = custom.edns.as_ref().unwrap();
+        assert_eq!(edns.max_payload(), 1400);
+        assert!(edns.flags().dnssec_ok);
+
+        assert!(plain.metadata.recursion_desired);
+        assert!(!plain.metadata.checking_disabled);
+        assert!(plain.edns.is_none());
+    }
+
+    #[tokio::test]
+    async fn truncated_udp_falls_back_to_tcp() {
+        let stub = Stub::start(&[1], |_, transport, request| Some(answer(request, transport, true))).await;
+        let exchange = query(&settings(vec![stub.addr(1).to_string()])).await;
+        assert!(exchange.truncated);
+        assert_eq!(exchange.transport, "tcp");
+        assert!(!exchange.response.metadata.truncation);
+        assert_eq!(exchange.response.answers.len(), 1);
+        let transports: Vec<_> = stub.received().into_iter().map(|r| r.transport).collect();
+        assert_eq!(transports, ["udp", "tcp"]);
+    }
+
+    #[tokio::test]
+    async fn timeout_moves_to_next_server() {
+        // 127.0.0.1 收到请求但不应答
+        let stub = Stub::start(&[1, 2], |server, transport, request| {
+            (server != Ipv4Addr::LOCALHOST).then(|| answer(request, transport, false))
+        })
+        .await;
+        let servers = vec![stub.addr(1).to_string(), stub.addr(2).to_string()];
+        let exchange = query(&settings(servers)).await;
+        assert_eq!(exchange.server, stub.addr(2));
+        assert!(exchange.elapsed < QUERY_TIMEOUT);
+        let servers: Vec<_> = stub.received().into_iter().map(|r| r.server).collect();
+        // UDP 在超时前可能重发，只要求先问过第一台
+        assert_eq!(servers.first(), Some(&stub.addr(1).ip()));
+        assert_eq!(servers.last(), Some(&stub.addr(2).ip()));
+    }
+
+    #[tokio::test]
+    async fn reuses_tcp_connection_across_queries() {
+        let stub = Stub::start(&[1], |_, transport, request| Some(answer(request, transport, false))).await;
+        let tcp = ResolverSettings { transport: Transport::Tcp, ..settings(vec![stub.addr(1).to_string()]) };
+        let client = DnsClient::new(&tcp).unwrap_or_else(|e| panic!("{}", e.message));
+        for host in ["a.example.com.", "b.example.com.", "c.example.com."] {
+            let name = Name::from_ascii(host).unwrap();
+            let exchange = client.clone().refresh().query(&name, RecordType::A).await.unwrap_or_else(|e| panic!("{e}"));
+            assert_eq!(exchange.transport, "tcp");
+        }
+        let peers: Vec<_> = stub.received().into_iter().map(|r| r.peer).collect();
+        assert_eq!(peers.len(), 3);
+        assert!(peers.iter().all(|p| *p == peers[0]), "{peers:?}");
+    }
+}
@@ -0,0 +1,1116 @@
+//! DNSSEC 验证：按 RRSIG 的签名者逐级向上核对 DS → DNSKEY → RRSIG，直到内置的根信任锚，
+//! 对应答中的每个 RRset 给出 SECURE / INSECURE / BOGUS。
+//!
+//! 查询一律带 DO 与 CD 位，验证型解析器也会原样返回签名有误的数据。不安全委派须有父区签名的
+//! NSEC / NSEC3 证明委派点没有 DS（NSEC3 也可由 opt-out 区间覆盖）；NXDOMAIN 与 NODATA 应答须有
+//! 证明名称或类型不存在的 NSEC / NSEC3，否则为 BOGUS。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hickory_resolver::proto::dnssec::rdata::{DNSSECRData, DNSKEY, DS, NSEC, NSEC3, RRSIG};
+use hickory_resolver::proto::dnssec::{PublicKey, TrustAnchors, Verifier};
+use hickory_resolver::proto::op::{DnsResponse, ResponseCode};
+use hickory_resolver::proto::rr::{DNSClass, Name, RData, Record, RecordType, RecordTypeSet};
+use serde::Serialize;
+
+use super::client::{DnsClient, QueryError};
+use super::{rcode_name, DnsResult, Status};
+
+/// 信任链最多向上追溯的区数。
+const MAX_ZONES: usize = 16;
+
+/// 验证结果，按严重程度排序（取最差者作为整体结果）。
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Security {
+    Secure,
+    Insecure,
+    /// 查询失败等原因无法判断
+    Indeterminate,
+    Bogus,
+}
+
+#[derive(Serialize)]
+pub struct DnssecReport {
+    /// 所有 RRset 中最差的结果
+    pub status: Security,
+    pub rrsets: Vec<RrsetCheck>,
+    /// 涉及的区，从根开始
+    pub chain: Vec<ZoneCheck>,
+}
+
+#[derive(Serialize)]
+pub struct RrsetCheck {
+    pub name: String,
+    pub record_type: String,
+    pub status: Security,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub signatures: Vec<SignatureCheck>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ZoneCheck {
+    pub zone: String,
+    pub status: Security,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 父区中的 DS 记录
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ds: Vec<DsInfo>,
+    /// DS 集合（或无 DS 时的否定证明）的签名
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ds_signatures: Vec<SignatureCheck>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dnskeys: Vec<KeyInfo>,
+    /// DNSKEY 集合的签名
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dnskey_signatures: Vec<SignatureCheck>,
+    /// 已验证的区密钥，用于校验区内数据
+    #[serde(skip)]
+    keys: Vec<DNSKEY>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DsInfo {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub algorithm_name: String,
+    pub digest_type: u8,
+    /// 与区内某个 DNSKEY 的摘要一致
+    pub matched: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct KeyInfo {
+    pub key_tag: Option<u16>,
+    pub algorithm: u8,
+    pub algorithm_name: String,
+    pub flags: u16,
+    /// KSK（设置 SEP 位）或 ZSK
+    pub role: &'static str,
+    /// 被父区 DS 指向（根区为与信任锚一致）
+    pub trusted: bool,
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SignatureCheck {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub algorithm_name: String,
+    pub signer: String,
+    /// UTC，RFC 3339 格式
+    pub inception: String,
+    pub expiration: String,
+    /// 距过期的天数，已过期为负数
+    pub expires_in_days: i64,
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ZoneCheck {
+    fn new(zone: &Name, status: Security, reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        Self {
+            zone: zone.to_string(),
+            status,
+            reason: (!reason.is_empty()).then_some(reason),
+            ds: Vec::new(),
+            ds_signatures: Vec::new(),
+            dnskeys: Vec::new(),
+            dnskey_signatures: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    fn fail(&mut self, status: Security, reason: impl Into<String>) {
+        self.status = status;
+        self.reason = Some(reason.into());
+        self.keys.clear();
+    }
+}
+
+/// DNSSEC 模式的查询：整理结果后逐个 RRset 验证并附上报告。
+pub(super) async fn query(client: &DnsClient, record_type: String, rt: RecordType, name: &Name) -> DnsResult {
+    match client.query(name, rt).await {
+        Ok(exchange) => {
+            let report = validate(client, name, rt, &exchange.response, TrustAnchors::default()).await;
+            let mut result = DnsResult::from_exchange(record_type, rt, name, exchange);
+            result.dnssec = Some(report);
+            result
+        }
+        Err(e @ QueryError::Timeout) => DnsResult::failed(name, record_type, Status::Timeout, e.to_string()),
+        Err(e) => DnsResult::failed(name, record_type, Status::Failed, e.to_string()),
+    }
+}
+
+async fn validate(
+    client: &DnsClient,
+    name: &Name,
+    rt: RecordType,
+    response: &DnsResponse,
+    anchors: TrustAnchors,
+) -> DnssecReport {
+    let mut validator = Validator::new(client, anchors);
+    // 有应答记录时验证应答区，否则验证权威区中的 SOA 与 NSEC / NSEC3（否定应答）
+    let section = if response.answers.is_empty() {
+        &response.authorities
+    } else {
+        &response.answers
+    };
+    let mut rrsets = Vec::new();
+    for set in group(section) {
+        rrsets.push(validator.check_rrset(&set).await);
+    }
+
+    let code = response.metadata.response_code;
+    let status = if let Some(worst) = rrsets.iter().map(|r| r.status).max() {
+        match denial_check(name, rt, code, response) {
+            Some(check) if worst == Security::Secure => {
+                rrsets.push(check);
+                Security::Bogus
+            }
+            _ => worst,
+        }
+    } else if !matches!(code, ResponseCode::NoError | ResponseCode::NXDomain) {
+        rrsets.push(RrsetCheck {
+            name: name.to_string(),
+            record_type: String::new(),
+            status: Security::Indeterminate,
+            reason: Some(format!("查询返回 {}，无法验证", rcode_name(code))),
+            signatures: Vec::new(),
+        });
+        Security::Indeterminate
+    } else {
+        // 没有任何记录可验证：已签名的区必须给出经签名的否定证明
+        let (status, reason) = match validator.enclosing_zone(name).await {
+            Some(zone) => match validator.zone(&zone).await {
+                Security::Secure => (Security::Bogus, format!("{zone} 已签名，但应答中没有经签名的记录")),
+                other => (other, chain_reason(&zone, other)),
+            },
+            None => (Security::Indeterminate, "无法确定所在区".to_string()),
+        };
+        rrsets.push(RrsetCheck {
+            name: name.to_string(),
+            record_type: String::new(),
+            status,
+            reason: Some(reason),
+            signatures: Vec::new(),
+        });
+        status
+    };
+
+    let mut chain: Vec<(Name, ZoneCheck)> = validator.zones.into_iter().collect();
+    chain.sort_by(|(a, _), (b, _)| a.num_labels().cmp(&b.num_labels()).then_with(|| a.cmp(b)));
+    DnssecReport {
+        status,
+        rrsets,
+        chain: chain.into_iter().map(|(_, check)| check).collect(),
+    }
+}
+
+/// 否定应答（NXDOMAIN / 没有应答记录的 NOERROR）须由权威区中的 NSEC / NSEC3 证明名称或类型
+/// 不存在；缺少证明时返回 BOGUS 的检查项。区取 SOA 的所有者，没有 SOA 时取签名者。
+fn denial_check(name: &Name, rt: RecordType, code: ResponseCode, response: &DnsResponse) -> Option<RrsetCheck> {
+    if !response.answers.is_empty() || !matches!(code, ResponseCode::NoError | ResponseCode::NXDomain) {
+        return None;
+    }
+    let authorities = &response.authorities;
+    let zone = authorities
+        .iter()
+        .find(|r| r.record_type() == RecordType::SOA)
+        .map(|r| r.name.clone())
+        .or_else(|| {
+            authorities.iter().find_map(|r| match &r.data {
+                RData::DNSSEC(DNSSECRData::RRSIG(sig)) => Some(sig.input().signer_name.clone()),
+                _ => None,
+            })
+        })?;
+    let denial = Denial::new(&zone, authorities);
+    let (proven, reason) = if code == ResponseCode::NXDomain {
+        (denial.no_name(name), format!("{zone} 的 NSEC / NSEC3 未能证明 {name} 不存在"))
+    } else {
+        (denial.no_data(name, rt), format!("{zone} 的 NSEC / NSEC3 未能证明 {name} 没有 {rt} 记录"))
+    };
+    (!proven).then(|| RrsetCheck {
+        name: name.to_string(),
+        record_type: rt.to_string(),
+        status: Security::Bogus,
+        reason: Some(reason),
+        signatures: Vec::new(),
+    })
+}
+
+/// 同一所有者、同一类型的一组记录及覆盖它的 RRSIG。
+struct Rrset<'r> {
+    name: Name,
+    record_type: RecordType,
+    records: Vec<&'r Record>,
+    sigs: Vec<&'r RRSIG>,
+}
+
+fn group(records: &[Record]) -> Vec<Rrset<'_>> {
+    let mut sets: Vec<Rrset<'_>> = Vec::new();
+    for record in records {
+        if matches!(record.data, RData::DNSSEC(DNSSECRData::RRSIG(_))) {
+            continue;
+        }
+        let rt = record.record_type();
+        match sets.iter_mut().find(|s| s.name == record.name && s.record_type == rt) {
+            Some(set) => set.records.push(record),
+            None => sets.push(Rrset {
+                name: record.name.clone(),
+                record_type: rt,
+                records: vec![record],
+                sigs: Vec::new(),
+            }),
+        }
+    }
+    for record in records {
+        if let RData::DNSSEC(DNSSECRData::RRSIG(sig)) = &record.data {
+            let covered = sig.input().type_covered;
+            if let Some(set) = sets.iter_mut().find(|s| s.name == record.name && s.record_type == covered) {
+                set.sigs.push(sig);
+            }
+        }
+    }
+    sets
+}
+
+struct Fetched {
+    rcode: ResponseCode,
+    answers: Vec<Record>,
+    authorities: Vec<Record>,
+}
+
+struct Validator<'a> {
+    client: &'a DnsClient,
+    anchors: TrustAnchors,
+    now: u32,
+    zones: HashMap<Name, ZoneCheck>,
+    cache: HashMap<(Name, RecordType), Arc<Fetched>>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(client: &'a DnsClient, anchors: TrustAnchors) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as u32);
+        Self {
+            client,
+            anchors,
+            now,
+            zones: HashMap::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    async fn fetch(&mut self, name: &Name, rt: RecordType) -> Result<Arc<Fetched>, String> {
+        let key = (name.clone(), rt);
+        if let Some(fetched) = self.cache.get(&key) {
+            return Ok(fetched.clone());
+        }
+        let exchange = self.client.query(name, rt).await.map_err(|e| e.to_string())?;
+        let fetched = Arc::new(Fetched {
+            rcode: exchange.response.metadata.response_code,
+            answers: exchange.response.answers.clone(),
+            authorities: exchange.response.authorities.clone(),
+        });
+        self.cache.insert(key, fetched.clone());
+        Ok(fetched)
+    }
+
+    async fn check_rrset(&mut self, set: &Rrset<'_>) -> RrsetCheck {
+        let (status, reason, signatures) = match set.sigs.first().map(|s| s.input().signer_name.clone()) {
+            Some(signer) if !signer.zone_of(&set.name) => (
+                Security::Bogus,
+                Some(format!("签名者 {signer} 不是 {} 的上级区", set.name)),
+                Vec::new(),
+            ),
+            Some(signer) => match self.zone(&signer).await {
+                Security::Secure => {
+                    let keys = self.zones[&signer].keys.clone();
+                    let signatures = self.verify(set, &signer, &keys);
+                    if signatures.iter().any(|s| s.valid) {
+                        (Security::Secure, None, signatures)
+                    } else {
+                        (Security::Bogus, Some("没有有效的 RRSIG".to_string()), signatures)
+                    }
+                }
+                other => (other, Some(chain_reason(&signer, other)), Vec::new()),
+            },
+            None => match self.enclosing_zone(&set.name).await {
+                Some(zone) => match self.zone(&zone).await {
+                    Security::Secure => (Security::Bogus, Some(format!("{zone} 已签名，但该 RRset 没有 RRSIG")), Vec::new()),
+                    other => (other, Some(chain_reason(&zone, other)), Vec::new()),
+                },
+                None => (Security::Indeterminate, Some("无法确定所在区".to_string()), Vec::new()),
+            },
+        };
+        RrsetCheck {
+            name: set.name.to_string(),
+            record_type: set.record_type.to_string(),
+            status,
+            reason,
+            signatures,
+        }
+    }
+
+    /// 通过 SOA 查询找到名称所在的区（区顶点）。
+    async fn enclosing_zone(&mut self, name: &Name) -> Option<Name> {
+        let fetched = self.fetch(name, RecordType::SOA).await.ok()?;
+        fetched
+            .answers
+            .iter()
+            .chain(&fetched.authorities)
+            .find(|r| r.record_type() == RecordType::SOA && r.name.zone_of(name))
+            .map(|r| r.name.clone())
+    }
+
+    /// 确定区的安全状态并缓存；上级区尚未验证时先入栈处理上级区。
+    async fn zone(&mut self, zone: &Name) -> Security {
+        let mut stack = vec![zone.clone()];
+        while let Some(current) = stack.last().cloned() {
+            if self.zones.contains_key(&current) {
+                stack.pop();
+                continue;
+            }
+            if stack.len() > MAX_ZONES {
+                self.zones.insert(current.clone(), ZoneCheck::new(&current, Security::Indeterminate, "信任链过长"));
+                stack.pop();
+                continue;
+            }
+            match self.evaluate(&current).await {
+                Ok(check) => {
+                    self.zones.insert(current, check);
+                    stack.pop();
+                }
+                Err(parent) => stack.push(parent),
+            }
+        }
+        self.zones[zone].status
+    }
+
+    /// 验证一个区；返回 `Err(上级区)` 表示需要先验证上级区。
+    async fn evaluate(&mut self, zone: &Name) -> Result<ZoneCheck, Name> {
+        if zone.is_root() {
+            return Ok(self.root().await);
+        }
+        let ds = match self.fetch(zone, RecordType::DS).await {
+            Ok(fetched) => fetched,
+            Err(e) => return Ok(ZoneCheck::new(zone, Security::Indeterminate, format!("查询 DS 失败: {e}"))),
+        };
+        let parent = parent_zone(zone, &ds);
+        let Some(parent_check) = self.zones.get(&parent) else {
+            return Err(parent);
+        };
+        match parent_check.status {
+            Security::Secure => {}
+            Security::Bogus => return Ok(ZoneCheck::new(zone, Security::Bogus, format!("上级区 {parent} 验证失败"))),
+            Security::Insecure => return Ok(ZoneCheck::new(zone, Security::Insecure, format!("上级区 {parent} 未受 DNSSEC 保护"))),
+            Security::Indeterminate => {
+                return Ok(ZoneCheck::new(zone, Security::Indeterminate, format!("上级区 {parent} 无法验证")))
+            }
+        }
+        let parent_keys = parent_check.keys.clone();
+        let mut check = ZoneCheck::new(zone, Security::Secure, "");
+
+        let sets = group(&ds.answers);
+        let Some(ds_set) = sets.iter().find(|s| s.record_type == RecordType::DS && s.name == *zone) else {
+            // 没有 DS：父区须给出经签名的否定证明，此后为不安全委派
+            if !matches!(ds.rcode, ResponseCode::NoError | ResponseCode::NXDomain) {
+                check.fail(Security::Indeterminate, format!("查询 DS 返回 {}", rcode_name(ds.rcode)));
+                return Ok(check);
+            }
+            let proofs: Vec<Rrset<'_>> = group(&ds.authorities)
+                .into_iter()
+                .filter(|s| matches!(s.record_type, RecordType::NSEC | RecordType::NSEC3))
+                .collect();
+            let mut signed = !proofs.is_empty();
+            for set in &proofs {
+                let signatures = self.verify(set, &parent, &parent_keys);
+                signed &= signatures.iter().any(|s| s.valid);
+                check.ds_signatures.extend(signatures);
+            }
+            let applies = Denial::new(&parent, proofs.iter().flat_map(|s| s.records.iter().copied())).no_ds(zone);
+            if !signed {
+                check.fail(Security::Bogus, format!("父区 {parent} 没有 DS 记录，且缺少有效签名的否定证明"));
+            } else if !applies {
+                check.fail(
+                    Security::Indeterminate,
+                    format!("父区 {parent} 的 NSEC / NSEC3 不能证明 {zone} 是没有 DS 的委派"),
+                );
+            } else {
+                check.fail(Security::Insecure, format!("父区 {parent} 没有 DS 记录（否定证明有效），为不安全委派"));
+            }
+            return Ok(check);
+        };
+
+        check.ds_signatures = self.verify(ds_set, &parent, &parent_keys);
+        let ds_records: Vec<&DS> = ds_set
+            .records
+            .iter()
+            .filter_map(|r| match &r.data {
+                RData::DNSSEC(DNSSECRData::DS(ds)) => Some(ds),
+                _ => None,
+            })
+            .collect();
+        if !check.ds_signatures.iter().any(|s| s.valid) {
+            check.ds = ds_info(zone, &ds_records, &[]);
+            check.fail(Security::Bogus, "DS 记录的签名无效");
+            return Ok(check);
+        }
+        if !ds_records
+            .iter()
+            .any(|d| d.algorithm().is_supported() && d.digest_type().is_supported())
+        {
+            check.ds = ds_info(zone, &ds_records, &[]);
+            check.fail(Security::Insecure, "DS 使用的算法均不受支持，按不安全委派处理");
+            return Ok(check);
+        }
+
+        let fetched = match self.fetch(zone, RecordType::DNSKEY).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                check.fail(Security::Indeterminate, format!("查询 DNSKEY 失败: {e}"));
+                return Ok(check);
+            }
+        };
+        let keys = dnskeys(zone, &fetched);
+        check.ds = ds_info(zone, &ds_records, &keys);
+        self.check_keys(&mut check, zone, &fetched, |key| {
+            ds_records.iter().any(|d| d.covers(zone, key).unwrap_or(false))
+        });
+        Ok(check)
+    }
+
+    /// 根区：DNSKEY 须与内置信任锚一致。
+    async fn root(&mut self) -> ZoneCheck {
+        let root = Name::root();
+        let mut check = ZoneCheck::new(&root, Security::Secure, "");
+        match self.fetch(&root, RecordType::DNSKEY).await {
+            Ok(fetched) => {
+                let anchors = std::mem::take(&mut self.anchors);
+                self.check_keys(&mut check, &root, &fetched, |key| anchors.contains(key.public_key()));
+                self.anchors = anchors;
+            }
+            Err(e) => check.fail(Security::Indeterminate, format!("查询根区 DNSKEY 失败: {e}")),
+        }
+        check
+    }
+
+    /// DNSKEY 集合须由受信任（被 DS 指向或与信任锚一致）的密钥签名。
+    fn check_keys(&self, check: &mut ZoneCheck, zone: &Name, fetched: &Fetched, trusted: impl Fn(&DNSKEY) -> bool) {
+        let sets = group(&fetched.answers);
+        let Some(key_set) = sets.iter().find(|s| s.record_type == RecordType::DNSKEY && s.name == *zone) else {
+            check.fail(Security::Bogus, format!("{zone} 没有 DNSKEY 记录"));
+            return;
+        };
+        let keys = dnskeys(zone, fetched);
+        let trusted_tags: Vec<u16> = keys
+            .iter()
+            .filter(|k| trusted(k))
+            .filter_map(|k| k.calculate_key_tag().ok())
+            .collect();
+        check.dnskeys = keys
+            .iter()
+            .map(|key| {
+                let algorithm = key.public_key().algorithm();
+                KeyInfo {
+                    key_tag: key.calculate_key_tag().ok(),
+                    algorithm: algorithm.into(),
+                    algorithm_name: algorithm.to_string(),
+                    flags: key.flags(),
+                    role: if key.secure_entry_point() { "KSK" } else { "ZSK" },
+                    trusted: trusted(key),
+                    revoked: key.revoke(),
+                }
+            })
+            .collect();
+        check.dnskey_signatures = self.verify(key_set, zone, &keys);
+        if trusted_tags.is_empty() {
+            let reason = if zone.is_root() {
+                "根区 DNSKEY 与内置信任锚不一致"
+            } else {
+                "没有与 DS 匹配的 DNSKEY"
+            };
+            check.fail(Security::Bogus, reason);
+        } else if !check
+            .dnskey_signatures
+            .iter()
+            .any(|s| s.valid && trusted_tags.contains(&s.key_tag))
+        {
+            check.fail(Security::Bogus, "DNSKEY 集合没有受信任密钥的有效签名");
+        } else {
+            check.keys = keys.into_iter().filter(|k| k.zone_key() && !k.revoke()).collect();
+        }
+    }
+
+    /// 逐个校验 RRset 的签名（签名者须为 `zone`）。
+    fn verify(&self, set: &Rrset<'_>, zone: &Name, keys: &[DNSKEY]) -> Vec<SignatureCheck> {
+        set.sigs
+            .iter()
+            .map(|sig| {
+                let input = sig.input();
+                let inception = input.sig_inception.get();
+                let expiration = input.sig_expiration.get();
+                let error = if input.signer_name != *zone {
+                    Some(format!("签名者 {} 不是 {zone}", input.signer_name))
+                } else if !input.algorithm.is_supported() {
+                    Some(format!("不支持的算法 {}", input.algorithm))
+                } else if (self.now.wrapping_sub(inception) as i32) < 0 {
+                    Some("签名尚未生效".to_string())
+                } else if (expiration.wrapping_sub(self.now) as i32) < 0 {
+                    Some("签名已过期".to_string())
+                } else {
+                    let mut candidates = keys.iter().filter(|k| {
+                        k.public_key().algorithm() == input.algorithm && k.calculate_key_tag().ok() == Some(input.key_tag)
+                    });
+                    match candidates.next() {
+                        None => Some(format!("找不到密钥标签为 {} 的 DNSKEY", input.key_tag)),
+                        Some(first) => std::iter::once(first)
+                            .chain(candidates)
+                            .all(|key| {
+                                key.verify_rrsig(&set.name, DNSClass::IN, sig, set.records.iter().copied())
+                                    .is_err()
+                            })
+                            .then(|| "签名校验失败".to_string()),
+                    }
+                };
+                SignatureCheck {
+                    key_tag: input.key_tag,
+                    algorithm: input.algorithm.into(),
+                    algorithm_name: input.algorithm.to_string(),
+                    signer: input.signer_name.to_string(),
+                    inception: utc(inception),
+                    expiration: utc(expiration),
+                    expires_in_days: i64::from(expiration.wrapping_sub(self.now) as i32) / 86_400,
+                    valid: error.is_none(),
+                    error,
+                }
+            })
+            .collect()
+    }
+}
+
+/// 区未能验证为安全时，RRset 结果的说明。
+fn chain_reason(zone: &Name, status: Security) -> String {
+    match status {
+        Security::Insecure => format!("所在区 {zone} 未受 DNSSEC 保护"),
+        Security::Bogus => format!("所在区 {zone} 的信任链验证失败"),
+        _ => format!("无法验证所在区 {zone} 的信任链"),
+    }
+}
+
+/// 否定证明：某个区内经过签名验证的 NSEC / NSEC3 记录（RFC 4035 §5.4、RFC 5155 §8）。
+struct Denial<'r> {
+    zone: Name,
+    nsec: Vec<(&'r Name, &'r NSEC)>,
+    /// NSEC3 所有者的哈希标签（小写 base32hex）
+    nsec3: Vec<(String, &'r NSEC3)>,
+}
+
+impl<'r> Denial<'r> {
+    /// 只收集所有者在 `zone` 内的 NSEC 与所有者为 `<哈希>.zone` 的 NSEC3。
+    fn new(zone: &Name, records: impl IntoIterator<Item = &'r Record>) -> Self {
+        let mut denial = Self { zone: zone.clone(), nsec: Vec::new(), nsec3: Vec::new() };
+        for record in records {
+            match &record.data {
+                RData::DNSSEC(DNSSECRData::NSEC(nsec)) if zone.zone_of(&record.name) => {
+                    denial.nsec.push((&record.name, nsec));
+                }
+                RData::DNSSEC(DNSSECRData::NSEC3(nsec3)) if record.name.base_name() == *zone => {
+                    if let Some(label) = record.name.iter().next() {
+                        denial.nsec3.push((String::from_utf8_lossy(label).to_ascii_lowercase(), nsec3));
+                    }
+                }
+                _ => {}
+            }
+        }
+        denial
+    }
+
+    /// `child` 是没有 DS 的委派：同名记录的类型位图有 NS、没有 DS 与 SOA，
+    /// 或 NSEC3 最近祖先证明中覆盖下一个更近名称的记录设置了 opt-out。
+    fn no_ds(&self, child: &Name) -> bool {
+        match self.matching(child) {
+            Some(types) => {
+                types.contains(RecordType::NS) && !types.contains(RecordType::DS) && !types.contains(RecordType::SOA)
+            }
+            None => self.closest_encloser(child).is_some_and(|(_, opt_out)| opt_out),
+        }
+    }
+
+    /// NODATA：`name` 存在但没有 `rt` 与 CNAME 记录，或名称不存在而匹配的通配符没有该类型；
+    /// DS 查询还接受 opt-out 覆盖。父区一侧的委派记录不能证明子区数据不存在。
+    fn no_data(&self, name: &Name, rt: RecordType) -> bool {
+        let empty = |types: &RecordTypeSet| {
+            !types.contains(rt)
+                && !types.contains(RecordType::CNAME)
+                && (rt == RecordType::DS || !types.contains(RecordType::NS) || types.contains(RecordType::SOA))
+        };
+        if let Some(types) = self.matching(name) {
+            return empty(types);
+        }
+        let Some((encloser, opt_out)) = self.closest_encloser(name) else {
+            return false;
+        };
+        (rt == RecordType::DS && opt_out) || wildcard(&encloser).and_then(|w| self.matching(&w)).is_some_and(empty)
+    }
+
+    /// NXDOMAIN：`name` 不存在，且最近祖先下的通配符也不存在。
+    fn no_name(&self, name: &Name) -> bool {
+        self.closest_encloser(name)
+            .and_then(|(encloser, _)| wildcard(&encloser))
+            .is_some_and(|w| self.covers(&w))
+    }
+
+    /// 与 `name` 同名的 NSEC / NSEC3 的类型位图。
+    fn matching(&self, name: &Name) -> Option<&'r RecordTypeSet> {
+        let nsec = self.nsec.iter().find(|(owner, _)| *owner == name).map(|(_, nsec)| nsec.type_set());
+        nsec.or_else(|| {
+            self.nsec3
+                .iter()
+                .find(|(label, nsec3)| nsec3_hash(nsec3, name).is_some_and(|h| h == *label))
+                .map(|(_, nsec3)| nsec3.type_set())
+        })
+    }
+
+    /// 有记录的区间覆盖 `name`，即证明该名称不存在。
+    fn covers(&self, name: &Name) -> bool {
+        self.nsec.iter().any(|(owner, nsec)| nsec_covers(owner, nsec, name))
+            || self.nsec3.iter().any(|(label, nsec3)| nsec3_covers(label, nsec3, name))
+    }
+
+    /// 最近祖先证明：返回 `name` 最近的存在的祖先，以及覆盖下一个更近名称的 NSEC3 是否 opt-out。
+    /// NSEC 由覆盖 `name` 的记录推出：所有者与下一个名称中与 `name` 共同祖先较长者。
+    fn closest_encloser(&self, name: &Name) -> Option<(Name, bool)> {
+        if let Some((owner, nsec)) = self.nsec.iter().find(|(owner, nsec)| nsec_covers(owner, nsec, name)) {
+            let (a, b) = (common_ancestor(name, owner), common_ancestor(name, nsec.next_domain_name()));
+            return Some((if a.num_labels() >= b.num_labels() { a } else { b }, false));
+        }
+        let mut candidate = name.base_name();
+        while self.zone.zone_of(&candidate) {
+            if self.nsec3.iter().any(|(label, nsec3)| nsec3_hash(nsec3, &candidate).is_some_and(|h| h == *label)) {
+                let next_closer = name.trim_to(usize::from(candidate.num_labels()) + 1);
+                return self
+                    .nsec3
+                    .iter()
+                    .find(|(label, nsec3)| nsec3_covers(label, nsec3, &next_closer))
+                    .map(|(_, nsec3)| (candidate, nsec3.opt_out()));
+            }
+            if candidate.is_root() {
+                break;
+            }
+            candidate = candidate.base_name();
+        }
+        None
+    }
+}
+
+/// NSEC 区间 (所有者, 下一个名称) 覆盖 `name`；区内最后一条的下一个名称回绕到区顶点。
+/// 委派点（有 NS 无 SOA）与 DNAME 的记录不能证明其下的名称不存在。
+fn nsec_covers(owner: &Name, nsec: &NSEC, name: &Name) -> bool {
+    let types = nsec.type_set();
+    // DNAME（类型 39）hickory 没有对应的枚举值
+    let cut = (types.contains(RecordType::NS) && !types.contains(RecordType::SOA)) || types.contains(RecordType::from(39));
+    if cut && owner.zone_of(name) {
+        return false;
+    }
+    let next = nsec.next_domain_name();
+    if owner < next {
+        owner < name && name < next
+    } else {
+        owner < name || name < next
+    }
+}
+
+fn nsec3_covers(label: &str, nsec3: &NSEC3, name: &Name) -> bool {
+    let Some(hashed) = nsec3_hash(nsec3, name) else {
+        return false;
+    };
+    // base32hex 保持字节序，可直接比较编码后的文本
+    let next = base32hex(nsec3.next_hashed_owner_name());
+    if label < next.as_str() {
+        label < hashed.as_str() && hashed < next
+    } else {
+        label < hashed.as_str() || hashed < next
+    }
+}
+
+/// 按 NSEC3 记录的参数计算名称的哈希标签。
+fn nsec3_hash(nsec3: &NSEC3, name: &Name) -> Option<String> {
+    let hash = nsec3.hash_algorithm().hash(nsec3.salt(), name, nsec3.iterations()).ok()?;
+    Some(base32hex(hash.as_ref()))
+}
+
+/// 两个名称从右数相同的标签构成的共同祖先。
+fn common_ancestor(a: &Name, b: &Name) -> Name {
+    let shared = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x.eq_ignore_ascii_case(y)).count();
+    a.trim_to(shared)
+}
+
+fn wildcard(encloser: &Name) -> Option<Name> {
+    Name::from_ascii("*").ok()?.append_domain(encloser).ok()
+}
+
+/// NSEC3 所有者名使用的 base32hex 编码（小写、无填充）。
+fn base32hex(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &b in bytes {
+        buffer = (buffer << 8) | u32::from(b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(char::from(ALPHABET[((buffer >> bits) & 31) as usize]));
+        }
+    }
+    if bits > 0 {
+        out.push(char::from(ALPHABET[((buffer << (5 - bits)) & 31) as usize]));
+    }
+    out
+}
+
+/// DS 应答中 RRSIG 的签名者或权威区 SOA 的所有者即为父区，取不到时退回上一级名称。
+fn parent_zone(zone: &Name, ds: &Fetched) -> Name {
+    let signer = ds.answers.iter().chain(&ds.authorities).find_map(|r| match &r.data {
+        RData::DNSSEC(DNSSECRData::RRSIG(sig)) => Some(sig.input().signer_name.clone()),
+        _ => None,
+    });
+    let soa = || {
+        ds.authorities
+            .iter()
+            .find(|r| r.record_type() == RecordType::SOA)
+            .map(|r| r.name.clone())
+    };
+    signer
+        .or_else(soa)
+        .filter(|p| p.zone_of(zone) && p != zone)
+        .unwrap_or_else(|| zone.base_name())
+}
+
+fn dnskeys(zone: &Name, fetched: &Fetched) -> Vec<DNSKEY> {
+    fetched
+        .answers
+        .iter()
+        .filter(|r| r.name == *zone)
+        .filter_map(|r| match &r.data {
+            RData::DNSSEC(DNSSECRData::DNSKEY(key)) => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ds_info(zone: &Name, ds_records: &[&DS], keys: &[DNSKEY]) -> Vec<DsInfo> {
+    ds_records
+        .iter()
+        .map(|ds| DsInfo {
+            key_tag: ds.key_tag(),
+            algorithm: ds.algorithm().into(),
+            algorithm_name: ds.algorithm().to_string(),
+            digest_type: ds.digest_type().into(),
+            matched: keys.iter().any(|key| ds.covers(zone, key).unwrap_or(false)),
+        })
+        .collect()
+}
+
+/// Unix 时间戳转为 UTC 的 RFC 3339 文本（按公历换算年月日）。
+fn utc(timestamp: u32) -> String {
+    let secs = i64::from(timestamp);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    use hickory_resolver::proto::dnssec::crypto::Ed25519SigningKey;
+    use hickory_resolver::proto::dnssec::rdata::SigInput;
+    use hickory_resolver::proto::dnssec::{Algorithm, DigestType, Nsec3HashAlgorithm, SigningKey, TBS};
+    use hickory_resolver::proto::rr::rdata::{A, SOA};
+    use hickory_resolver::proto::rr::SerialNumber;
+
+    use crate::handlers::dns::stub::{self, Stub};
+    use crate::handlers::dns::ResolverSettings;
+
+    fn name(s: &str) -> Name {
+        Name::from_ascii(s).unwrap()
+    }
+
+    fn nsec(owner: &str, next: &str, types: &[RecordType]) -> Record {
+        let rdata = NSEC::new(name(next), types.iter().copied());
+        Record::from_rdata(name(owner), 300, RData::DNSSEC(DNSSECRData::NSEC(rdata)))
+    }
+
+    fn nsec3(owner: &str, next: Vec<u8>, opt_out: bool, types: &[RecordType]) -> Record {
+        let salt = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        let rdata = NSEC3::new(Nsec3HashAlgorithm::SHA1, opt_out, 12, salt, next, types.iter().copied());
+        Record::from_rdata(name(owner), 300, RData::DNSSEC(DNSSECRData::NSEC3(rdata)))
+    }
+
+    fn hashed(zone: &str) -> String {
+        let hash = Nsec3HashAlgorithm::SHA1.hash(&[0xaa, 0xbb, 0xcc, 0xdd], &name(zone), 12).unwrap();
+        base32hex(hash.as_ref())
+    }
+
+    fn denial<'r>(zone: &str, records: &'r [Record]) -> Denial<'r> {
+        Denial::new(&name(zone), records)
+    }
+
+    #[test]
+    fn base32hex_matches_rfc_vectors() {
+        assert_eq!(base32hex(b""), "");
+        assert_eq!(base32hex(b"f"), "co");
+        assert_eq!(base32hex(b"foobar"), "cpnmuoj1e8");
+        // RFC 5155 附录 A
+        assert_eq!(hashed("example."), "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom");
+        assert_eq!(hashed("a.example."), "35mthgpgcu1qg68fab165klnsnk3dpvl");
+    }
+
+    #[test]
+    fn nsec_at_delegation_without_ds() {
+        let zone = name("child.example.");
+        let ns = [RecordType::NS, RecordType::RRSIG, RecordType::NSEC];
+        assert!(denial("example.", &[nsec("child.example.", "d.example.", &ns)]).no_ds(&zone));
+        let with_ds = [RecordType::NS, RecordType::DS, RecordType::RRSIG, RecordType::NSEC];
+        assert!(!denial("example.", &[nsec("child.example.", "d.example.", &with_ds)]).no_ds(&zone));
+        // 子区顶点的 NSEC（带 SOA）不能证明父区没有 DS
+        let apex = [RecordType::SOA, RecordType::NS, RecordType::RRSIG, RecordType::NSEC];
+        assert!(!denial("example.", &[nsec("child.example.", "a.child.example.", &apex)]).no_ds(&zone));
+        // 没有 NS 位不是委派
+        let plain = [RecordType::A, RecordType::RRSIG, RecordType::NSEC];
+        assert!(!denial("example.", &[nsec("child.example.", "d.example.", &plain)]).no_ds(&zone));
+        // 覆盖 zone 的 NSEC 只说明名称不存在，不能证明委派
+        assert!(!denial("example.", &[nsec("b.example.", "d.example.", &plain)]).no_ds(&zone));
+        // 所有者不在父区内
+        assert!(!denial("example.", &[nsec("child.other.", "d.other.", &ns)]).no_ds(&zone));
+    }
+
+    #[test]
+    fn nsec_name_and_data_denials() {
+        let types = [RecordType::A, RecordType::RRSIG, RecordType::NSEC];
+        let covering = nsec("b.example.", "d.example.", &types);
+        // 区顶点之后紧接 *.example.，该区间证明通配符不存在
+        let no_wildcard = nsec("example.", "a.example.", &[RecordType::SOA, RecordType::NS]);
+        let records = [covering.clone(), no_wildcard];
+        assert!(denial("example.", &records).no_name(&name("c.example.")));
+        assert!(!denial("example.", &records).no_name(&name("x.example.")));
+        assert!(!denial("example.", &[covering]).no_name(&name("c.example.")));
+        // 区内最后一条 NSEC 回绕到区顶点
+        let last = [nsec("x.example.", "example.", &types), nsec("example.", "a.example.", &[RecordType::SOA])];
+        assert!(denial("example.", &last).no_name(&name("y.example.")));
+        // 委派点的 NSEC 不能证明其下的名称不存在
+        let cut = [nsec("sub.example.", "z.example.", &[RecordType::NS]), nsec("example.", "a.example.", &[])];
+        assert!(!denial("example.", &cut).no_name(&name("x.sub.example.")));
+
+        let www = [nsec("www.example.", "z.example.", &types)];
+        assert!(denial("example.", &www).no_data(&name("www.example."), RecordType::TXT));
+        assert!(!denial("example.", &www).no_data(&name("www.example."), RecordType::A));
+        assert!(!denial("example.", &www).no_data(&name("other.example."), RecordType::TXT));
+        let alias = [nsec("www.example.", "z.example.", &[RecordType::CNAME])];
+        assert!(!denial("example.", &alias).no_data(&name("www.example."), RecordType::TXT));
+    }
+
+    #[test]
+    fn nsec3_matching_and_opt_out() {
+        let zone = name("a.example.");
+        let owner = format!("{}.example.", hashed("a.example."));
+        let ns = [RecordType::NS, RecordType::RRSIG];
+        assert!(denial("example.", &[nsec3(&owner, vec![0xff; 20], false, &ns)]).no_ds(&zone));
+        let with_ds = [RecordType::NS, RecordType::DS, RecordType::RRSIG];
+        assert!(!denial("example.", &[nsec3(&owner, vec![0xff; 20], false, &with_ds)]).no_ds(&zone));
+        // 其他区的 NSEC3 不算
+        let foreign = format!("{}.other.", hashed("a.example."));
+        assert!(!denial("example.", &[nsec3(&foreign, vec![0xff; 20], false, &ns)]).no_ds(&zone));
+
+        // 最近祖先证明：区顶点的 NSEC3 匹配，覆盖下一个更近名称的区间须设置 opt-out
+        // 区顶点的区间（0p9… ~ 20g…）不覆盖 a.example. 的哈希 35m…
+        let apex = nsec3(&format!("{}.example.", hashed("example.")), vec![0x10; 20], false, &[RecordType::SOA]);
+        let low = format!("{}.example.", "0".repeat(32));
+        let cover = |opt_out| nsec3(&low, vec![0xff; 20], opt_out, &[]);
+        assert!(denial("example.", &[apex.clone(), cover(true)]).no_ds(&zone));
+        assert!(!denial("example.", &[apex.clone(), cover(false)]).no_ds(&zone));
+        assert!(!denial("example.", &[cover(true)]).no_ds(&zone));
+        let mut next = vec![0x00; 20];
+        next[19] = 1;
+        assert!(!denial("example.", &[apex.clone(), nsec3(&low, next, true, &[])]).no_ds(&zone));
+
+        // NXDOMAIN 还须覆盖 *.example.
+        let records = [apex, cover(false)];
+        assert!(denial("example.", &records).no_name(&name("b.c.example.")));
+        assert!(!denial("example.", &records).no_name(&name("example.")));
+    }
+
+    /// 测试用的签名区：KSK 与 ZSK 共用一把 Ed25519 密钥。
+    struct Zone {
+        name: Name,
+        key: Ed25519SigningKey,
+        dnskey: DNSKEY,
+    }
+
+    impl Zone {
+        fn new(zone: &str) -> Self {
+            let key = Ed25519SigningKey::from_pkcs8(&Ed25519SigningKey::generate_pkcs8().unwrap()).unwrap();
+            let dnskey = DNSKEY::from_key(&key.to_public_key().unwrap());
+            Self { name: name(zone), key, dnskey }
+        }
+
+        /// 在记录后附上覆盖它们的 RRSIG（有效期为当前时间前后一小时）。
+        fn signed(&self, mut records: Vec<Record>) -> Vec<Record> {
+            let first = &records[0];
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+            let input = SigInput {
+                type_covered: first.record_type(),
+                algorithm: Algorithm::ED25519,
+                num_labels: first.name.num_labels(),
+                original_ttl: first.ttl,
+                sig_expiration: SerialNumber::new(now + 3600),
+                sig_inception: SerialNumber::new(now - 3600),
+                key_tag: self.dnskey.calculate_key_tag().unwrap(),
+                signer_name: self.name.clone(),
+            };
+            let tbs = TBS::from_input(&first.name, DNSClass::IN, &input, records.iter()).unwrap();
+            let sig = RRSIG::from_sig(input, self.key.sign(&tbs).unwrap());
+            let owner = first.name.clone();
+            records.push(Record::from_rdata(owner, 300, RData::DNSSEC(DNSSECRData::RRSIG(sig))));
+            records
+        }
+
+        fn dnskey(&self) -> Vec<Record> {
+            let key = Record::from_rdata(self.name.clone(), 300, RData::DNSSEC(DNSSECRData::DNSKEY(self.dnskey.clone())));
+            self.signed(vec![key])
+        }
+
+        fn ds(&self) -> Record {
+            let ds = DS::from_key(self.dnskey.public_key(), &self.name, DigestType::SHA256).unwrap();
+            Record::from_rdata(self.name.clone(), 300, RData::DNSSEC(DNSSECRData::DS(ds)))
+        }
+    }
+
+    fn soa(zone: &str) -> Record {
+        let soa = SOA::new(name(zone), name("hostmaster.test."), 1, 3600, 600, 86400, 300);
+        Record::from_rdata(name(zone), 300, RData::SOA(soa))
+    }
+
+    fn a(host: &str) -> Record {
+        Record::from_rdata(name(host), 300, RData::A(A(Ipv4Addr::new(192, 0, 2, 1))))
+    }
+
+    /// 桩服务器的应答：(rcode, 应答记录, 权威记录)。
+    type Answer = (ResponseCode, Vec<Record>, Vec<Record>);
+
+    /// 已签名的根与 example.，以及没有 DS 的委派 insecure.example.；example. 的 NSEC 链为
+    /// example. → insecure.example. → www.example. → example.。
+    /// 桩服务器按 (名称, 类型) 查表应答，模拟递归解析器。
+    fn zones() -> (TrustAnchors, HashMap<(Name, RecordType), Answer>) {
+        let (root, example) = (Zone::new("."), Zone::new("example."));
+        let mut anchors = TrustAnchors::empty();
+        anchors.insert(root.dnskey.public_key());
+
+        let apex_types = [RecordType::SOA, RecordType::NS, RecordType::DNSKEY, RecordType::RRSIG, RecordType::NSEC];
+        let apex_nsec = example.signed(vec![nsec("example.", "insecure.example.", &apex_types)]);
+        let cut_nsec = example.signed(vec![nsec(
+            "insecure.example.",
+            "www.example.",
+            &[RecordType::NS, RecordType::RRSIG, RecordType::NSEC],
+        )]);
+        let www_nsec = example.signed(vec![nsec("www.example.", "example.", &[RecordType::A, RecordType::RRSIG, RecordType::NSEC])]);
+        let example_soa = example.signed(vec![soa("example.")]);
+        let negative = |proofs: &[&Vec<Record>]| -> Vec<Record> {
+            example_soa.iter().chain(proofs.iter().flat_map(|p| p.iter())).cloned().collect()
+        };
+
+        let mut table = HashMap::new();
+        let mut add = |qname: &str, qtype, answer: Answer| {
+            table.insert((name(qname), qtype), answer);
+        };
+        let ok = ResponseCode::NoError;
+        add(".", RecordType::DNSKEY, (ok, root.dnskey(), vec![]));
+        add("example.", RecordType::DS, (ok, root.signed(vec![example.ds()]), vec![]));
+        add("example.", RecordType::DNSKEY, (ok, example.dnskey(), vec![]));
+        add("www.example.", RecordType::A, (ok, example.signed(vec![a("www.example.")]), vec![]));
+        add("www.example.", RecordType::TXT, (ok, vec![], negative(&[&www_nsec])));
+        add("nope.example.", RecordType::A, (ResponseCode::NXDomain, vec![], negative(&[&cut_nsec, &apex_nsec])));
+        // zzz.example. 在 www.example. 之后，cut_nsec 覆盖不到它
+        add("zzz.example.", RecordType::A, (ResponseCode::NXDomain, vec![], negative(&[&cut_nsec, &apex_nsec])));
+        add("insecure.example.", RecordType::DS, (ok, vec![], negative(&[&cut_nsec])));
+        add("host.insecure.example.", RecordType::A, (ok, vec![a("host.insecure.example.")], vec![]));
+        add("host.insecure.example.", RecordType::SOA, (ok, vec![], vec![soa("insecure.example.")]));
+        (anchors, table)
+    }
+
+    async fn report(qname: &str, rt: RecordType) -> DnssecReport {
+        let (anchors, table) = zones();
+        let stub = Stub::start(&[1], move |_, _, request| {
+            let query = &request.queries[0];
+            let mut reply = stub::reply(request);
+            match table.get(&(query.name().clone(), query.query_type())) {
+                Some((code, answers, authorities)) => {
+                    reply.metadata.response_code = *code;
+                    reply.add_answers(answers.iter().cloned());
+                    reply.add_authorities(authorities.iter().cloned());
+                }
+                None => reply.metadata.response_code = ResponseCode::ServFail,
+            }
+            Some(reply)
+        })
+        .await;
+        let settings = ResolverSettings { nameservers: vec![stub.addr(1).to_string()], ..Default::default() };
+        let client = DnsClient::new(&settings).unwrap_or_else(|e| panic!("{}", e.message));
+        let name = name(qname);
+        let exchange = client.query(&name, rt).await.unwrap_or_else(|e| panic!("{e}"));
+        validate(&client, &name, rt, &exchange.response, anchors).await
+    }
+
+    fn reasons(report: &DnssecReport) -> Vec<String> {
+        let rrsets = report.rrsets.iter().filter_map(|r| r.reason.clone());
+        rrsets.chain(report.chain.iter().filter_map(|z| z.reason.clone())).collect()
+    }
+
+    #[tokio::test]
+    async fn signed_answers_and_proven_denials_are_secure() {
+        for (qname, rt) in [
+            ("www.example.", RecordType::A),
+            ("www.example.", RecordType::TXT),
+            ("nope.example.", RecordType::A),
+        ] {
+            let report = report(qname, rt).await;
+            assert!(report.status == Security::Secure, "{qname} {rt}: {:?}", reasons(&report));
+        }
+    }
+
+    #[tokio::test]
+    async fn delegation_without_ds_is_insecure() {
+        let report = report("host.insecure.example.", RecordType::A).await;
+        assert!(report.status == Security::Insecure, "{:?}", reasons(&report));
+        let zone = report.chain.iter().find(|z| z.zone == "insecure.example.").unwrap();
+        assert!(zone.status == Security::Insecure);
+    }
+
+    #[tokio::test]
+    async fn signed_nxdomain_without_covering_nsec_is_bogus() {
+        let report = report("zzz.example.", RecordType::A).await;
+        assert!(report.status == Security::Bogus, "{:?}", reasons(&report));
+        assert!(reasons(&report).iter().any(|r| r.contains("未能证明 zzz.example. 不存在")));
+    }
+}
@@ -0,0 +1,75 @@
+//! 国际化域名（IDN）：按 UTS #46 在 Unicode 与 Punycode（xn--）之间转换。
+
+use axum::Json;
+use idna::uts46::{AsciiDenyList, DnsLength, Hyphens, Uts46};
+use serde::{Deserialize, Serialize};
+
+use crate::handlers::error::AppError;
+
+/// 转为查询用的 ASCII 形式。允许下划线（`_dmarc` 等），拒绝 URL 中的分隔符与空白。
+pub(super) fn to_ascii(domain: &str) -> Result<String, AppError> {
+    Uts46::new()
+        .to_ascii(
+            domain.as_bytes(),
+            AsciiDenyList::URL,
+            Hyphens::Allow,
+            DnsLength::VerifyAllowRootDot,
+        )
+        .map(|ascii| ascii.into_owned())
+        .map_err(|_| AppError::bad_request(format!("无效的域名: {domain}（不符合 IDNA / UTS #46 规范或超出长度限制）")))
+}
+
+/// 转为展示用的 Unicode 形式；无法解码的 xn-- 标签保持原样。
+pub(super) fn to_unicode(ascii: &str) -> String {
+    let (unicode, result) = idna::domain_to_unicode(ascii);
+    if result.is_ok() {
+        unicode
+    } else {
+        ascii.to_string()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct IdnRequest {
+    /// Unicode 或 Punycode 形式的域名
+    pub domain: String,
+}
+
+#[derive(Serialize)]
+pub struct IdnResponse {
+    pub ascii: String,
+    pub unicode: String,
+    /// 是否包含需要 Punycode 编码的标签
+    pub is_idn: bool,
+    pub labels: Vec<IdnLabel>,
+}
+
+#[derive(Serialize)]
+pub struct IdnLabel {
+    pub ascii: String,
+    pub unicode: String,
+}
+
+/// IDN ↔ Punycode 互转：输入任一形式，同时返回两种形式及逐标签对照。
+pub async fn idn(Json(req): Json<IdnRequest>) -> Result<Json<IdnResponse>, AppError> {
+    let domain = req.domain.trim();
+    if domain.is_empty() {
+        return Err(AppError::bad_request("请输入域名"));
+    }
+    let ascii = to_ascii(domain)?;
+    let unicode = to_unicode(&ascii);
+    let labels: Vec<IdnLabel> = ascii
+        .trim_end_matches('.')
+        .split('.')
+        .map(|label| IdnLabel {
+            ascii: label.to_string(),
+            unicode: to_unicode(label),
+        })
+        .collect();
+    Ok(Json(IdnResponse {
+        is_idn: labels.iter().any(|l| l.ascii != l.unicode),
+        ascii,
+        unicode,
+        labels,
+    }))
+}
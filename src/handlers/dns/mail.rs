@@ -0,0 +1,1083 @@
+//! 邮件投递检查：MX、SPF（递归展开 include 并统计 DNS 查询次数）、DMARC、DKIM、MTA-STS、TLS-RPT 与 BIMI，
+//! 各项解析为结构化字段，问题汇总为带级别的发现。
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+use axum::extract::State;
+use axum::Json;
+use base64::Engine;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{Name, RData, RecordType};
+use serde::{Deserialize, Serialize};
+
+use super::client::{DnsClient, ResolverSettings};
+use super::{default_true, idn, parse_name, rcode_name, DnsConfig};
+use crate::handlers::error::AppError;
+
+/// SPF 评估的 DNS 查询次数上限（RFC 7208 4.6.4）。
+const SPF_LOOKUP_LIMIT: usize = 10;
+/// SPF 无结果查询（void lookup）的上限。
+const SPF_VOID_LIMIT: usize = 2;
+/// include / redirect 的最大展开深度。
+const SPF_MAX_DEPTH: usize = 10;
+/// 单次最多检查的 DKIM 选择器数。
+const MAX_SELECTORS: usize = 16;
+/// 检查地址解析的 MX 主机上限。
+const MAX_MX_HOSTS: usize = 8;
+
+#[derive(Deserialize)]
+pub struct MailRequest {
+    pub domain: String,
+    /// 要检查的 DKIM 选择器（如 `google`、`selector1`）
+    #[serde(default)]
+    pub dkim_selectors: Vec<String>,
+    #[serde(default = "default_bimi_selector")]
+    pub bimi_selector: String,
+    /// 使用应答缓存；修改记录后复查时设为 false
+    #[serde(default = "default_true")]
+    pub cache: bool,
+    #[serde(flatten)]
+    pub resolver: ResolverSettings,
+}
+
+fn default_bimi_selector() -> String {
+    "default".to_string()
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Serialize)]
+pub struct Finding {
+    /// mx / spf / dmarc / dkim / mta_sts / tls_rpt / bimi
+    pub check: &'static str,
+    pub level: Level,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct MailResponse {
+    pub domain: String,
+    pub domain_unicode: String,
+    pub mx: MxCheck,
+    pub spf: SpfCheck,
+    pub dmarc: TagRecord,
+    pub dkim: Vec<DkimCheck>,
+    pub mta_sts: TagRecord,
+    pub tls_rpt: TagRecord,
+    pub bimi: TagRecord,
+    /// 全部发现，错误在前
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Serialize)]
+pub struct MxCheck {
+    pub hosts: Vec<MxHost>,
+    /// 声明不接收邮件（RFC 7505 Null MX）
+    pub null_mx: bool,
+}
+
+#[derive(Serialize)]
+pub struct MxHost {
+    pub preference: u16,
+    pub exchange: String,
+}
+
+#[derive(Serialize)]
+pub struct SpfCheck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<SpfRecord>,
+    /// 展开全部 include / redirect 后计入上限的 DNS 查询次数
+    pub lookups: usize,
+    pub void_lookups: usize,
+    pub lookup_limit: usize,
+}
+
+#[derive(Serialize)]
+pub struct SpfRecord {
+    pub domain: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<String>,
+    pub terms: Vec<SpfTerm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SpfTerm {
+    /// 原始文本
+    pub term: String,
+    /// 机制名或修饰符名（小写）
+    pub mechanism: String,
+    /// pass / fail / softfail / neutral；修饰符没有限定符
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qualifier: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// 该项本身计入的 DNS 查询次数
+    pub lookups: usize,
+    /// include / redirect 展开的目标记录
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub included: Option<Box<SpfRecord>>,
+}
+
+/// `tag=value;` 形式的记录（DMARC、DKIM、MTA-STS、TLS-RPT、BIMI）。
+#[derive(Serialize)]
+pub struct TagRecord {
+    /// 查询的名称
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<String>,
+    pub tags: Vec<Tag>,
+    /// 查询失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Tag {
+    pub tag: String,
+    pub value: String,
+}
+
+#[derive(Serialize)]
+pub struct DkimCheck {
+    pub selector: String,
+    #[serde(flatten)]
+    pub record: TagRecord,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_bits: Option<usize>,
+    /// `p=` 为空表示密钥已撤销
+    pub revoked: bool,
+}
+
+impl TagRecord {
+    fn empty(name: &Name) -> Self {
+        Self {
+            name: name.to_ascii(),
+            record: None,
+            tags: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn get(&self, tag: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|t| t.tag.eq_ignore_ascii_case(tag))
+            .map(|t| t.value.as_str())
+    }
+}
+
+/// 单项检查的发现收集器。
+struct Findings {
+    check: &'static str,
+    items: Vec<Finding>,
+}
+
+impl Findings {
+    fn new(check: &'static str) -> Self {
+        Self { check, items: Vec::new() }
+    }
+
+    fn push(&mut self, level: Level, message: impl Into<String>) {
+        self.items.push(Finding {
+            check: self.check,
+            level,
+            message: message.into(),
+        });
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.push(Level::Error, message);
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        self.push(Level::Warning, message);
+    }
+
+    fn info(&mut self, message: impl Into<String>) {
+        self.push(Level::Info, message);
+    }
+}
+
+pub async fn mail(State(cfg): State<DnsConfig>, Json(req): Json<MailRequest>) -> Result<Json<MailResponse>, AppError> {
+    let domain = parse_name(req.domain.trim())?;
+    if req.dkim_selectors.len() > MAX_SELECTORS {
+        return Err(AppError::bad_request(format!("DKIM 选择器最多 {MAX_SELECTORS} 个")));
+    }
+    let client = cfg.clients.client(&req.resolver)?;
+    let client = if req.cache { client } else { client.refresh() };
+
+    // 各项互不依赖，并发查询
+    let ((mx, mx_findings), (spf, spf_findings), (dmarc, mut dmarc_findings), dkim, mta_sts, tls_rpt, bimi) = tokio::join!(
+        check_mx(&client, &domain),
+        check_spf(&client, &domain),
+        check_dmarc(&client, &domain),
+        check_dkim(&client, &domain, &req.dkim_selectors),
+        check_mta_sts(&client, &domain),
+        check_tls_rpt(&client, &domain),
+        check_bimi(&client, &domain, req.bimi_selector.trim()),
+    );
+    let (dkim, dkim_findings) = dkim;
+    let (mta_sts, mta_sts_findings) = mta_sts;
+    let (tls_rpt, mut tls_rpt_findings) = tls_rpt;
+    let (bimi, mut bimi_findings) = bimi;
+
+    if tls_rpt.record.is_none() && tls_rpt.error.is_none() {
+        if mta_sts.record.is_some() {
+            tls_rpt_findings.warn("部署了 MTA-STS 但没有 TLS-RPT，收不到 TLS 失败报告");
+        } else {
+            tls_rpt_findings.info("未部署 TLS-RPT");
+        }
+    }
+    if bimi.record.is_some() {
+        let enforced = matches!(dmarc.get("p").map(str::to_ascii_lowercase).as_deref(), Some("quarantine" | "reject"));
+        let full = dmarc.get("pct").is_none_or(|pct| pct.trim() == "100");
+        if !enforced || !full {
+            bimi_findings.warn("BIMI 要求 DMARC 策略为 quarantine 或 reject 且 pct=100，邮箱服务商不会展示标志");
+        }
+    }
+    let no_dkim = !dkim.is_empty() && dkim.iter().all(|d| d.record.record.is_none());
+    if dmarc.record.is_some() && spf.record.is_none() && no_dkim {
+        dmarc_findings.warn("既没有 SPF 也没有找到 DKIM，DMARC 校验无法通过");
+    }
+
+    let mut findings: Vec<Finding> = [
+        mx_findings,
+        spf_findings,
+        dmarc_findings,
+        dkim_findings,
+        mta_sts_findings,
+        tls_rpt_findings,
+        bimi_findings,
+    ]
+    .into_iter()
+    .flat_map(|f| f.items)
+    .collect();
+    findings.sort_by_key(|f| f.level);
+
+    let ascii = domain.to_ascii();
+    Ok(Json(MailResponse {
+        domain_unicode: idn::to_unicode(&ascii),
+        domain: ascii,
+        mx,
+        spf,
+        dmarc,
+        dkim,
+        mta_sts,
+        tls_rpt,
+        bimi,
+        findings,
+    }))
+}
+
+/// 查询 TXT 并合并每条记录的多个字符串；NXDOMAIN / NODATA 时为空列表。
+async fn txt(client: &DnsClient, name: &Name) -> Result<Vec<String>, String> {
+    let exchange = client.query(name, RecordType::TXT).await.map_err(|e| e.to_string())?;
+    let code = exchange.response.metadata.response_code;
+    if !matches!(code, ResponseCode::NoError | ResponseCode::NXDomain) {
+        return Err(format!("查询返回 {}", rcode_name(code)));
+    }
+    Ok(exchange
+        .response
+        .answers
+        .iter()
+        .filter_map(|r| match &r.data {
+            RData::TXT(txt) => Some(txt.txt_data.iter().map(|s| String::from_utf8_lossy(s)).collect()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// 名称有 A 或 AAAA 记录。
+async fn has_address(client: &DnsClient, name: &Name) -> bool {
+    for rt in [RecordType::A, RecordType::AAAA] {
+        if let Ok(exchange) = client.query(name, rt).await {
+            if exchange.response.answers.iter().any(|r| r.record_type() == rt) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// 在域名前加上 `_dmarc` 之类的前缀。
+fn child(prefix: &str, domain: &Name) -> Option<Name> {
+    Name::from_ascii(prefix).ok()?.append_domain(domain).ok()
+}
+
+/// 记录以指定版本标签开头（如 `v=DMARC1`），其后为 `;`、空白或结尾。
+fn has_version(record: &str, version: &str) -> bool {
+    let record = record.trim_start();
+    // 记录内容不受控，按字节偏移切片前须确认落在字符边界上
+    record.get(..version.len()).is_some_and(|p| p.eq_ignore_ascii_case(version))
+        && record[version.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| c == ';' || c.is_whitespace())
+}
+
+fn parse_tags(record: &str) -> Vec<Tag> {
+    record
+        .split(';')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (tag, value) = part.split_once('=').unwrap_or((part, ""));
+            Tag {
+                tag: tag.trim().to_string(),
+                value: value.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// 查询带版本标签的 TXT 记录并解析标签；多条时报错并取第一条。
+async fn fetch_tagged(client: &DnsClient, name: &Name, version: &str, findings: &mut Findings) -> TagRecord {
+    let mut result = TagRecord::empty(name);
+    let records: Vec<String> = match txt(client, name).await {
+        Ok(records) => records.into_iter().filter(|r| has_version(r, version)).collect(),
+        Err(e) => {
+            findings.error(format!("{} {e}", name.to_ascii()));
+            result.error = Some(e);
+            return result;
+        }
+    };
+    if records.len() > 1 {
+        findings.error(format!("{} 存在 {} 条 {version} 记录，接收方会忽略全部记录", name.to_ascii(), records.len()));
+    }
+    if let Some(record) = records.into_iter().next() {
+        result.tags = parse_tags(&record);
+        result.record = Some(record);
+    }
+    result
+}
+
+async fn check_mx(client: &DnsClient, domain: &Name) -> (MxCheck, Findings) {
+    let mut findings = Findings::new("mx");
+    let mut check = MxCheck {
+        hosts: Vec::new(),
+        null_mx: false,
+    };
+    let exchange = match client.query(domain, RecordType::MX).await {
+        Ok(exchange) => exchange,
+        Err(e) => {
+            findings.error(format!("查询 MX 失败: {e}"));
+            return (check, findings);
+        }
+    };
+    match exchange.response.metadata.response_code {
+        ResponseCode::NoError => {}
+        ResponseCode::NXDomain => {
+            findings.error("域名不存在（NXDOMAIN）");
+            return (check, findings);
+        }
+        other => {
+            findings.error(format!("查询 MX 返回 {}", rcode_name(other)));
+            return (check, findings);
+        }
+    }
+    let mut hosts: Vec<(u16, Name)> = exchange
+        .response
+        .answers
+        .iter()
+        .filter_map(|r| match &r.data {
+            RData::MX(mx) => Some((mx.preference, mx.exchange.clone())),
+            _ => None,
+        })
+        .collect();
+    hosts.sort_by_key(|(preference, _)| *preference);
+    check.hosts = hosts
+        .iter()
+        .map(|(preference, exchange)| MxHost {
+            preference: *preference,
+            exchange: exchange.to_ascii(),
+        })
+        .collect();
+
+    if hosts.is_empty() {
+        findings.warn("没有 MX 记录，发件方会回退到域名的 A / AAAA 地址投递");
+    } else if hosts.iter().any(|(_, host)| host.is_root()) {
+        check.null_mx = true;
+        if hosts.len() > 1 {
+            findings.error("Null MX（.）不能与其他 MX 记录并存");
+        } else {
+            findings.info("Null MX：声明该域名不接收邮件");
+        }
+    } else {
+        for (_, host) in hosts.iter().take(MAX_MX_HOSTS) {
+            if !has_address(client, host).await {
+                findings.error(format!("MX 主机 {} 没有 A / AAAA 记录", host.to_ascii()));
+            }
+        }
+    }
+    (check, findings)
+}
+
+async fn check_spf(client: &DnsClient, domain: &Name) -> (SpfCheck, Findings) {
+    let root = domain.to_ascii().trim_end_matches('.').to_string();
+    let mut walker = SpfWalker {
+        client,
+        lookups: 0,
+        void_lookups: 0,
+        path: vec![root.to_ascii_lowercase()],
+        stopped: false,
+        findings: Findings::new("spf"),
+    };
+    let root = walker.expand(root, 0).await;
+    let mut findings = walker.findings;
+    if root.record.is_none() && root.error.as_deref() == Some(NO_SPF) {
+        findings.warn("没有 SPF 记录，接收方无法验证发件服务器");
+    }
+    if walker.stopped {
+        findings.error(format!(
+            "展开到第 {} 次 DNS 查询时已超过 {SPF_LOOKUP_LIMIT} 次上限，其余引用未再展开，评估结果为 PermError",
+            walker.lookups
+        ));
+    } else if walker.lookups > SPF_LOOKUP_LIMIT {
+        findings.error(format!(
+            "展开后需要 {} 次 DNS 查询，超过 {SPF_LOOKUP_LIMIT} 次上限，评估结果为 PermError",
+            walker.lookups
+        ));
+    } else if walker.lookups + 2 > SPF_LOOKUP_LIMIT {
+        findings.warn(format!("展开后需要 {} 次 DNS 查询，接近 {SPF_LOOKUP_LIMIT} 次上限", walker.lookups));
+    }
+    if walker.void_lookups > SPF_VOID_LIMIT {
+        findings.error(format!(
+            "{} 次查询没有结果，超过 {SPF_VOID_LIMIT} 次上限，评估结果为 PermError",
+            walker.void_lookups
+        ));
+    }
+    (
+        SpfCheck {
+            record: root.record.is_some().then_some(root),
+            lookups: walker.lookups,
+            void_lookups: walker.void_lookups,
+            lookup_limit: SPF_LOOKUP_LIMIT,
+        },
+        findings,
+    )
+}
+
+const NO_SPF: &str = "没有 SPF 记录";
+
+struct SpfWalker<'a> {
+    client: &'a DnsClient,
+    lookups: usize,
+    void_lookups: usize,
+    /// 当前展开路径上的域名，用于发现循环引用；同一记录在不同分支重复出现时每次都展开计数
+    path: Vec<String>,
+    /// 查询次数超过上限后不再展开后续引用
+    stopped: bool,
+    findings: Findings,
+}
+
+impl SpfWalker<'_> {
+    /// 取出并解析一个域名的 SPF 记录，递归展开 include 与 redirect。
+    fn expand(&mut self, domain: String, depth: usize) -> Pin<Box<dyn Future<Output = SpfRecord> + Send + '_>> {
+        Box::pin(async move {
+            let mut node = SpfRecord {
+                domain: domain.clone(),
+                record: None,
+                terms: Vec::new(),
+                error: None,
+            };
+            let Some(name) = spf_name(&domain) else {
+                node.error = Some("无效的域名".to_string());
+                self.findings.error(format!("SPF 引用了无效的域名 {domain}"));
+                return node;
+            };
+            let records = match txt(self.client, &name).await {
+                Ok(records) => records,
+                Err(e) => {
+                    self.findings.error(format!("查询 {domain} 的 SPF 失败: {e}"));
+                    node.error = Some(e);
+                    return node;
+                }
+            };
+            if records.is_empty() && depth > 0 {
+                self.void_lookups += 1;
+            }
+            let mut spf: Vec<String> = records.into_iter().filter(|r| has_version(r, "v=spf1")).collect();
+            if spf.len() > 1 {
+                self.findings.error(format!("{domain} 存在 {} 条 SPF 记录，评估结果为 PermError", spf.len()));
+            }
+            let Some(record) = (!spf.is_empty()).then(|| spf.swap_remove(0)) else {
+                node.error = Some(NO_SPF.to_string());
+                return node;
+            };
+
+            let mut all = None;
+            let mut redirect = None;
+            for raw in record.split_whitespace().skip(1) {
+                let mut term = parse_term(raw);
+                if all.is_some() && term.qualifier.is_some() {
+                    self.findings.warn(format!("{domain}: all 之后的 {raw} 不会生效"));
+                }
+                match term.mechanism.as_str() {
+                    "all" => {
+                        all = term.qualifier;
+                        match term.qualifier {
+                            Some("pass") => self.findings.error(format!("{domain}: +all 允许任何服务器以该域名发信")),
+                            Some("neutral") => self.findings.warn(format!("{domain}: ?all 对未授权的服务器不给结论，等同没有保护")),
+                            _ => {}
+                        }
+                    }
+                    "include" => {
+                        term.lookups = 1;
+                        self.lookups += 1;
+                        match term.value.clone() {
+                            Some(target) => term.included = self.follow(&domain, target, depth).await,
+                            None => self.findings.error(format!("{domain}: include 缺少域名")),
+                        }
+                    }
+                    "a" | "mx" => {
+                        term.lookups = 1;
+                        self.lookups += 1;
+                        let target = term.value.as_deref().map_or(domain.as_str(), |v| v.split('/').next().unwrap_or(v));
+                        let target = if target.is_empty() { domain.as_str() } else { target };
+                        self.check_target(&term.mechanism, target).await;
+                    }
+                    "ptr" => {
+                        term.lookups = 1;
+                        self.lookups += 1;
+                        self.findings.warn(format!("{domain}: 不建议使用 ptr 机制（慢且不可靠，RFC 7208 5.5）"));
+                    }
+                    "exists" => {
+                        term.lookups = 1;
+                        self.lookups += 1;
+                    }
+                    "ip4" | "ip6" if !valid_network(&term.mechanism, term.value.as_deref()) => {
+                        self.findings.error(format!("{domain}: 无效的地址 {raw}"));
+                    }
+                    "ip4" | "ip6" => {}
+                    "redirect" => {
+                        term.lookups = 1;
+                        self.lookups += 1;
+                        redirect = term.value.clone();
+                    }
+                    "exp" => {}
+                    other if term.qualifier.is_some() => {
+                        self.findings.error(format!("{domain}: 未知机制 {other}，评估结果为 PermError"));
+                    }
+                    _ => {}
+                }
+                node.terms.push(term);
+            }
+
+            // 有 all 时 redirect 被忽略；否则按 redirect 的目标记录继续评估
+            match (all, redirect) {
+                (Some(_), Some(_)) => self.findings.info(format!("{domain}: 存在 all，redirect 不会生效")),
+                (None, Some(target)) => {
+                    let included = self.follow(&domain, target, depth).await;
+                    if let Some(term) = node.terms.iter_mut().find(|t| t.mechanism == "redirect") {
+                        term.included = included;
+                    }
+                }
+                (None, None) if depth == 0 => {
+                    self.findings.warn(format!("{domain}: 缺少 all 机制，未匹配的来源结果为 neutral"));
+                }
+                _ => {}
+            }
+            node.record = Some(record);
+            node
+        })
+    }
+
+    /// 展开 include / redirect 指向的记录。
+    async fn follow(&mut self, from: &str, target: String, depth: usize) -> Option<Box<SpfRecord>> {
+        if target.contains('%') {
+            self.findings.info(format!("{from}: {target} 含宏，需结合发件人信息才能展开"));
+            return None;
+        }
+        let key = target.to_ascii_lowercase();
+        if self.path.contains(&key) {
+            self.findings.error(format!("{from}: 循环引用 {target}"));
+            return None;
+        }
+        if depth + 1 > SPF_MAX_DEPTH {
+            self.findings.error(format!("{from}: 引用层数超过 {SPF_MAX_DEPTH}，停止展开"));
+            return None;
+        }
+        // 已超过上限时评估结果必为 PermError，不再展开，避免重复子树反复扩张
+        if self.lookups > SPF_LOOKUP_LIMIT {
+            self.stopped = true;
+            return None;
+        }
+        self.path.push(key);
+        let included = self.expand(target.clone(), depth + 1).await;
+        self.path.pop();
+        if included.error.as_deref() == Some(NO_SPF) {
+            self.findings.error(format!("{from}: {target} 没有 SPF 记录，评估结果为 PermError"));
+        }
+        Some(Box::new(included))
+    }
+
+    /// a / mx 机制的目标没有地址或 MX 时计为无结果查询。
+    async fn check_target(&mut self, mechanism: &str, target: &str) {
+        if target.contains('%') {
+            return;
+        }
+        let Some(name) = spf_name(target) else {
+            self.findings.error(format!("{mechanism} 机制引用了无效的域名 {target}"));
+            return;
+        };
+        let found = if mechanism == "mx" {
+            match self.client.query(&name, RecordType::MX).await {
+                Ok(exchange) => {
+                    let count = exchange.response.answers.iter().filter(|r| r.record_type() == RecordType::MX).count();
+                    if count > SPF_LOOKUP_LIMIT {
+                        self.findings.error(format!("mx:{target} 有 {count} 个 MX 主机，超过 {SPF_LOOKUP_LIMIT} 个上限"));
+                    }
+                    count > 0
+                }
+                Err(_) => true,
+            }
+        } else {
+            has_address(self.client, &name).await
+        };
+        if !found {
+            self.void_lookups += 1;
+            self.findings.warn(format!("{mechanism}:{target} 没有查询结果（void lookup）"));
+        }
+    }
+}
+
+fn spf_name(domain: &str) -> Option<Name> {
+    let mut name = Name::from_ascii(domain.trim_end_matches('.')).ok()?;
+    name.set_fqdn(true);
+    Some(name)
+}
+
+/// 解析一项 SPF 机制（`[+-~?]名称[:值][/前缀]`）或修饰符（`名称=值`）。
+fn parse_term(raw: &str) -> SpfTerm {
+    let is_modifier = raw
+        .split_once('=')
+        .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')));
+    if is_modifier {
+        let (name, value) = raw.split_once('=').unwrap_or((raw, ""));
+        return SpfTerm {
+            term: raw.to_string(),
+            mechanism: name.to_ascii_lowercase(),
+            qualifier: None,
+            value: Some(value.to_string()),
+            lookups: 0,
+            included: None,
+        };
+    }
+    let (qualifier, rest) = match raw.chars().next() {
+        Some('+') => ("pass", &raw[1..]),
+        Some('-') => ("fail", &raw[1..]),
+        Some('~') => ("softfail", &raw[1..]),
+        Some('?') => ("neutral", &raw[1..]),
+        _ => ("pass", raw),
+    };
+    let split = rest.find([':', '/']).unwrap_or(rest.len());
+    let (name, value) = rest.split_at(split);
+    let value = value.strip_prefix(':').unwrap_or(value);
+    SpfTerm {
+        term: raw.to_string(),
+        mechanism: name.to_ascii_lowercase(),
+        qualifier: Some(qualifier),
+        value: (!value.is_empty()).then(|| value.to_string()),
+        lookups: 0,
+        included: None,
+    }
+}
+
+fn valid_network(mechanism: &str, value: Option<&str>) -> bool {
+    let Some(value) = value else { return false };
+    let (addr, prefix) = value.split_once('/').map_or((value, None), |(a, p)| (a, Some(p)));
+    let max = match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) if mechanism == "ip4" => 32,
+        Ok(IpAddr::V6(_)) if mechanism == "ip6" => 128,
+        _ => return false,
+    };
+    prefix.is_none_or(|p| p.parse::<u8>().is_ok_and(|p| p <= max))
+}
+
+async fn check_dmarc(client: &DnsClient, domain: &Name) -> (TagRecord, Findings) {
+    let mut findings = Findings::new("dmarc");
+    let Some(name) = child("_dmarc", domain) else {
+        findings.error("域名过长，无法查询 DMARC");
+        return (TagRecord::empty(domain), findings);
+    };
+    let mut dmarc = fetch_tagged(client, &name, "v=DMARC1", &mut findings).await;
+    if dmarc.error.is_some() {
+        return (dmarc, findings);
+    }
+    // 子域名没有记录时接收方使用组织域的策略；这里按最后两级标签推断组织域
+    let mut org = domain.clone();
+    if dmarc.record.is_none() && domain.num_labels() > 2 {
+        org = domain.trim_to(2);
+        if let Some(org_name) = child("_dmarc", &org) {
+            let inherited = fetch_tagged(client, &org_name, "v=DMARC1", &mut findings).await;
+            if inherited.record.is_some() {
+                findings.info(format!("{} 没有 DMARC 记录，沿用组织域 {} 的策略（sp 优先）", domain.to_ascii(), org.to_ascii()));
+                dmarc = inherited;
+            }
+        }
+    }
+    if dmarc.record.is_none() {
+        findings.error("没有 DMARC 记录，无法防止他人伪造该域名发信");
+        return (dmarc, findings);
+    }
+
+    match dmarc.get("p").map(str::to_ascii_lowercase).as_deref() {
+        None => findings.error("缺少必需的 p 标签"),
+        Some("none") => findings.warn("p=none 仅监控，不会拦截伪造邮件"),
+        Some("quarantine" | "reject") => {}
+        Some(other) => findings.error(format!("无效的策略 p={other}")),
+    }
+    match dmarc.get("sp").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("quarantine" | "reject") => {}
+        Some("none") => findings.warn("sp=none：子域名不受保护"),
+        Some(other) => findings.error(format!("无效的子域策略 sp={other}")),
+    }
+    if let Some(pct) = dmarc.get("pct") {
+        match pct.parse::<u8>() {
+            Ok(100) => {}
+            Ok(pct) if pct < 100 => findings.warn(format!("pct={pct}：策略只作用于 {pct}% 的邮件")),
+            _ => findings.error(format!("无效的 pct={pct}（应为 0-100）")),
+        }
+    }
+    for tag in ["adkim", "aspf"] {
+        if let Some(mode) = dmarc.get(tag) {
+            if !matches!(mode.to_ascii_lowercase().as_str(), "r" | "s") {
+                findings.error(format!("无效的 {tag}={mode}（应为 r 或 s）"));
+            }
+        }
+    }
+    if dmarc.get("ruf").is_some() {
+        findings.info("多数邮箱服务商不发送 ruf 失败报告");
+    }
+    let rua: Vec<String> = dmarc
+        .get("rua")
+        .map(|rua| rua.split(',').map(|u| u.trim().to_string()).filter(|u| !u.is_empty()).collect())
+        .unwrap_or_default();
+    if rua.is_empty() {
+        findings.warn("未配置汇总报告地址 rua，无法了解谁在以该域名发信");
+    }
+    for uri in &rua {
+        let Some(address) = uri.get(..7).filter(|s| s.eq_ignore_ascii_case("mailto:")).map(|_| &uri[7..]) else {
+            findings.warn(format!("报告地址 {uri} 不是 mailto: URI"));
+            continue;
+        };
+        // 报告发往其他域时，对方须发布 <域名>._report._dmarc.<对方域> 授权记录
+        let host = address.rsplit('@').next().unwrap_or_default().split('!').next().unwrap_or_default();
+        let Some(host) = spf_name(host) else {
+            findings.error(format!("报告地址 {uri} 的域名无效"));
+            continue;
+        };
+        if org.zone_of(&host) {
+            continue;
+        }
+        let auth = format!(
+            "{}._report._dmarc.{}",
+            domain.to_ascii().trim_end_matches('.'),
+            host.to_ascii()
+        );
+        let authorized = match Name::from_ascii(&auth) {
+            Ok(auth) => txt(client, &auth).await.is_ok_and(|r| r.iter().any(|r| has_version(r, "v=DMARC1"))),
+            Err(_) => false,
+        };
+        if !authorized {
+            findings.warn(format!(
+                "外部报告地址 {} 未发布 {}._report._dmarc 授权记录，报告会被丢弃",
+                host.to_ascii(),
+                domain.to_ascii().trim_end_matches('.')
+            ));
+        }
+    }
+    (dmarc, findings)
+}
+
+async fn check_dkim(client: &DnsClient, domain: &Name, selectors: &[String]) -> (Vec<DkimCheck>, Findings) {
+    let mut findings = Findings::new("dkim");
+    let selectors: Vec<&str> = selectors.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+    if selectors.is_empty() {
+        findings.info("未指定 DKIM 选择器，跳过 DKIM 检查");
+        return (Vec::new(), findings);
+    }
+    let mut checks = Vec::with_capacity(selectors.len());
+    for selector in selectors {
+        let Some(name) = child(&format!("{selector}._domainkey"), domain) else {
+            findings.error(format!("无效的 DKIM 选择器 {selector}"));
+            continue;
+        };
+        let mut check = DkimCheck {
+            selector: selector.to_string(),
+            record: TagRecord::empty(&name),
+            key_type: None,
+            key_bits: None,
+            revoked: false,
+        };
+        let records: Vec<String> = match txt(client, &name).await {
+            Ok(records) => records
+                .into_iter()
+                .filter(|r| has_version(r, "v=DKIM1") || parse_tags(r).iter().any(|t| t.tag == "p"))
+                .collect(),
+            Err(e) => {
+                findings.error(format!("查询选择器 {selector} 失败: {e}"));
+                check.record.error = Some(e);
+                checks.push(check);
+                continue;
+            }
+        };
+        let Some(record) = records.first() else {
+            findings.error(format!("选择器 {selector} 没有 DKIM 记录（{}）", name.to_ascii()));
+            checks.push(check);
+            continue;
+        };
+        if records.len() > 1 {
+            findings.warn(format!("选择器 {selector} 存在 {} 条 DKIM 记录，验证结果不确定", records.len()));
+        }
+        check.record.tags = parse_tags(record);
+        check.record.record = Some(record.clone());
+        let record = &check.record;
+
+        let key_type = record.get("k").unwrap_or("rsa").to_ascii_lowercase();
+        let key: String = record.get("p").unwrap_or_default().split_whitespace().collect();
+        if key.is_empty() {
+            check.revoked = true;
+            findings.warn(format!("选择器 {selector} 的公钥为空（已撤销）"));
+        } else {
+            match base64::engine::general_purpose::STANDARD.decode(&key) {
+                Ok(der) => {
+                    let bits = match key_type.as_str() {
+                        "rsa" => rsa_bits(&der),
+                        "ed25519" => (der.len() == 32).then_some(256),
+                        _ => None,
+                    };
+                    match (key_type.as_str(), bits) {
+                        ("rsa", Some(bits)) if bits < 1024 => findings.error(format!("选择器 {selector} 的 RSA 密钥只有 {bits} 位，接收方会拒绝（RFC 8301 要求至少 1024 位）")),
+                        ("rsa", Some(bits)) if bits < 2048 => findings.warn(format!("选择器 {selector} 的 RSA 密钥为 {bits} 位，建议使用 2048 位")),
+                        ("rsa" | "ed25519", None) => findings.error(format!("选择器 {selector} 的公钥无法解析")),
+                        ("rsa" | "ed25519", Some(_)) => {}
+                        (other, _) => findings.error(format!("选择器 {selector} 使用未知的密钥类型 k={other}")),
+                    }
+                    check.key_bits = bits;
+                }
+                Err(_) => findings.error(format!("选择器 {selector} 的公钥不是有效的 Base64")),
+            }
+        }
+        if record.get("t").is_some_and(|t| t.split(':').any(|f| f.trim() == "y")) {
+            findings.warn(format!("选择器 {selector} 处于测试模式（t=y），接收方可能忽略签名结果"));
+        }
+        if record.get("h").is_some_and(|h| h.split(':').all(|a| a.trim().eq_ignore_ascii_case("sha1"))) {
+            findings.warn(format!("选择器 {selector} 只允许 SHA-1（h=sha1），已不再安全"));
+        }
+        check.key_type = Some(key_type);
+        checks.push(check);
+    }
+    (checks, findings)
+}
+
+/// RSA 模数的位数，公钥为 SubjectPublicKeyInfo 或裸 RSAPublicKey 的 DER。
+fn rsa_bits(der: &[u8]) -> Option<usize> {
+    let (tag, body, _) = der_tlv(der)?;
+    if tag != 0x30 {
+        return None;
+    }
+    let (inner_tag, inner, rest) = der_tlv(body)?;
+    match inner_tag {
+        // RSAPublicKey：第一个 INTEGER 即模数
+        0x02 => {
+            let modulus: &[u8] = &inner[inner.iter().position(|b| *b != 0)?..];
+            Some(modulus.len() * 8 - modulus[0].leading_zeros() as usize)
+        }
+        // SubjectPublicKeyInfo：跳过算法标识，BIT STRING 去掉未用位字节后是 RSAPublicKey
+        0x30 => {
+            let (bit_tag, bits, _) = der_tlv(rest)?;
+            (bit_tag == 0x03).then_some(())?;
+            rsa_bits(bits.get(1..)?)
+        }
+        _ => None,
+    }
+}
+
+/// 拆出一个 DER TLV：(标签, 内容, 剩余字节)。
+fn der_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (len, start) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let n = usize::from(first & 0x7f);
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + n)?.iter().fold(0usize, |acc, b| acc << 8 | usize::from(*b));
+        (len, 2 + n)
+    };
+    let body = data.get(start..start.checked_add(len)?)?;
+    Some((tag, body, &data[start + len..]))
+}
+
+async fn check_mta_sts(client: &DnsClient, domain: &Name) -> (TagRecord, Findings) {
+    let mut findings = Findings::new("mta_sts");
+    let Some(name) = child("_mta-sts", domain) else {
+        return (TagRecord::empty(domain), findings);
+    };
+    let record = fetch_tagged(client, &name, "v=STSv1", &mut findings).await;
+    if record.error.is_some() {
+        return (record, findings);
+    }
+    if record.record.is_none() {
+        findings.info("未部署 MTA-STS，发件方不会强制使用 TLS 投递");
+        return (record, findings);
+    }
+    match record.get("id") {
+        None => findings.error("缺少必需的 id 标签"),
+        Some(id) if id.is_empty() || id.len() > 32 || !id.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            findings.error(format!("无效的 id={id}（应为 1-32 位字母数字）"))
+        }
+        Some(_) => {}
+    }
+    // 策略文件位于 https://mta-sts.<域名>/.well-known/mta-sts.txt
+    if let Some(host) = child("mta-sts", domain) {
+        if !has_address(client, &host).await {
+            findings.error(format!("策略主机 {} 无法解析，发件方取不到策略文件", host.to_ascii()));
+        }
+    }
+    (record, findings)
+}
+
+async fn check_tls_rpt(client: &DnsClient, domain: &Name) -> (TagRecord, Findings) {
+    let mut findings = Findings::new("tls_rpt");
+    let Some(name) = child("_smtp._tls", domain) else {
+        return (TagRecord::empty(domain), findings);
+    };
+    let record = fetch_tagged(client, &name, "v=TLSRPTv1", &mut findings).await;
+    if record.error.is_some() {
+        return (record, findings);
+    }
+    if record.record.is_some() {
+        let rua: Vec<&str> = record.get("rua").unwrap_or_default().split(',').map(str::trim).filter(|u| !u.is_empty()).collect();
+        if rua.is_empty() {
+            findings.error("缺少必需的 rua 标签");
+        }
+        for uri in rua {
+            let lower = uri.to_ascii_lowercase();
+            if !lower.starts_with("mailto:") && !lower.starts_with("https:") {
+                findings.error(format!("报告地址 {uri} 应为 mailto: 或 https: URI"));
+            }
+        }
+    }
+    (record, findings)
+}
+
+async fn check_bimi(client: &DnsClient, domain: &Name, selector: &str) -> (TagRecord, Findings) {
+    let mut findings = Findings::new("bimi");
+    let selector = if selector.is_empty() { "default" } else { selector };
+    let Some(name) = child(&format!("{selector}._bimi"), domain) else {
+        findings.error(format!("无效的 BIMI 选择器 {selector}"));
+        return (TagRecord::empty(domain), findings);
+    };
+    let record = fetch_tagged(client, &name, "v=BIMI1", &mut findings).await;
+    if record.error.is_some() {
+        return (record, findings);
+    }
+    if record.record.is_none() {
+        findings.info("未部署 BIMI");
+        return (record, findings);
+    }
+    match record.get("l") {
+        None | Some("") => findings.info("l 为空：声明不展示标志"),
+        Some(logo) if !logo.to_ascii_lowercase().starts_with("https://") => {
+            findings.error(format!("标志地址 {logo} 必须使用 HTTPS"))
+        }
+        Some(logo) if !logo.to_ascii_lowercase().ends_with(".svg") => {
+            findings.warn(format!("标志地址 {logo} 应为 SVG Tiny PS 文件"))
+        }
+        Some(_) => {}
+    }
+    match record.get("a") {
+        None | Some("") => findings.info("未提供 VMC / CMC 证书（a=），Gmail 等服务商不会展示标志"),
+        Some(authority) if !authority.to_ascii_lowercase().starts_with("https://") => {
+            findings.error(format!("证书地址 {authority} 必须使用 HTTPS"))
+        }
+        Some(_) => {}
+    }
+    (record, findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hickory_resolver::proto::rr::rdata::TXT;
+    use hickory_resolver::proto::rr::Record;
+
+    use crate::handlers::dns::stub::{self, Stub};
+
+    const ZONE: [(&str, &str); 7] = [
+        ("diamond.test.", "v=spf1 include:a.test include:b.test -all"),
+        ("a.test.", "v=spf1 include:shared.test -all"),
+        ("b.test.", "v=spf1 include:shared.test ~all"),
+        ("shared.test.", "v=spf1 ip4:192.0.2.0/24 include:leaf.test -all"),
+        ("leaf.test.", "v=spf1 ip4:198.51.100.0/24 -all"),
+        ("loop.test.", "v=spf1 include:loop2.test -all"),
+        ("loop2.test.", "v=spf1 include:LOOP.test -all"),
+    ];
+
+    async fn spf(domain: &str) -> (SpfCheck, Vec<String>) {
+        let stub = Stub::start(&[1], |_, _, request| {
+            let mut reply = stub::reply(request);
+            let qname = request.queries[0].name().clone();
+            match ZONE.iter().find(|(name, _)| qname.to_ascii().eq_ignore_ascii_case(name)) {
+                Some((_, text)) => {
+                    reply.add_answer(Record::from_rdata(qname, 300, RData::TXT(TXT::new(vec![text.to_string()]))));
+                }
+                None => reply.metadata.response_code = ResponseCode::NXDomain,
+            }
+            Some(reply)
+        })
+        .await;
+        let settings = ResolverSettings { nameservers: vec![stub.addr(1).to_string()], ..Default::default() };
+        let client = DnsClient::new(&settings).unwrap_or_else(|e| panic!("{}", e.message));
+        let (check, findings) = check_spf(&client, &Name::from_ascii(domain).unwrap()).await;
+        (check, findings.items.into_iter().map(|f| f.message).collect())
+    }
+
+    #[tokio::test]
+    async fn repeated_subtrees_are_counted_each_time() {
+        let (check, findings) = spf("diamond.test.").await;
+        // a、b 各 1 次，shared 与 leaf 在两个分支各 1 次
+        assert_eq!(check.lookups, 6);
+        assert!(!findings.iter().any(|f| f.contains("循环引用")), "{findings:?}");
+        let root = check.record.unwrap();
+        for term in &root.terms[..2] {
+            let shared = term.included.as_ref().unwrap().terms[0].included.as_ref().unwrap();
+            assert_eq!(shared.domain, "shared.test");
+            assert!(shared.terms[1].included.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn cycles_on_the_current_path_are_reported() {
+        let (check, findings) = spf("loop.test.").await;
+        assert_eq!(check.lookups, 2);
+        assert!(findings.iter().any(|f| f == "loop2.test: 循环引用 LOOP.test"), "{findings:?}");
+    }
+
+    #[test]
+    fn version_prefix_on_multibyte_records() {
+        assert!(has_version("v=DMARC1; p=none", "v=DMARC1"));
+        assert!(has_version("  V=spf1", "v=spf1"));
+        assert!(!has_version("v=spf10", "v=spf1"));
+        assert!(!has_version("v=DMAR€; p=none", "v=DMARC1"));
+        assert!(!has_version("v=sp€", "v=spf1"));
+        assert!(!has_version("€", "v=spf1"));
+    }
+}
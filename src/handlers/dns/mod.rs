@@ -0,0 +1,331 @@
+use std::net::IpAddr;
+
+use axum::extract::State;
+use axum::Json;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{Name, RData, RecordType};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use super::error::AppError;
+
+mod cache;
+mod client;
+mod dnssec;
+mod idn;
+mod mail;
+mod propagation;
+mod records;
+mod reverse;
+#[cfg(test)]
+mod stub;
+mod trace;
+
+use client::{DnsClient, Exchange, QueryError, ResolverSettings};
+use dnssec::DnssecReport;
+use records::{DnsRecord, SUPPORTED_TYPES};
+use reverse::ReverseInfo;
+
+pub use cache::{flush_cache, ClientPool, DnsConfig};
+pub use idn::idn;
+pub use mail::mail;
+pub use propagation::propagation;
+pub use trace::trace;
+
+#[derive(Deserialize)]
+pub struct ResolveRequest {
+    /// 域名（支持 Unicode 国际化域名）；为 IP 地址时执行反向解析
+    pub domain: String,
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// DNSSEC 验证模式：带 DO / CD 位查询，逐个 RRset 报告 SECURE / INSECURE / BOGUS 及信任链
+    #[serde(default)]
+    pub dnssec: bool,
+    /// 使用应答缓存；为 false 时直接查询服务器并刷新缓存
+    #[serde(default = "default_true")]
+    pub cache: bool,
+    /// 服务器、传输协议与 RD / EDNS / DO 等选项，均可省略
+    #[serde(flatten)]
+    pub resolver: ResolverSettings,
+}
+
+/// 查询结果状态：区分"域名不存在"、"存在但没有该类型记录"与服务器失败。
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    #[serde(rename = "NOERROR")]
+    NoError,
+    #[serde(rename = "NXDOMAIN")]
+    NxDomain,
+    #[serde(rename = "NODATA")]
+    NoData,
+    #[serde(rename = "SERVFAIL")]
+    ServFail,
+    #[serde(rename = "REFUSED")]
+    Refused,
+    /// 其余响应码，见 `response.rcode`
+    #[serde(rename = "ERROR")]
+    Error,
+    #[serde(rename = "TIMEOUT")]
+    Timeout,
+    /// 网络错误等未收到响应的情况
+    #[serde(rename = "FAILED")]
+    Failed,
+    #[serde(rename = "UNSUPPORTED")]
+    Unsupported,
+}
+
+#[derive(Serialize)]
+pub struct DnsResult {
+    /// 实际查询的名称（ASCII / Punycode 形式）
+    pub name: String,
+    /// 同一名称的 Unicode 形式
+    pub name_unicode: String,
+    pub record_type: String,
+    pub status: Status,
+    /// 记录的文本形式；查询失败时为错误信息
+    pub records: Vec<String>,
+    /// 结构化的记录（含各自的 TTL）
+    pub answers: Vec<DnsRecord>,
+    /// 从查询名出发依次经过的 CNAME
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cname_chain: Vec<CnameHop>,
+    /// 收到响应时的报文信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<ResponseInfo>,
+    /// 输入为 IP 时的反向解析与正向确认
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverse: Option<ReverseInfo>,
+    /// DNSSEC 模式下的验证结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<DnssecReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CnameHop {
+    pub name: String,
+    pub target: String,
+    pub ttl: u32,
+}
+
+#[derive(Serialize)]
+pub struct ResponseInfo {
+    /// 应答的服务器（IP:端口）
+    pub server: String,
+    /// 最终使用的传输协议
+    pub transport: &'static str,
+    /// 毫秒，含截断后的 TCP 重试
+    pub latency_ms: f64,
+    /// 取自应答缓存，记录的 TTL 为剩余时间
+    pub cached: bool,
+    pub rcode: String,
+    pub authoritative: bool,
+    /// UDP 响应被截断（已改用 TCP 重试）
+    pub truncated: bool,
+    pub recursion_available: bool,
+    pub authentic_data: bool,
+    /// NXDOMAIN / NODATA 的否定缓存时间（取自权威区 SOA）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub negative_ttl: Option<u32>,
+}
+
+impl DnsResult {
+    fn failed(name: &Name, record_type: String, status: Status, error: String) -> Self {
+        let ascii = name.to_ascii();
+        Self {
+            name_unicode: idn::to_unicode(&ascii),
+            name: ascii,
+            record_type,
+            status,
+            records: vec![error.clone()],
+            answers: Vec::new(),
+            cname_chain: Vec::new(),
+            response: None,
+            reverse: None,
+            dnssec: None,
+            error: Some(error),
+        }
+    }
+
+    /// 查询一种记录类型并整理为结果。
+    async fn query(client: &DnsClient, record_type: String, rt: RecordType, name: &Name) -> Self {
+        match client.query(name, rt).await {
+            Ok(exchange) => Self::from_exchange(record_type, rt, name, exchange),
+            Err(e @ QueryError::Timeout) => Self::failed(name, record_type, Status::Timeout, e.to_string()),
+            Err(e) => Self::failed(name, record_type, Status::Failed, e.to_string()),
+        }
+    }
+
+    fn from_exchange(record_type: String, rt: RecordType, name: &Name, exchange: Exchange) -> Self {
+        let response = &exchange.response;
+        let answers = &response.answers;
+        let rcode = response.metadata.response_code;
+        let cname_chain = cname_chain(name, answers);
+        // CNAME 链之外没有所查类型的记录即为 NODATA
+        let has_data = answers
+            .iter()
+            .any(|r| rt == RecordType::ANY || rt == RecordType::CNAME || r.record_type() != RecordType::CNAME);
+        let (status, error) = match rcode {
+            ResponseCode::NoError if has_data => (Status::NoError, None),
+            ResponseCode::NoError => (Status::NoData, Some(format!("没有 {record_type} 记录（NODATA）"))),
+            ResponseCode::NXDomain => (Status::NxDomain, Some("域名不存在（NXDOMAIN）".to_string())),
+            ResponseCode::ServFail => (Status::ServFail, Some("服务器失败（SERVFAIL）".to_string())),
+            ResponseCode::Refused => (Status::Refused, Some("服务器拒绝查询（REFUSED）".to_string())),
+            other => (Status::Error, Some(format!("查询失败: {}", rcode_name(other)))),
+        };
+        let info = ResponseInfo {
+            server: exchange.server.to_string(),
+            transport: exchange.transport,
+            latency_ms: (exchange.elapsed.as_secs_f64() * 100_000.0).round() / 100.0,
+            cached: exchange.cached,
+            rcode: rcode_name(rcode),
+            authoritative: response.metadata.authoritative,
+            truncated: exchange.truncated,
+            recursion_available: response.metadata.recursion_available,
+            authentic_data: response.metadata.authentic_data,
+            negative_ttl: if matches!(status, Status::NxDomain | Status::NoData) {
+                response.negative_ttl()
+            } else {
+                None
+            },
+        };
+        let ascii = name.to_ascii();
+        Self {
+            name_unicode: idn::to_unicode(&ascii),
+            name: ascii,
+            status,
+            // ANY 或 CNAME 链中类型与查询不同的记录，文本前加上类型；失败时为错误信息
+            records: match &error {
+                Some(error) => vec![error.clone()],
+                None => answers
+                    .iter()
+                    .map(|r| {
+                        if r.record_type() == rt {
+                            r.data.to_string()
+                        } else {
+                            format!("{} {}", r.record_type(), r.data)
+                        }
+                    })
+                    .collect(),
+            },
+            answers: answers.iter().map(DnsRecord::from_record).collect(),
+            cname_chain,
+            response: Some(info),
+            reverse: None,
+            dnssec: None,
+            record_type,
+            error,
+        }
+    }
+}
+
+/// 沿应答区中的 CNAME 记录从查询名走到最终目标。
+fn cname_chain(name: &Name, answers: &[hickory_resolver::proto::rr::Record]) -> Vec<CnameHop> {
+    let mut chain = Vec::new();
+    let mut current = name.clone();
+    while chain.len() < 16 {
+        let Some((record, target)) = answers.iter().find_map(|r| match &r.data {
+            RData::CNAME(cname) if r.name == current => Some((r, cname.0.clone())),
+            _ => None,
+        }) else {
+            break;
+        };
+        chain.push(CnameHop {
+            name: record.name.to_string(),
+            target: target.to_string(),
+            ttl: record.ttl,
+        });
+        current = target;
+    }
+    chain
+}
+
+/// 响应码的助记符（NOERROR、NXDOMAIN 等）。
+fn rcode_name(code: ResponseCode) -> String {
+    let name = match code {
+        ResponseCode::NoError => "NOERROR",
+        ResponseCode::FormErr => "FORMERR",
+        ResponseCode::ServFail => "SERVFAIL",
+        ResponseCode::NXDomain => "NXDOMAIN",
+        ResponseCode::NotImp => "NOTIMP",
+        ResponseCode::Refused => "REFUSED",
+        ResponseCode::YXDomain => "YXDOMAIN",
+        ResponseCode::YXRRSet => "YXRRSET",
+        ResponseCode::NXRRSet => "NXRRSET",
+        ResponseCode::NotAuth => "NOTAUTH",
+        ResponseCode::NotZone => "NOTZONE",
+        ResponseCode::BADVERS => "BADVERS",
+        ResponseCode::BADCOOKIE => "BADCOOKIE",
+        other => return format!("RCODE{}", u16::from(other)),
+    };
+    name.to_string()
+}
+
+/// 把用户输入的域名（可为 Unicode）转为查询用的绝对名称。
+fn parse_name(domain: &str) -> Result<Name, AppError> {
+    if domain.is_empty() {
+        return Err(AppError::bad_request("无效的域名"));
+    }
+    let ascii = idn::to_ascii(domain)?;
+    let mut name = Name::from_ascii(&ascii).map_err(|e| AppError::bad_request(format!("无效的域名: {e}")))?;
+    name.set_fqdn(true);
+    Ok(name)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 输入为 IPv4 / IPv6 地址时忽略 `types`，改为 PTR 反向解析并做 FCrDNS 检查。
+/// 客户端按服务器配置复用，多种记录类型并发查询。
+pub async fn resolve(State(cfg): State<DnsConfig>, Json(req): Json<ResolveRequest>) -> Result<Json<Vec<DnsResult>>, AppError> {
+    let domain = req.domain.trim();
+    let client_for = |settings: &ResolverSettings| -> Result<DnsClient, AppError> {
+        let client = cfg.clients.client(settings)?;
+        Ok(if req.cache { client } else { client.refresh() })
+    };
+    if let Ok(ip) = domain.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        let client = client_for(&req.resolver)?;
+        return Ok(Json(vec![reverse::lookup(&client, ip).await]));
+    }
+    if req.types.is_empty() {
+        return Err(AppError::bad_request("请至少选择一种记录类型"));
+    }
+    if req.types.len() > SUPPORTED_TYPES.len() {
+        return Err(AppError::bad_request("记录类型过多"));
+    }
+
+    let name = parse_name(domain)?;
+    let mut settings = req.resolver.clone();
+    if req.dnssec {
+        settings.dnssec_ok = true;
+        settings.checking_disabled = true;
+    }
+    let client = client_for(&settings)?;
+
+    let mut results: Vec<Option<DnsResult>> = req.types.iter().map(|_| None).collect();
+    let mut tasks = JoinSet::new();
+    for (index, t) in req.types.iter().enumerate() {
+        let t = t.trim().to_ascii_uppercase();
+        let Some(rt) = records::parse_type(&t) else {
+            let msg = format!("不支持的记录类型: {t}（支持 {}）", SUPPORTED_TYPES.join(" / "));
+            results[index] = Some(DnsResult::failed(&name, t, Status::Unsupported, msg));
+            continue;
+        };
+        let (client, name, dnssec) = (client.clone(), name.clone(), req.dnssec);
+        tasks.spawn(async move {
+            let result = if dnssec {
+                dnssec::query(&client, t, rt, &name).await
+            } else {
+                DnsResult::query(&client, t, rt, &name).await
+            };
+            (index, result)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined.map_err(|e| anyhow::anyhow!("查询任务异常: {e}"))?;
+        results[index] = Some(result);
+    }
+    Ok(Json(results.into_iter().flatten().collect()))
+}
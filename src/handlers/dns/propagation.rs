@@ -0,0 +1,234 @@
+//! 传播检查：同时向多个解析器查询同一名称与类型，对比各家应答。
+
+use axum::extract::State;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use super::client::{ResolverSettings, Transport};
+use super::{idn, parse_name, records, DnsConfig, DnsResult, Status, SUPPORTED_TYPES};
+use crate::handlers::error::AppError;
+
+/// 单次检查的解析器上限。
+const MAX_RESOLVERS: usize = 16;
+
+/// 未指定解析器时使用的公共解析器（外加系统 DNS）。
+const DEFAULT_RESOLVERS: [(&str, &str); 6] = [
+    ("Cloudflare", "1.1.1.1"),
+    ("Google", "8.8.8.8"),
+    ("Quad9", "9.9.9.9"),
+    ("OpenDNS", "208.67.222.222"),
+    ("阿里 DNS", "223.5.5.5"),
+    ("DNSPod", "119.29.29.29"),
+];
+
+#[derive(Deserialize)]
+pub struct PropagationRequest {
+    pub domain: String,
+    #[serde(default = "default_type")]
+    pub record_type: String,
+    /// 为空时使用系统 DNS 与内置的公共解析器
+    #[serde(default)]
+    pub resolvers: Vec<ResolverEntry>,
+    /// 各解析器共用的传输协议与 RD / EDNS / DO 选项（`nameservers` 不生效）
+    #[serde(flatten)]
+    pub options: ResolverSettings,
+}
+
+fn default_type() -> String {
+    "A".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct ResolverEntry {
+    /// 展示名，缺省为地址
+    #[serde(default)]
+    pub label: Option<String>,
+    /// `IP` 或 `IP:端口`，为空表示系统 DNS
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub transport: Option<Transport>,
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PropagationResponse {
+    pub name: String,
+    pub name_unicode: String,
+    pub record_type: String,
+    /// 所有解析器的应答（状态与记录集合）一致
+    pub consistent: bool,
+    /// 多数解析器给出的状态与记录
+    pub consensus_status: Status,
+    pub consensus: Vec<String>,
+    /// 应答矩阵：每行一个记录值，`seen_by` 与 `results` 顺序对应
+    pub matrix: Vec<MatrixRow>,
+    pub results: Vec<ResolverResult>,
+}
+
+#[derive(Serialize)]
+pub struct MatrixRow {
+    pub value: String,
+    pub seen_by: Vec<bool>,
+    /// 并非所有解析器都返回了该值
+    pub mismatch: bool,
+}
+
+#[derive(Serialize)]
+pub struct ResolverResult {
+    pub label: String,
+    /// 应答的服务器（IP:端口）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    pub status: Status,
+    /// 排序去重后的记录文本
+    pub answers: Vec<String>,
+    /// 应答记录中最小的 TTL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ttl: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    /// 与多数结果一致
+    pub matches_consensus: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub async fn propagation(
+    State(cfg): State<DnsConfig>,
+    Json(req): Json<PropagationRequest>,
+) -> Result<Json<PropagationResponse>, AppError> {
+    let name = parse_name(req.domain.trim())?;
+    let record_type = req.record_type.trim().to_ascii_uppercase();
+    let Some(rt) = records::parse_type(&record_type) else {
+        return Err(AppError::bad_request(format!(
+            "不支持的记录类型: {record_type}（支持 {}）",
+            SUPPORTED_TYPES.join(" / ")
+        )));
+    };
+    if req.resolvers.len() > MAX_RESOLVERS {
+        return Err(AppError::bad_request(format!("解析器最多 {MAX_RESOLVERS} 个")));
+    }
+
+    let entries: Vec<ResolverEntry> = if req.resolvers.is_empty() {
+        std::iter::once(("系统 DNS", ""))
+            .chain(DEFAULT_RESOLVERS)
+            .map(|(label, address)| ResolverEntry {
+                label: Some(label.to_string()),
+                address: address.to_string(),
+                transport: None,
+                tls_server_name: None,
+            })
+            .collect()
+    } else {
+        req.resolvers
+    };
+
+    // 先全部校验并建好客户端，再并发查询
+    let mut labels = Vec::with_capacity(entries.len());
+    let mut tasks = JoinSet::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let address = entry.address.trim().to_string();
+        let mut settings = req.options.clone();
+        settings.nameservers = if address.is_empty() { Vec::new() } else { vec![address.clone()] };
+        if let Some(transport) = entry.transport {
+            settings.transport = transport;
+        }
+        if entry.tls_server_name.is_some() {
+            settings.tls_server_name = entry.tls_server_name;
+        }
+        // 复用连接池中的客户端，但不读缓存：传播检查要看各解析器当前的应答
+        let client = cfg.clients.client(&settings)?.refresh();
+        labels.push(match entry.label {
+            Some(label) if !label.trim().is_empty() => label.trim().to_string(),
+            _ if address.is_empty() => "系统 DNS".to_string(),
+            _ => address,
+        });
+        let (name, record_type) = (name.clone(), record_type.clone());
+        tasks.spawn(async move { (index, DnsResult::query(&client, record_type, rt, &name).await) });
+    }
+    let mut answers: Vec<Option<DnsResult>> = labels.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined.map_err(|e| anyhow::anyhow!("查询任务异常: {e}"))?;
+        answers[index] = Some(result);
+    }
+
+    let mut results: Vec<ResolverResult> = labels
+        .into_iter()
+        .zip(answers)
+        .filter_map(|(label, result)| result.map(|r| resolver_result(label, r)))
+        .collect();
+
+    // 以 (状态, 记录集合) 为键统计，出现最多的作为多数结果（并列时取靠前者）
+    let mut groups: Vec<((Status, &[String]), usize)> = Vec::new();
+    for r in &results {
+        let key = (r.status, r.answers.as_slice());
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, count)) => *count += 1,
+            None => groups.push((key, 1)),
+        }
+    }
+    let mut best = 0;
+    for (i, (_, count)) in groups.iter().enumerate() {
+        if *count > groups[best].1 {
+            best = i;
+        }
+    }
+    let (consensus_status, consensus) = groups
+        .get(best)
+        .map_or((Status::Failed, Vec::new()), |((status, answers), _)| (*status, answers.to_vec()));
+    let consistent = groups.len() <= 1;
+
+    let mut values: Vec<&String> = results.iter().flat_map(|r| &r.answers).collect();
+    values.sort();
+    values.dedup();
+    let matrix: Vec<MatrixRow> = values
+        .into_iter()
+        .map(|value| {
+            let seen_by: Vec<bool> = results.iter().map(|r| r.answers.contains(value)).collect();
+            MatrixRow {
+                value: value.clone(),
+                mismatch: seen_by.iter().any(|seen| !seen),
+                seen_by,
+            }
+        })
+        .collect();
+
+    for r in &mut results {
+        r.matches_consensus = r.status == consensus_status && r.answers == consensus;
+    }
+
+    let ascii = name.to_ascii();
+    Ok(Json(PropagationResponse {
+        name_unicode: idn::to_unicode(&ascii),
+        name: ascii,
+        record_type,
+        consistent,
+        consensus_status,
+        consensus,
+        matrix,
+        results,
+    }))
+}
+
+fn resolver_result(label: String, result: DnsResult) -> ResolverResult {
+    let mut answers = if result.status == Status::NoError {
+        result.records
+    } else {
+        Vec::new()
+    };
+    answers.sort();
+    answers.dedup();
+    ResolverResult {
+        label,
+        server: result.response.as_ref().map(|r| r.server.clone()),
+        status: result.status,
+        answers,
+        min_ttl: result.answers.iter().map(|a| a.ttl).min(),
+        latency_ms: result.response.as_ref().map(|r| r.latency_ms),
+        matches_consensus: false,
+        error: result.error,
+    }
+}
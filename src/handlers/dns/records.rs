@@ -0,0 +1,282 @@
+//! 把 hickory 的资源记录转换为结构化的 JSON 形式（各类型的优先级、权重、端口、目标等字段）。
+
+use base64::Engine;
+use hickory_resolver::proto::dnssec::rdata::DNSSECRData;
+use hickory_resolver::proto::dnssec::PublicKey;
+use hickory_resolver::proto::rr::rdata::svcb::{SvcParamValue, SVCB};
+use hickory_resolver::proto::rr::{RData, Record, RecordType};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// 支持查询的记录类型。
+pub(super) const SUPPORTED_TYPES: [&str; 18] = [
+    "A", "AAAA", "MX", "CNAME", "TXT", "NS", "SOA", "SRV", "CAA", "PTR", "DS", "DNSKEY", "HTTPS", "SVCB",
+    "NAPTR", "TLSA", "SSHFP", "ANY",
+];
+
+/// 解析记录类型名（不区分大小写），不在支持列表内时返回 None。
+pub(super) fn parse_type(name: &str) -> Option<RecordType> {
+    let upper = name.trim().to_ascii_uppercase();
+    if !SUPPORTED_TYPES.contains(&upper.as_str()) {
+        return None;
+    }
+    RecordType::from_str(&upper).ok()
+}
+
+/// 单条资源记录：所有者名、实际类型、TTL，以及按类型展开的字段。
+#[derive(Serialize)]
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: String,
+    pub ttl: u32,
+    #[serde(flatten)]
+    pub data: RecordData,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum RecordData {
+    Address {
+        address: IpAddr,
+    },
+    /// CNAME / NS / PTR
+    Name {
+        target: String,
+    },
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Txt {
+        /// 各字符串拼接后的文本
+        text: String,
+        strings: Vec<String>,
+    },
+    Soa {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: i32,
+        retry: i32,
+        expire: i32,
+        minimum: u32,
+    },
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Caa {
+        flags: u8,
+        critical: bool,
+        tag: String,
+        value: String,
+    },
+    Ds {
+        key_tag: u16,
+        algorithm: u8,
+        algorithm_name: String,
+        digest_type: u8,
+        /// 十六进制
+        digest: String,
+    },
+    Dnskey {
+        flags: u16,
+        zone_key: bool,
+        secure_entry_point: bool,
+        revoked: bool,
+        algorithm: u8,
+        algorithm_name: String,
+        key_tag: Option<u16>,
+        /// Base64
+        public_key: String,
+    },
+    Rrsig {
+        type_covered: String,
+        algorithm: u8,
+        labels: u8,
+        original_ttl: u32,
+        /// Unix 时间戳（秒）
+        expiration: u32,
+        inception: u32,
+        key_tag: u16,
+        signer_name: String,
+    },
+    /// HTTPS / SVCB
+    Svcb {
+        priority: u16,
+        target: String,
+        params: Vec<SvcParam>,
+    },
+    Naptr {
+        order: u16,
+        preference: u16,
+        flags: String,
+        services: String,
+        regexp: String,
+        replacement: String,
+    },
+    Tlsa {
+        cert_usage: u8,
+        selector: u8,
+        matching: u8,
+        /// 十六进制
+        cert_data: String,
+    },
+    Sshfp {
+        algorithm: u8,
+        fingerprint_type: u8,
+        /// 十六进制
+        fingerprint: String,
+    },
+    /// 其余类型只给出文本形式
+    Other {
+        rdata: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct SvcParam {
+    pub key: String,
+    pub value: String,
+}
+
+impl DnsRecord {
+    pub(super) fn from_record(record: &Record) -> Self {
+        Self {
+            name: record.name.to_string(),
+            record_type: record.record_type().to_string(),
+            ttl: record.ttl,
+            data: RecordData::from_rdata(&record.data),
+        }
+    }
+}
+
+impl RecordData {
+    fn from_rdata(rdata: &RData) -> Self {
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+        match rdata {
+            RData::A(a) => Self::Address { address: IpAddr::V4(a.0) },
+            RData::AAAA(a) => Self::Address { address: IpAddr::V6(a.0) },
+            RData::CNAME(n) => Self::Name { target: n.0.to_string() },
+            RData::NS(n) => Self::Name { target: n.0.to_string() },
+            RData::PTR(n) => Self::Name { target: n.0.to_string() },
+            RData::MX(mx) => Self::Mx { preference: mx.preference, exchange: mx.exchange.to_string() },
+            RData::TXT(txt) => {
+                let strings: Vec<String> = txt.txt_data.iter().map(|s| text(s)).collect();
+                Self::Txt { text: strings.concat(), strings }
+            }
+            RData::SOA(soa) => Self::Soa {
+                mname: soa.mname.to_string(),
+                rname: soa.rname.to_string(),
+                serial: soa.serial,
+                refresh: soa.refresh,
+                retry: soa.retry,
+                expire: soa.expire,
+                minimum: soa.minimum,
+            },
+            RData::SRV(srv) => Self::Srv {
+                priority: srv.priority,
+                weight: srv.weight,
+                port: srv.port,
+                target: srv.target.to_string(),
+            },
+            RData::CAA(caa) => Self::Caa {
+                flags: caa.flags(),
+                critical: caa.issuer_critical,
+                tag: caa.tag.clone(),
+                value: text(&caa.value),
+            },
+            RData::HTTPS(https) => Self::svcb(&https.0),
+            RData::SVCB(svcb) => Self::svcb(svcb),
+            RData::NAPTR(naptr) => Self::Naptr {
+                order: naptr.order,
+                preference: naptr.preference,
+                flags: text(&naptr.flags),
+                services: text(&naptr.services),
+                regexp: text(&naptr.regexp),
+                replacement: naptr.replacement.to_string(),
+            },
+            RData::TLSA(tlsa) => Self::Tlsa {
+                cert_usage: tlsa.cert_usage.into(),
+                selector: tlsa.selector.into(),
+                matching: tlsa.matching.into(),
+                cert_data: hex(&tlsa.cert_data),
+            },
+            RData::SSHFP(sshfp) => Self::Sshfp {
+                algorithm: sshfp.algorithm.into(),
+                fingerprint_type: sshfp.fingerprint_type.into(),
+                fingerprint: hex(&sshfp.fingerprint),
+            },
+            RData::DNSSEC(DNSSECRData::DS(ds)) => Self::Ds {
+                key_tag: ds.key_tag(),
+                algorithm: ds.algorithm().into(),
+                algorithm_name: ds.algorithm().to_string(),
+                digest_type: ds.digest_type().into(),
+                digest: hex(ds.digest()),
+            },
+            RData::DNSSEC(DNSSECRData::DNSKEY(key)) => {
+                let algorithm = key.public_key().algorithm();
+                Self::Dnskey {
+                    flags: key.flags(),
+                    zone_key: key.zone_key(),
+                    secure_entry_point: key.secure_entry_point(),
+                    revoked: key.revoke(),
+                    algorithm: algorithm.into(),
+                    algorithm_name: algorithm.to_string(),
+                    key_tag: key.calculate_key_tag().ok(),
+                    public_key: base64::engine::general_purpose::STANDARD
+                        .encode(key.public_key().public_bytes()),
+                }
+            }
+            RData::DNSSEC(DNSSECRData::RRSIG(sig)) => {
+                let input = sig.input();
+                Self::Rrsig {
+                    type_covered: input.type_covered.to_string(),
+                    algorithm: input.algorithm.into(),
+                    labels: input.num_labels,
+                    original_ttl: input.original_ttl,
+                    expiration: input.sig_expiration.get(),
+                    inception: input.sig_inception.get(),
+                    key_tag: input.key_tag,
+                    signer_name: input.signer_name.to_string(),
+                }
+            }
+            other => Self::Other { rdata: other.to_string() },
+        }
+    }
+
+    fn svcb(svcb: &SVCB) -> Self {
+        Self::Svcb {
+            priority: svcb.svc_priority,
+            target: svcb.target_name.to_string(),
+            params: svcb
+                .svc_params
+                .iter()
+                .map(|(key, value)| SvcParam { key: key.to_string(), value: svc_value(value) })
+                .collect(),
+        }
+    }
+}
+
+/// SvcParam 的展示形式，列表值以逗号分隔（hickory 自带的 Display 会多出尾随逗号）。
+fn svc_value(value: &SvcParamValue) -> String {
+    let join = |items: Vec<String>| items.join(",");
+    match value {
+        SvcParamValue::Mandatory(keys) => join(keys.0.iter().map(ToString::to_string).collect()),
+        SvcParamValue::Alpn(alpn) => alpn.0.join(","),
+        SvcParamValue::NoDefaultAlpn => String::new(),
+        SvcParamValue::Port(port) => port.to_string(),
+        SvcParamValue::Ipv4Hint(hint) => join(hint.0.iter().map(ToString::to_string).collect()),
+        SvcParamValue::Ipv6Hint(hint) => join(hint.0.iter().map(ToString::to_string).collect()),
+        SvcParamValue::EchConfigList(ech) => base64::engine::general_purpose::STANDARD.encode(&ech.0),
+        SvcParamValue::Unknown(unknown) => hex(&unknown.0),
+    }
+}
+
+/// 小写十六进制编码。
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
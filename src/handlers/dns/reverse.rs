@@ -0,0 +1,96 @@
+//! IP 地址的反向解析（PTR）与正向确认（FCrDNS）。
+
+use std::net::IpAddr;
+
+use hickory_resolver::proto::rr::{Name, RecordType};
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use super::client::DnsClient;
+use super::records::RecordData;
+use super::{DnsResult, Status};
+
+/// 参与正向确认的 PTR 主机名上限。
+const MAX_HOSTS: usize = 8;
+
+#[derive(Serialize)]
+pub struct ReverseInfo {
+    pub ip: IpAddr,
+    /// 由 IP 生成的 in-addr.arpa / ip6.arpa 名称
+    pub ptr_name: String,
+    /// 至少一个 PTR 主机名正向解析后包含原 IP（FCrDNS 通过）
+    pub fcrdns: bool,
+    pub hosts: Vec<ForwardCheck>,
+}
+
+/// PTR 主机名的正向（A / AAAA）解析结果。
+#[derive(Serialize)]
+pub struct ForwardCheck {
+    pub hostname: String,
+    pub status: Status,
+    pub addresses: Vec<IpAddr>,
+    /// 正向结果包含原 IP
+    pub matches: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 查询 IP 的 PTR 记录，并对每个主机名做正向确认。
+pub(super) async fn lookup(client: &DnsClient, ip: IpAddr) -> DnsResult {
+    let name = Name::from(ip);
+    let mut result = DnsResult::query(client, "PTR".to_string(), RecordType::PTR, &name).await;
+
+    let targets: Vec<Name> = result
+        .answers
+        .iter()
+        .filter(|r| r.record_type == "PTR")
+        .filter_map(|r| match &r.data {
+            RecordData::Name { target } => Name::from_ascii(target).ok(),
+            _ => None,
+        })
+        .take(MAX_HOSTS)
+        .collect();
+
+    // 各主机名的正向查询并发进行，按 PTR 应答的顺序汇总
+    let forward_type = if ip.is_ipv4() { RecordType::A } else { RecordType::AAAA };
+    let mut tasks = JoinSet::new();
+    for (index, target) in targets.into_iter().enumerate() {
+        let client = client.clone();
+        tasks.spawn(async move { (index, confirm(&client, target, forward_type, ip).await) });
+    }
+    let mut checks: Vec<Option<ForwardCheck>> = std::iter::repeat_with(|| None).take(tasks.len()).collect();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((index, check)) = joined {
+            checks[index] = Some(check);
+        }
+    }
+    let hosts: Vec<ForwardCheck> = checks.into_iter().flatten().collect();
+
+    result.reverse = Some(ReverseInfo {
+        ip,
+        ptr_name: name.to_string(),
+        fcrdns: hosts.iter().any(|h| h.matches),
+        hosts,
+    });
+    result
+}
+
+/// 正向解析一个 PTR 主机名，检查结果是否包含原 IP。
+async fn confirm(client: &DnsClient, target: Name, forward_type: RecordType, ip: IpAddr) -> ForwardCheck {
+    let forward = DnsResult::query(client, forward_type.to_string(), forward_type, &target).await;
+    let addresses: Vec<IpAddr> = forward
+        .answers
+        .iter()
+        .filter_map(|r| match r.data {
+            RecordData::Address { address } => Some(address),
+            _ => None,
+        })
+        .collect();
+    ForwardCheck {
+        hostname: target.to_string(),
+        status: forward.status,
+        matches: addresses.contains(&ip),
+        addresses,
+        error: forward.error,
+    }
+}
@@ -0,0 +1,112 @@
+//! 测试用的本地 DNS 桩服务器：在若干回环地址的同一端口上监听 UDP 与 TCP，按回调应答。
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use hickory_resolver::proto::op::Message;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// 应答回调：参数为收到请求的服务器地址、协议（udp / tcp）与请求，返回 None 表示不应答。
+type Handler = dyn Fn(IpAddr, &'static str, &Message) -> Option<Message> + Send + Sync;
+
+/// 收到的一个请求。
+#[derive(Clone)]
+pub(super) struct Received {
+    pub server: IpAddr,
+    /// 客户端地址；同一 TCP 连接上的请求相同
+    pub peer: SocketAddr,
+    pub transport: &'static str,
+    pub request: Message,
+}
+
+pub(super) struct Stub {
+    pub port: u16,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Stub {
+    /// 在 127.0.0.`hosts` 上以同一端口启动。
+    pub async fn start<F>(hosts: &[u8], handler: F) -> Self
+    where
+        F: Fn(IpAddr, &'static str, &Message) -> Option<Message> + Send + Sync + 'static,
+    {
+        let handler: Arc<Handler> = Arc::new(handler);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sockets = loop {
+            if let Some(sockets) = bind_all(hosts).await {
+                break sockets;
+            }
+        };
+        let port = sockets[0].0.local_addr().unwrap().port();
+        for (udp, tcp) in sockets {
+            tokio::spawn(serve_udp(udp, handler.clone(), received.clone()));
+            tokio::spawn(serve_tcp(tcp, handler.clone(), received.clone()));
+        }
+        Self { port, received }
+    }
+
+    pub fn addr(&self, host: u8) -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::new(127, 0, 0, host).into(), self.port)
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+/// 以首个地址分配到的端口绑定全部地址，端口被占用时返回 None 重试。
+async fn bind_all(hosts: &[u8]) -> Option<Vec<(UdpSocket, TcpListener)>> {
+    let mut port = 0;
+    let mut sockets = Vec::new();
+    for &host in hosts {
+        let addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, host).into(), port);
+        let udp = UdpSocket::bind(addr).await.ok()?;
+        port = udp.local_addr().ok()?.port();
+        let tcp = TcpListener::bind((addr.ip(), port)).await.ok()?;
+        sockets.push((udp, tcp));
+    }
+    Some(sockets)
+}
+
+async fn serve_udp(udp: UdpSocket, handler: Arc<Handler>, received: Arc<Mutex<Vec<Received>>>) {
+    let server = udp.local_addr().unwrap().ip();
+    let mut buf = [0u8; 4096];
+    while let Ok((len, peer)) = udp.recv_from(&mut buf).await {
+        let request = Message::from_vec(&buf[..len]).unwrap();
+        let reply = handler(server, "udp", &request);
+        received.lock().unwrap().push(Received { server, peer, transport: "udp", request });
+        if let Some(reply) = reply {
+            udp.send_to(&reply.to_vec().unwrap(), peer).await.unwrap();
+        }
+    }
+}
+
+async fn serve_tcp(tcp: TcpListener, handler: Arc<Handler>, received: Arc<Mutex<Vec<Received>>>) {
+    let server = tcp.local_addr().unwrap().ip();
+    while let Ok((mut stream, peer)) = tcp.accept().await {
+        let (handler, received) = (handler.clone(), received.clone());
+        tokio::spawn(async move {
+            let mut len = [0u8; 2];
+            while stream.read_exact(&mut len).await.is_ok() {
+                let mut buf = vec![0u8; usize::from(u16::from_be_bytes(len))];
+                stream.read_exact(&mut buf).await.unwrap();
+                let request = Message::from_vec(&buf).unwrap();
+                let reply = handler(server, "tcp", &request);
+                received.lock().unwrap().push(Received { server, peer, transport: "tcp", request });
+                let Some(reply) = reply else { continue };
+                let reply = reply.to_vec().unwrap();
+                stream.write_all(&(reply.len() as u16).to_be_bytes()).await.unwrap();
+                stream.write_all(&reply).await.unwrap();
+            }
+        });
+    }
+}
+
+/// 复制请求的 ID、问题与 RD 位的空响应。
+pub(super) fn reply(request: &Message) -> Message {
+    let mut reply = Message::response(request.metadata.id, request.metadata.op_code);
+    reply.metadata.recursion_desired = request.metadata.recursion_desired;
+    reply.add_queries(request.queries.clone());
+    reply
+}
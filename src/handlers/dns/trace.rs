@@ -0,0 +1,642 @@
+//! 委派追踪（类似 dig +trace）：从根服务器逐级跟随引用直到权威应答，
+//! 并检查每一级委派的跛脚服务器与父子区 NS 是否一致。
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::State;
+use axum::Json;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{Name, RData, Record, RecordType};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use super::client::{DnsClient, Exchange, ResolverSettings, Transport};
+use super::records::DnsRecord;
+use super::{default_true, idn, parse_name, records, rcode_name, DnsConfig, DnsResult, SUPPORTED_TYPES};
+use crate::handlers::error::AppError;
+
+/// 根提示（IANA root hints 的 IPv4 地址）。
+const ROOT_HINTS: [(&str, &str); 13] = [
+    ("a.root-servers.net.", "198.41.0.4"),
+    ("b.root-servers.net.", "170.247.170.2"),
+    ("c.root-servers.net.", "192.33.4.12"),
+    ("d.root-servers.net.", "199.7.91.13"),
+    ("e.root-servers.net.", "192.203.230.10"),
+    ("f.root-servers.net.", "192.5.5.241"),
+    ("g.root-servers.net.", "192.112.36.4"),
+    ("h.root-servers.net.", "198.97.190.53"),
+    ("i.root-servers.net.", "192.36.148.17"),
+    ("j.root-servers.net.", "192.58.128.30"),
+    ("k.root-servers.net.", "193.0.14.129"),
+    ("l.root-servers.net.", "199.7.83.42"),
+    ("m.root-servers.net.", "202.12.27.33"),
+];
+
+/// 最多跟随的引用层数。
+const MAX_STEPS: usize = 16;
+/// 每一级最多尝试几台服务器，全部无响应即终止追踪。
+const MAX_ATTEMPTS: usize = 3;
+/// 每次委派检查最多查询的服务器地址数。
+const MAX_CHECK_SERVERS: usize = 16;
+/// 无胶水记录时最多解析的 NS 主机名数。
+const MAX_GLUELESS: usize = 8;
+
+#[derive(Deserialize)]
+pub struct TraceRequest {
+    pub domain: String,
+    #[serde(default = "default_type")]
+    pub record_type: String,
+    /// 起点服务器（IP 或 IP:端口），缺省为根提示；测试时可指向本地替身。
+    /// 引用中的服务器沿用应答服务器的端口
+    #[serde(default)]
+    pub root_servers: Vec<String>,
+    /// 检查每一级委派（跛脚服务器、父子 NS 一致性），默认开启
+    #[serde(default = "default_true")]
+    pub check_delegations: bool,
+    /// 无胶水 NS 主机名的递归解析器；传输协议与 EDNS / DO 选项同样用于迭代查询
+    #[serde(flatten)]
+    pub resolver: ResolverSettings,
+}
+
+fn default_type() -> String {
+    "A".to_string()
+}
+
+#[derive(Serialize)]
+pub struct TraceResponse {
+    pub name: String,
+    pub name_unicode: String,
+    pub record_type: String,
+    /// 最终应答（含状态、记录与应答服务器）；所有服务器均无响应时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<DnsResult>,
+    pub steps: Vec<TraceStep>,
+    pub delegations: Vec<DelegationCheck>,
+    /// 发现的问题（跛脚委派、NS 不一致、缺少胶水记录等）
+    pub issues: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TraceStep {
+    /// 本步询问的区
+    pub zone: String,
+    /// 应答的服务器名（起点服务器未知名称时为地址）
+    pub server: String,
+    pub address: SocketAddr,
+    pub latency_ms: f64,
+    pub rcode: String,
+    pub authoritative: bool,
+    /// referral / answer / nxdomain / nodata / error
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referral: Option<Referral>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub answers: Vec<DnsRecord>,
+    /// 在此之前尝试但无响应的服务器
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_servers: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Referral {
+    /// 被委派的子区
+    pub zone: String,
+    pub nameservers: Vec<NsEntry>,
+}
+
+#[derive(Serialize)]
+pub struct NsEntry {
+    pub name: String,
+    /// 附加区中的胶水地址
+    pub glue: Vec<IpAddr>,
+    /// 无胶水时通过递归解析得到的地址
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resolved: Vec<IpAddr>,
+}
+
+#[derive(Serialize)]
+pub struct DelegationCheck {
+    pub zone: String,
+    /// 父区引用中的 NS
+    pub parent_ns: Vec<String>,
+    /// 子区权威服务器返回的 NS（没有可用的权威服务器时为空）
+    pub child_ns: Vec<String>,
+    /// 父子区 NS 是否一致；未能取得子区 NS 时为空（无法判断）
+    pub ns_match: Option<bool>,
+    pub servers: Vec<ServerCheck>,
+}
+
+#[derive(Serialize)]
+pub struct ServerCheck {
+    pub name: String,
+    pub address: SocketAddr,
+    /// ok / lame / unreachable
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// 一台待询问的服务器。
+#[derive(Clone)]
+struct Server {
+    name: String,
+    addr: SocketAddr,
+}
+
+pub async fn trace(State(cfg): State<DnsConfig>, Json(req): Json<TraceRequest>) -> Result<Json<TraceResponse>, AppError> {
+    let name = parse_name(req.domain.trim())?;
+    let record_type = req.record_type.trim().to_ascii_uppercase();
+    let Some(rt) = records::parse_type(&record_type) else {
+        return Err(AppError::bad_request(format!(
+            "不支持的记录类型: {record_type}（支持 {}）",
+            SUPPORTED_TYPES.join(" / ")
+        )));
+    };
+    if matches!(req.resolver.transport, Transport::Dot | Transport::Doh) {
+        return Err(AppError::bad_request("追踪只支持 UDP / TCP（权威服务器一般不提供 DoT / DoH）"));
+    }
+
+    // 迭代查询：不设 RD，从根提示或指定的起点出发
+    let mut settings = req.resolver.clone();
+    settings.recursion_desired = false;
+    settings.nameservers = if req.root_servers.is_empty() {
+        ROOT_HINTS.iter().map(|(_, ip)| ip.to_string()).collect()
+    } else {
+        req.root_servers.clone()
+    };
+    let iterative = cfg.clients.client(&settings)?;
+    let glue_resolver = cfg.clients.client(&req.resolver)?;
+
+    let mut servers: Vec<Server> = iterative
+        .servers()
+        .iter()
+        .map(|addr| Server {
+            name: ROOT_HINTS
+                .iter()
+                .find(|(_, ip)| req.root_servers.is_empty() && ip.parse() == Ok(addr.ip()))
+                .map_or_else(|| addr.to_string(), |(host, _)| host.to_string()),
+            addr: *addr,
+        })
+        .collect();
+    let mut zone = Name::root();
+    let mut steps = Vec::new();
+    let mut delegations = Vec::new();
+    let mut issues = Vec::new();
+    let mut result = None;
+
+    for _ in 0..MAX_STEPS {
+        let mut failed_servers = Vec::new();
+        let mut answered = None;
+        for server in servers.iter().take(MAX_ATTEMPTS) {
+            match iterative.query_at(server.addr, &name, rt).await {
+                Ok(exchange) => {
+                    answered = Some((server.clone(), exchange));
+                    break;
+                }
+                Err(e) => failed_servers.push(format!("{} ({}): {e}", server.name, server.addr)),
+            }
+        }
+        let Some((server, exchange)) = answered else {
+            issues.push(format!("{} 的服务器均无响应：{}", zone, failed_servers.join("；")));
+            break;
+        };
+
+        let response = &exchange.response;
+        let metadata = &response.metadata;
+        let mut step = TraceStep {
+            zone: zone.to_string(),
+            server: server.name.clone(),
+            address: server.addr,
+            latency_ms: (exchange.elapsed.as_secs_f64() * 100_000.0).round() / 100.0,
+            rcode: rcode_name(metadata.response_code),
+            authoritative: metadata.authoritative,
+            kind: "answer",
+            referral: None,
+            answers: response.answers.iter().map(DnsRecord::from_record).collect(),
+            failed_servers,
+        };
+
+        // 带 AA 的应答即使在权威区附带本区 NS 也是最终应答（NODATA），不是引用
+        let cut = referral_zone(&response.authorities, &zone);
+        let is_referral = response.answers.is_empty()
+            && metadata.response_code == ResponseCode::NoError
+            && !metadata.authoritative
+            && cut.is_some();
+        if !is_referral {
+            step.kind = match metadata.response_code {
+                ResponseCode::NoError if response.answers.is_empty() => "nodata",
+                ResponseCode::NoError => "answer",
+                ResponseCode::NXDomain => "nxdomain",
+                _ => "error",
+            };
+            if !metadata.authoritative && step.kind != "error" {
+                issues.push(format!("{} ({}) 的最终应答未设置 AA 标志", server.name, server.addr));
+            }
+            steps.push(step);
+            result = Some(DnsResult::from_exchange(record_type.clone(), rt, &name, exchange));
+            break;
+        }
+
+        let cut = cut.unwrap_or_else(Name::root);
+        // 引用必须指向更接近查询名的子区，否则是无效（向上或旁路）引用
+        if !(zone.zone_of(&cut) && cut != zone && cut.zone_of(&name)) {
+            step.kind = "error";
+            issues.push(format!("{} ({}) 返回无效引用：{} 不在 {} 之下或与查询名无关", server.name, server.addr, cut, zone));
+            steps.push(step);
+            break;
+        }
+
+        let nameservers = referral_nameservers(&cut, &exchange, &glue_resolver, &mut issues).await;
+        let next: Vec<Server> = ordered_servers(&nameservers, server.addr.port());
+        step.kind = "referral";
+        step.referral = Some(Referral {
+            zone: cut.to_string(),
+            nameservers,
+        });
+        steps.push(step);
+
+        if req.check_delegations {
+            let parent_ns = step_ns_names(steps.last());
+            let check = check_delegation(&iterative, &cut, parent_ns, &next).await;
+            report_delegation(&check, &mut issues);
+            delegations.push(check);
+        }
+
+        if next.is_empty() {
+            issues.push(format!("{cut} 的 NS 均没有可用地址，无法继续"));
+            break;
+        }
+        servers = next;
+        zone = cut;
+    }
+    if result.is_none() && steps.len() >= MAX_STEPS {
+        issues.push(format!("引用超过 {MAX_STEPS} 层，已停止"));
+    }
+
+    let ascii = name.to_ascii();
+    Ok(Json(TraceResponse {
+        name_unicode: idn::to_unicode(&ascii),
+        name: ascii,
+        record_type,
+        result,
+        steps,
+        delegations,
+        issues,
+    }))
+}
+
+/// 权威区中 NS 记录的所有者名（引用指向的子区）：优先取 `zone` 的真子区，没有时取第一条 NS 的所有者名，交由调用方判为无效引用。
+fn referral_zone(authorities: &[Record], zone: &Name) -> Option<Name> {
+    let mut owners = authorities.iter().filter(|r| r.record_type() == RecordType::NS).map(|r| &r.name);
+    let first = owners.clone().next();
+    owners
+        .find(|owner| zone.zone_of(owner) && *owner != zone)
+        .or(first)
+        .cloned()
+}
+
+/// 整理引用中的 NS 与胶水；全部 NS 都没有胶水时递归解析其地址。
+async fn referral_nameservers(
+    cut: &Name,
+    response: &Exchange,
+    glue_resolver: &DnsClient,
+    issues: &mut Vec<String>,
+) -> Vec<NsEntry> {
+    let response = &response.response;
+    let mut ns_names: Vec<Name> = Vec::new();
+    for record in &response.authorities {
+        if let RData::NS(ns) = &record.data {
+            if record.name == *cut && !ns_names.contains(&ns.0) {
+                ns_names.push(ns.0.clone());
+            }
+        }
+    }
+
+    let mut entries = Vec::with_capacity(ns_names.len());
+    for ns in &ns_names {
+        let glue: Vec<IpAddr> = response
+            .additionals
+            .iter()
+            .filter(|r| r.name == *ns)
+            .filter_map(|r| r.data.ip_addr())
+            .collect();
+        // 区内（in-bailiwick）的 NS 必须带胶水，否则无法解析
+        if glue.is_empty() && cut.zone_of(ns) {
+            issues.push(format!("{cut} 的 NS {ns} 位于区内但引用中缺少胶水记录"));
+        }
+        entries.push(NsEntry {
+            name: ns.to_string(),
+            glue,
+            resolved: Vec::new(),
+        });
+    }
+
+    if entries.iter().all(|e| e.glue.is_empty()) {
+        for (entry, ns) in entries.iter_mut().zip(&ns_names).take(MAX_GLUELESS) {
+            if let Ok(exchange) = glue_resolver.query(ns, RecordType::A).await {
+                entry.resolved = exchange
+                    .response
+                    .answers
+                    .iter()
+                    .filter_map(|r| r.data.ip_addr())
+                    .collect();
+            }
+        }
+    }
+    entries
+}
+
+/// 下一级要询问的服务器：IPv4 优先，其次 IPv6；端口与给出引用的服务器相同。
+fn ordered_servers(nameservers: &[NsEntry], port: u16) -> Vec<Server> {
+    let mut servers: Vec<Server> = nameservers
+        .iter()
+        .flat_map(|ns| {
+            ns.glue.iter().chain(&ns.resolved).map(|ip| Server {
+                name: ns.name.clone(),
+                addr: SocketAddr::new(*ip, port),
+            })
+        })
+        .collect();
+    servers.sort_by_key(|s| s.addr.is_ipv6());
+    servers
+}
+
+fn step_ns_names(step: Option<&TraceStep>) -> Vec<String> {
+    step.and_then(|s| s.referral.as_ref())
+        .map(|r| r.nameservers.iter().map(|ns| ns.name.to_ascii_lowercase()).collect())
+        .unwrap_or_default()
+}
+
+/// 并发向子区的每台服务器查询 SOA（不设 RD），判断是否具有权威；再从一台权威服务器取子区 NS 与父区对比。
+async fn check_delegation(client: &DnsClient, cut: &Name, mut parent_ns: Vec<String>, servers: &[Server]) -> DelegationCheck {
+    let mut tasks = JoinSet::new();
+    for (index, server) in servers.iter().take(MAX_CHECK_SERVERS).enumerate() {
+        let (client, cut, addr) = (client.clone(), cut.clone(), server.addr);
+        tasks.spawn(async move { (index, client.query_at(addr, &cut, RecordType::SOA).await) });
+    }
+    let mut checks: Vec<Option<ServerCheck>> = servers.iter().take(MAX_CHECK_SERVERS).map(|_| None).collect();
+    while let Some(Ok((index, result))) = tasks.join_next().await {
+        let server = &servers[index];
+        let (status, detail) = match result {
+            Ok(exchange) => authority_status(cut, &exchange),
+            Err(e) => ("unreachable", Some(e.to_string())),
+        };
+        checks[index] = Some(ServerCheck {
+            name: server.name.clone(),
+            address: server.addr,
+            status,
+            detail,
+        });
+    }
+    let checks: Vec<ServerCheck> = checks.into_iter().flatten().collect();
+
+    let mut child_ns = Vec::new();
+    if let Some(ok) = checks.iter().find(|c| c.status == "ok") {
+        if let Ok(exchange) = client.query_at(ok.address, cut, RecordType::NS).await {
+            child_ns = exchange
+                .response
+                .answers
+                .iter()
+                .filter(|r| r.name == *cut)
+                .filter_map(|r| match &r.data {
+                    RData::NS(ns) => Some(ns.0.to_string().to_ascii_lowercase()),
+                    _ => None,
+                })
+                .collect();
+        }
+    }
+    parent_ns.sort();
+    parent_ns.dedup();
+    child_ns.sort();
+    child_ns.dedup();
+    DelegationCheck {
+        zone: cut.to_string(),
+        ns_match: (!child_ns.is_empty()).then(|| parent_ns == child_ns),
+        parent_ns,
+        child_ns,
+        servers: checks,
+    }
+}
+
+/// 服务器对该区的 SOA 查询给出带 AA 的应答才算权威，否则为跛脚委派。
+fn authority_status(cut: &Name, exchange: &Exchange) -> (&'static str, Option<String>) {
+    let response = &exchange.response;
+    let code = response.metadata.response_code;
+    if code != ResponseCode::NoError {
+        return ("lame", Some(format!("返回 {}", rcode_name(code))));
+    }
+    if !response.metadata.authoritative {
+        let detail = if referral_zone(&response.authorities, cut).is_some() {
+            "返回引用而非权威应答"
+        } else {
+            "应答未设置 AA 标志"
+        };
+        return ("lame", Some(detail.to_string()));
+    }
+    let has_soa = response
+        .answers
+        .iter()
+        .any(|r| r.name == *cut && r.record_type() == RecordType::SOA);
+    if has_soa {
+        ("ok", None)
+    } else {
+        ("lame", Some("权威应答中没有该区的 SOA".to_string()))
+    }
+}
+
+fn report_delegation(check: &DelegationCheck, issues: &mut Vec<String>) {
+    for server in &check.servers {
+        match server.status {
+            "lame" => issues.push(format!(
+                "跛脚委派：{} ({}) 对 {} 不具权威（{}）",
+                server.name,
+                server.address,
+                check.zone,
+                server.detail.as_deref().unwrap_or_default()
+            )),
+            "unreachable" => issues.push(format!("{} ({}) 无响应，无法检查 {}", server.name, server.address, check.zone)),
+            _ => {}
+        }
+    }
+    match check.ns_match {
+        Some(true) => {}
+        None => issues.push(format!("{} 没有权威服务器返回 NS，无法核对父子区 NS 是否一致", check.zone)),
+        Some(false) => {
+            let only_parent: Vec<&str> = check.parent_ns.iter().filter(|n| !check.child_ns.contains(n)).map(String::as_str).collect();
+            let only_child: Vec<&str> = check.child_ns.iter().filter(|n| !check.parent_ns.contains(n)).map(String::as_str).collect();
+            issues.push(format!(
+                "{} 的父区与子区 NS 不一致：仅父区 [{}]，仅子区 [{}]",
+                check.zone,
+                only_parent.join(", "),
+                only_child.join(", ")
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    use hickory_resolver::proto::op::Message;
+    use hickory_resolver::proto::rr::rdata::{A, NS, SOA};
+
+    use crate::handlers::dns::stub::{self, Stub};
+    use crate::handlers::dns::{ClientPool, Status};
+
+    fn name(s: &str) -> Name {
+        Name::from_ascii(s).unwrap()
+    }
+
+    fn ns(zone: &str, host: &str) -> Record {
+        Record::from_rdata(name(zone), 3600, RData::NS(NS(name(host))))
+    }
+
+    fn a(host: &str, last: u8) -> Record {
+        Record::from_rdata(name(host), 3600, RData::A(A(Ipv4Addr::new(127, 0, 0, last))))
+    }
+
+    fn soa(zone: &str) -> Record {
+        let soa = SOA::new(name(zone), name("hostmaster.test."), 1, 3600, 600, 86400, 300);
+        Record::from_rdata(name(zone), 3600, RData::SOA(soa))
+    }
+
+    /// 127.0.0.1 为根，.2 为 test. 的服务器，.3 为 example.test. 的权威，.4 为跛脚服务器（一律 REFUSED）。
+    /// example.test. 的父区 NS 为 ns1 / ns2 / ns5（ns5 缺胶水），子区 NS 为 ns1 / ns3。
+    fn hierarchy(server: IpAddr, _: &'static str, request: &Message) -> Option<Message> {
+        let query = &request.queries[0];
+        let (qname, qtype) = (query.name().to_ascii(), query.query_type());
+        let mut reply = stub::reply(request);
+        let IpAddr::V4(ip) = server else { return None };
+        match ip.octets()[3] {
+            1 => {
+                reply.add_authority(ns("test.", "ns1.nic.test."));
+                reply.add_additional(a("ns1.nic.test.", 2));
+            }
+            2 if qname == "test." => {
+                reply.metadata.authoritative = true;
+                reply.add_answer(if qtype == RecordType::NS { ns("test.", "ns1.nic.test.") } else { soa("test.") });
+            }
+            2 if qname.ends_with(".broken.test.") => {
+                reply.add_authority(ns("broken.test.", "ns.broken.test."));
+                reply.add_additional(a("ns.broken.test.", 4));
+            }
+            2 => {
+                for host in ["ns1.example.test.", "ns2.example.test.", "ns5.example.test."] {
+                    reply.add_authority(ns("example.test.", host));
+                }
+                reply.add_additional(a("ns1.example.test.", 3));
+                reply.add_additional(a("ns2.example.test.", 4));
+            }
+            3 => {
+                reply.metadata.authoritative = true;
+                match (qname.as_str(), qtype) {
+                    ("example.test.", RecordType::NS) => {
+                        reply.add_answer(ns("example.test.", "ns1.example.test."));
+                        reply.add_answer(ns("example.test.", "ns3.example.test."));
+                    }
+                    ("example.test.", RecordType::SOA) => {
+                        reply.add_answer(soa("example.test."));
+                    }
+                    ("www.example.test.", RecordType::A) => {
+                        reply.add_answer(Record::from_rdata(name(&qname), 300, RData::A(A(Ipv4Addr::new(192, 0, 2, 1)))));
+                    }
+                    // NODATA 时在权威区附带本区 NS（不是引用）
+                    ("www.example.test.", RecordType::TXT) => {
+                        reply.add_authority(ns("example.test.", "ns1.example.test."));
+                        reply.add_authority(ns("example.test.", "ns3.example.test."));
+                    }
+                    _ => reply.metadata.response_code = ResponseCode::NXDomain,
+                }
+            }
+            _ => reply.metadata.response_code = ResponseCode::Refused,
+        }
+        Some(reply)
+    }
+
+    async fn run(stub: &Stub, domain: &str) -> TraceResponse {
+        run_type(stub, domain, "A").await
+    }
+
+    async fn run_type(stub: &Stub, domain: &str, record_type: &str) -> TraceResponse {
+        let root = stub.addr(1).to_string();
+        let req: TraceRequest = serde_json::from_value(serde_json::json!({
+            "domain": domain,
+            "record_type": record_type,
+            "root_servers": [root],
+            "nameservers": [root],
+        }))
+        .unwrap();
+        let cfg = DnsConfig { clients: Arc::new(ClientPool::new(0)) };
+        match trace(State(cfg), Json(req)).await {
+            Ok(Json(response)) => response,
+            Err(e) => panic!("{}", e.message),
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_glue_and_checks_delegations() {
+        let stub = Stub::start(&[1, 2, 3, 4], hierarchy).await;
+        let trace = run(&stub, "www.example.test").await;
+
+        let kinds: Vec<_> = trace.steps.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, ["referral", "referral", "answer"]);
+        // 引用中的服务器沿用起点的非标准端口
+        let addresses: Vec<_> = trace.steps.iter().map(|s| s.address).collect();
+        assert_eq!(addresses, [stub.addr(1), stub.addr(2), stub.addr(3)]);
+        let referral = trace.steps[1].referral.as_ref().unwrap();
+        assert_eq!(referral.zone, "example.test.");
+        let glue: Vec<_> = referral.nameservers.iter().map(|ns| (ns.name.as_str(), ns.glue.clone())).collect();
+        assert_eq!(
+            glue,
+            [
+                ("ns1.example.test.", vec![stub.addr(3).ip()]),
+                ("ns2.example.test.", vec![stub.addr(4).ip()]),
+                ("ns5.example.test.", vec![]),
+            ]
+        );
+        assert_eq!(trace.result.unwrap().records, ["192.0.2.1"]);
+
+        let [tld, zone] = trace.delegations.as_slice() else {
+            panic!("应检查 2 级委派");
+        };
+        assert_eq!(tld.ns_match, Some(true));
+        assert_eq!(zone.ns_match, Some(false));
+        assert_eq!(zone.child_ns, ["ns1.example.test.", "ns3.example.test."]);
+        let statuses: Vec<_> = zone.servers.iter().map(|s| (s.name.as_str(), s.status)).collect();
+        assert_eq!(statuses, [("ns1.example.test.", "ok"), ("ns2.example.test.", "lame")]);
+
+        let has = |needle: &str| trace.issues.iter().any(|i| i.contains(needle));
+        assert!(has("ns5.example.test. 位于区内但引用中缺少胶水记录"));
+        assert!(has("跛脚委派：ns2.example.test."));
+        assert!(has("仅父区 [ns2.example.test., ns5.example.test.]，仅子区 [ns3.example.test.]"));
+        assert_eq!(trace.issues.len(), 3, "{:?}", trace.issues);
+    }
+
+    #[tokio::test]
+    async fn unknown_ns_match_when_every_server_is_lame() {
+        let stub = Stub::start(&[1, 2, 3, 4], hierarchy).await;
+        let trace = run(&stub, "x.broken.test").await;
+
+        let kinds: Vec<_> = trace.steps.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, ["referral", "referral", "error"]);
+        let zone = &trace.delegations[1];
+        assert_eq!(zone.ns_match, None);
+        assert!(zone.child_ns.is_empty());
+        assert!(trace.issues.iter().any(|i| i.contains("broken.test. 没有权威服务器返回 NS")));
+    }
+
+    #[tokio::test]
+    async fn authoritative_nodata_with_zone_ns_is_final() {
+        let stub = Stub::start(&[1, 2, 3, 4], hierarchy).await;
+        let trace = run_type(&stub, "www.example.test", "TXT").await;
+
+        let kinds: Vec<_> = trace.steps.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, ["referral", "referral", "nodata"]);
+        assert!(trace.steps[2].authoritative);
+        assert!(trace.result.unwrap().status == Status::NoData);
+        assert!(!trace.issues.iter().any(|i| i.contains("无效引用") || i.contains("AA 标志")), "{:?}", trace.issues);
+    }
+}
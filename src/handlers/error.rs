@@ -1,10 +1,15 @@
+use axum::extract::multipart::MultipartError;
+use axum::extract::rejection::JsonRejection;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 
 /// 统一错误类型：携带 HTTP 状态码，默认 500；输入校验类错误用 `bad_request` 返回 400。
+/// 带 `detail` 时响应体为 JSON（`{ error, message, ... }`），否则为纯文本。
 pub struct AppError {
     pub status: StatusCode,
     pub message: String,
+    pub detail: Option<serde_json::Value>,
 }
 
 impl AppError {
@@ -12,8 +17,55 @@ impl AppError {
         Self {
             status: StatusCode::BAD_REQUEST,
             message: message.into(),
+            detail: None,
         }
     }
+
+    /// 带机器可读错误码的结构化错误（JSON 响应体）。
+    pub fn coded(status: StatusCode, error: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            detail: Some(serde_json::json!({ "error": error })),
+        }
+    }
+
+    /// 超出大小 / 像素等上限：413，`error` 为错误码，`limit` 为生效的上限值。
+    pub fn too_large(error: &'static str, message: impl Into<String>, limit: u64) -> Self {
+        let mut e = Self::coded(StatusCode::PAYLOAD_TOO_LARGE, error, message);
+        if let Some(detail) = e.detail.as_mut() {
+            detail["limit"] = limit.into();
+        }
+        e
+    }
+
+    /// multipart 解析失败：请求体超过上限时返回 413，其余为 500。
+    pub fn multipart(e: MultipartError) -> Self {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return Self::body_too_large();
+        }
+        anyhow::anyhow!("解析 multipart 失败: {e}").into()
+    }
+
+    /// JSON 请求体解析失败：请求体超过上限时返回 413，其余沿用 axum 的状态码与信息。
+    pub fn json(e: JsonRejection) -> Self {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return Self::body_too_large();
+        }
+        Self {
+            status: e.status(),
+            message: e.body_text(),
+            detail: None,
+        }
+    }
+
+    fn body_too_large() -> Self {
+        Self::coded(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "body_too_large",
+            "请求体过大，超过服务端上限（--max-body-mb）",
+        )
+    }
 }
 
 impl<E: Into<anyhow::Error>> From<E> for AppError {
@@ -21,12 +73,65 @@ impl<E: Into<anyhow::Error>> From<E> for AppError {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
             message: e.into().to_string(),
+            detail: None,
         }
     }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (self.status, self.message).into_response()
+        match self.detail {
+            Some(mut detail) => {
+                detail["message"] = self.message.into();
+                (self.status, Json(detail)).into_response()
+            }
+            None => (self.status, self.message).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::extract::{FromRequest, Request};
+
+    async fn body_json(e: AppError) -> (StatusCode, serde_json::Value) {
+        let response = e.into_response();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn json_rejection(body: Vec<u8>) -> JsonRejection {
+        let request = Request::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        Json::<serde_json::Value>::from_request(request, &()).await.err().unwrap()
+    }
+
+    #[tokio::test]
+    async fn too_large_renders_code_limit_and_message() {
+        let (status, body) = body_json(AppError::too_large("upload_too_large", "文件过大", 42)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["error"], "upload_too_large");
+        assert_eq!(body["limit"], 42);
+        assert_eq!(body["message"], "文件过大");
+    }
+
+    #[tokio::test]
+    async fn oversized_json_bodies_map_to_413() {
+        // 超过 axum 默认的 2MB 请求体上限
+        let rejection = json_rejection(vec![b' '; 3 << 20]).await;
+        let (status, body) = body_json(AppError::json(rejection)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["error"], "body_too_large");
+
+        let rejection = json_rejection(b"{".to_vec()).await;
+        let expected = rejection.status();
+        let e = AppError::json(rejection);
+        assert_eq!(e.status, expected);
+        assert!(e.detail.is_none());
     }
 }
@@ -0,0 +1,423 @@
+use axum::extract::{Multipart, State};
+use axum::response::Response;
+use image::codecs::{
+    gif::{GifDecoder, GifEncoder, Repeat},
+    png::PngDecoder,
+    webp::WebPDecoder,
+};
+use image::{AnimationDecoder, Delay, DynamicImage, Frame, ImageDecoder, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Write};
+
+use super::{
+    attachment, do_resize, encode, load_image, output_name, parse_format, read_upload, resize,
+    ImageConfig, OutputFormat, ResizeOptions,
+};
+use crate::handlers::error::AppError;
+
+/// 单个动画允许的最大帧数（解码与合成共用）。
+const MAX_FRAMES: usize = 1000;
+
+/// 动图输入的处理方式。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationMode {
+    /// 输出格式支持动画（GIF / PNG / WEBP）时保留全部帧，否则取首帧
+    #[default]
+    Keep,
+    /// 只取首帧
+    First,
+    /// 导出全部帧为 PNG 并打包成 ZIP
+    Extract,
+}
+
+/// 解码动图的全部帧（已合成为完整画布，含每帧延时）；静态图或单帧动画返回 `None`。
+/// 每帧都是完整画布，像素预算按帧数累计。
+pub(super) fn decode_frames(
+    bytes: &[u8],
+    cfg: &ImageConfig,
+) -> Result<Option<Vec<Frame>>, AppError> {
+    let bad = |e: image::ImageError| AppError::bad_request(format!("解析动画帧失败: {e}"));
+    let frames = match image::guess_format(bytes) {
+        Ok(ImageFormat::Gif) => {
+            let mut decoder = GifDecoder::new(Cursor::new(bytes)).map_err(bad)?;
+            decoder.set_limits(cfg.decode_limits()).map_err(bad)?;
+            let (w, h) = decoder.dimensions();
+            collect_limited(decoder.into_frames(), w, h, cfg)?
+        }
+        Ok(ImageFormat::Png) => {
+            let mut decoder = PngDecoder::new(Cursor::new(bytes)).map_err(bad)?;
+            if !decoder.is_apng().map_err(bad)? {
+                return Ok(None);
+            }
+            decoder.set_limits(cfg.decode_limits()).map_err(bad)?;
+            let (w, h) = decoder.dimensions();
+            collect_limited(decoder.apng().map_err(bad)?.into_frames(), w, h, cfg)?
+        }
+        Ok(ImageFormat::WebP) => {
+            let mut decoder = WebPDecoder::new(Cursor::new(bytes)).map_err(bad)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.set_limits(cfg.decode_limits()).map_err(bad)?;
+            let (w, h) = decoder.dimensions();
+            collect_limited(decoder.into_frames(), w, h, cfg)?
+        }
+        _ => return Ok(None),
+    };
+    Ok((frames.len() > 1).then_some(frames))
+}
+
+/// 逐帧收集并校验帧数与累计像素预算，超限时立即停止解码。
+fn collect_limited(
+    frames: image::Frames<'_>,
+    w: u32,
+    h: u32,
+    cfg: &ImageConfig,
+) -> Result<Vec<Frame>, AppError> {
+    cfg.check_pixels(w, h, 1)?;
+    let mut out = Vec::new();
+    for frame in frames {
+        let frame = frame.map_err(|e| AppError::bad_request(format!("解析动画帧失败: {e}")))?;
+        out.push(frame);
+        if out.len() > MAX_FRAMES {
+            return Err(AppError::bad_request(format!("动画帧数过多，最多 {MAX_FRAMES} 帧")));
+        }
+        cfg.check_pixels(w, h, out.len() as u64)?;
+    }
+    Ok(out)
+}
+
+/// 对每一帧应用同一缩放参数，保留原延时。
+pub(super) fn resize_frames(
+    frames: Vec<Frame>,
+    resize: Option<&ResizeOptions>,
+    cfg: &ImageConfig,
+) -> Result<Vec<Frame>, AppError> {
+    let Some(resize) = resize else {
+        return Ok(frames);
+    };
+    let (delays, images): (Vec<_>, Vec<_>) = frames
+        .into_iter()
+        .map(|frame| (frame.delay(), DynamicImage::ImageRgba8(frame.into_buffer())))
+        .unzip();
+    let images = resize::resize_all(images, resize, cfg)?;
+    Ok(images
+        .into_iter()
+        .zip(delays)
+        .map(|(img, delay)| Frame::from_parts(img.to_rgba8(), 0, 0, delay))
+        .collect())
+}
+
+fn delay_ms(frame: &Frame) -> u32 {
+    let (numer, denom) = frame.delay().numer_denom_ms();
+    numer / denom.max(1)
+}
+
+/// 编码动画：GIF 走 image 自带编码器，PNG 输出 APNG，WEBP 通过 libwebp 输出动画 WebP。
+/// WebP 在 `lossless` 为 true 时无损，否则按 `quality`（默认 90）有损编码；`loop_count` 为 0 表示无限循环。
+pub(super) fn encode_animation(
+    frames: &[Frame],
+    format: OutputFormat,
+    quality: Option<u8>,
+    lossless: bool,
+    loop_count: u16,
+) -> anyhow::Result<Vec<u8>> {
+    let Some(first) = frames.first() else {
+        anyhow::bail!("动画没有任何帧");
+    };
+    let (w, h) = first.buffer().dimensions();
+    let mut buf = Vec::new();
+    match format {
+        OutputFormat::GIF => {
+            let mut encoder = GifEncoder::new_with_speed(&mut buf, 10);
+            encoder.set_repeat(match loop_count {
+                0 => Repeat::Infinite,
+                n => Repeat::Finite(n),
+            })?;
+            encoder.encode_frames(frames.iter().cloned())?;
+        }
+        OutputFormat::PNG => {
+            let mut encoder = png::Encoder::new(&mut buf, w, h);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, u32::from(loop_count))?;
+            let mut writer = encoder.write_header()?;
+            for frame in frames {
+                writer.set_frame_delay(delay_ms(frame).min(u16::MAX as u32) as u16, 1000)?;
+                writer.write_image_data(frame.buffer().as_raw())?;
+            }
+            writer.finish()?;
+        }
+        OutputFormat::WEBP => {
+            let mut config = libwebp_sys::WebPConfig::new()
+                .map_err(|_| anyhow::anyhow!("初始化 WebP 配置失败"))?;
+            if lossless {
+                config.lossless = 1;
+            } else {
+                config.quality = f32::from(quality.unwrap_or(90).clamp(1, 100));
+            }
+            buf = encode_webp_animation(frames, w, h, &config, loop_count)?;
+        }
+        other => anyhow::bail!("{} 不支持动画", other.extension().to_ascii_uppercase()),
+    }
+    Ok(buf)
+}
+
+/// 通过 libwebp 的 WebPAnimEncoder 编码动画 WebP。
+/// 不用 `webp::AnimEncoder`：它收尾时传入时间戳 0，libwebp 会拒绝并把末帧时长改成平均值。
+fn encode_webp_animation(
+    frames: &[Frame],
+    w: u32,
+    h: u32,
+    config: &libwebp_sys::WebPConfig,
+    loop_count: u16,
+) -> anyhow::Result<Vec<u8>> {
+    use libwebp_sys as sys;
+
+    let abi = sys::WebPGetMuxABIVersion();
+    // SAFETY: 各结构体均由 libwebp 的 Init 函数初始化；帧像素缓冲在 Add 调用期间保持有效，
+    // 每个 WebPPicture 与最终的 WebPData 都在离开作用域前释放，编码器在所有路径上 Delete。
+    unsafe {
+        let mut opts = std::mem::MaybeUninit::<sys::WebPAnimEncoderOptions>::uninit();
+        if sys::WebPAnimEncoderOptionsInitInternal(opts.as_mut_ptr(), abi) == 0 {
+            anyhow::bail!("初始化 WebP 动画参数失败");
+        }
+        let mut opts = opts.assume_init();
+        opts.anim_params.loop_count = i32::from(loop_count);
+        let enc = sys::WebPAnimEncoderNewInternal(w as i32, h as i32, &opts, abi);
+        if enc.is_null() {
+            anyhow::bail!("创建 WebP 动画编码器失败");
+        }
+        let error = |stage: &str| {
+            let msg = sys::WebPAnimEncoderGetError(enc);
+            let detail = if msg.is_null() {
+                String::new()
+            } else {
+                std::ffi::CStr::from_ptr(msg).to_string_lossy().into_owned()
+            };
+            anyhow::anyhow!("WebP 动画编码失败（{stage}）: {detail}")
+        };
+
+        let result = (|| {
+            let mut timestamp = 0i32;
+            for frame in frames {
+                let mut pic = sys::WebPPicture::new()
+                    .map_err(|_| anyhow::anyhow!("初始化 WebP 帧失败"))?;
+                pic.use_argb = 1;
+                pic.width = w as i32;
+                pic.height = h as i32;
+                let ok = sys::WebPPictureImportRGBA(&mut pic, frame.buffer().as_ptr(), (w * 4) as i32)
+                    != 0
+                    && sys::WebPAnimEncoderAdd(enc, &mut pic, timestamp, config) != 0;
+                sys::WebPPictureFree(&mut pic);
+                if !ok {
+                    return Err(error("添加帧"));
+                }
+                timestamp = timestamp.saturating_add(delay_ms(frame) as i32);
+            }
+            // 末尾传入总时长，末帧才能保留自己的延时
+            if sys::WebPAnimEncoderAdd(enc, std::ptr::null_mut(), timestamp, std::ptr::null()) == 0 {
+                return Err(error("结束"));
+            }
+            let mut data = sys::WebPData::default();
+            if sys::WebPAnimEncoderAssemble(enc, &mut data) == 0 {
+                return Err(error("封装"));
+            }
+            let out = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+            sys::WebPDataClear(&mut data);
+            Ok(out)
+        })();
+        sys::WebPAnimEncoderDelete(enc);
+        result
+    }
+}
+
+#[derive(Serialize)]
+struct FrameEntry {
+    file: String,
+    delay_ms: u32,
+    width: u32,
+    height: u32,
+}
+
+/// 导出全部帧为 PNG 并打包成 ZIP，附带记录帧延时的 `frames.json`。
+pub(super) fn frames_zip(frames: &[Frame]) -> anyhow::Result<Vec<u8>> {
+    use zip::write::SimpleFileOptions;
+
+    // PNG 本身已压缩，直接存储即可
+    let opts = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let mut manifest = Vec::with_capacity(frames.len());
+    for (i, frame) in frames.iter().enumerate() {
+        let file = format!("frame_{:04}.png", i + 1);
+        let img = DynamicImage::ImageRgba8(frame.buffer().clone());
+        zip.start_file(file.as_str(), opts)?;
+        zip.write_all(&encode(&img, OutputFormat::PNG, None)?)?;
+        manifest.push(FrameEntry {
+            file,
+            delay_ms: delay_ms(frame),
+            width: img.width(),
+            height: img.height(),
+        });
+    }
+    zip.start_file("frames.json", opts)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(zip.finish()?.into_inner())
+}
+
+// =====================================================================
+// 帧合成：多张上传图片按顺序合成为 GIF / APNG / 动画 WebP
+// =====================================================================
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct AnimateOptions {
+    /// 统一帧延时（毫秒）
+    pub delay_ms: u32,
+    /// 逐帧延时，优先于 `delay_ms`；长度不足时其余帧使用 `delay_ms`
+    pub delays: Vec<u32>,
+    /// 循环次数，0 为无限循环
+    pub loop_count: u16,
+    pub quality: Option<u8>,
+    pub resize: Option<ResizeOptions>,
+}
+
+impl Default for AnimateOptions {
+    fn default() -> Self {
+        Self {
+            delay_ms: 100,
+            delays: Vec::new(),
+            loop_count: 0,
+            quality: None,
+            resize: None,
+        }
+    }
+}
+
+pub async fn animate(State(cfg): State<ImageConfig>, mut mp: Multipart) -> Result<Response, AppError> {
+    let mut images: Vec<Vec<u8>> = Vec::new();
+    let mut format: Option<OutputFormat> = None;
+    let mut options = AnimateOptions::default();
+    let mut original_name: String = "animation".into();
+
+    while let Some(field) = mp
+        .next_field()
+        .await
+        .map_err(AppError::multipart)?
+    {
+        match field.name().unwrap_or("") {
+            "file" => {
+                if images.is_empty() {
+                    if let Some(fname) = field.file_name() {
+                        original_name = fname.to_string();
+                    }
+                }
+                if images.len() >= MAX_FRAMES {
+                    return Err(AppError::bad_request(format!("帧数过多，最多 {MAX_FRAMES} 帧")));
+                }
+                images.push(read_upload(field, &cfg).await?);
+            }
+            "format" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("读取 format 字段失败: {e}"))?;
+                format = parse_format(&text);
+            }
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| AppError::bad_request(format!("options 解析失败: {e}")))?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if images.len() < 2 {
+        return Err(AppError::bad_request("请至少上传两帧图片"));
+    }
+    let format = match format {
+        Some(f @ (OutputFormat::GIF | OutputFormat::PNG | OutputFormat::WEBP)) => f,
+        _ => return Err(AppError::bad_request("format 须为 GIF / PNG / WEBP")),
+    };
+
+    cfg.run(move |cfg| {
+        // 以首帧（缩放后）尺寸为画布，其余帧拉伸到同一尺寸
+        let mut canvas: Option<(u32, u32)> = None;
+        let mut frames = Vec::with_capacity(images.len());
+        for (i, bytes) in images.iter().enumerate() {
+            // 合成后的每帧都是画布大小，解码下一帧前先按已有帧数累计校验像素预算
+            if let Some((w, h)) = canvas {
+                cfg.check_pixels(w, h, i as u64 + 1)?;
+            }
+            let img = load_image(bytes, &Default::default(), cfg).map_err(|mut e| {
+                e.message = format!("第 {} 帧: {}", i + 1, e.message);
+                e
+            })?;
+            let img = match options.resize.as_ref() {
+                Some(resize) => do_resize(&img, resize, cfg)?,
+                None => img,
+            };
+            let (w, h) = *canvas.get_or_insert((img.width(), img.height()));
+            let rgba: RgbaImage = if (img.width(), img.height()) == (w, h) {
+                img.to_rgba8()
+            } else {
+                let filter = options.resize.as_ref().map(|r| r.filter).unwrap_or_default();
+                img.resize_exact(w, h, filter.into()).to_rgba8()
+            };
+            let ms = options.delays.get(i).copied().unwrap_or(options.delay_ms);
+            frames.push(Frame::from_parts(rgba, 0, 0, Delay::from_numer_denom_ms(ms, 1)));
+        }
+
+        // 合成接口没有 lossless 参数：给出低于 100 的 quality 时有损
+        let lossless = options.quality.is_none_or(|q| q >= 100);
+        let encoded = encode_animation(&frames, format, options.quality, lossless, options.loop_count)?;
+        let (w, h) = canvas.unwrap_or_default();
+        Ok(attachment(
+            encoded,
+            format.mime(),
+            &output_name(&original_name, format.extension()),
+            vec![
+                ("X-Width", w.to_string()),
+                ("X-Height", h.to_string()),
+                ("X-Frames", frames.len().to_string()),
+            ],
+        ))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::image::tests::config;
+    use axum::http::StatusCode;
+
+    fn gif(frames: u32) -> Vec<u8> {
+        let frames: Vec<Frame> = (0..frames)
+            .map(|i| Frame::new(RgbaImage::from_pixel(10, 10, image::Rgba([i as u8 * 40, 0, 0, 255]))))
+            .collect();
+        encode_animation(&frames, OutputFormat::GIF, None, false, 0).unwrap()
+    }
+
+    fn collect(bytes: &[u8], max_pixels: u64) -> Result<Vec<Frame>, AppError> {
+        let decoder = GifDecoder::new(Cursor::new(bytes)).unwrap();
+        collect_limited(decoder.into_frames(), 10, 10, &config(max_pixels))
+    }
+
+    #[test]
+    fn frames_are_held_to_the_cumulative_pixel_budget() {
+        let bytes = gif(3);
+        assert_eq!(collect(&bytes, 300).ok().unwrap().len(), 3);
+
+        let e = collect(&bytes, 299).err().unwrap();
+        assert_eq!(e.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(e.message.contains("× 3 帧"), "{}", e.message);
+        // 单帧就超出预算时不解码任何帧
+        let e = collect(&bytes, 99).err().unwrap();
+        assert!(!e.message.contains("帧"), "{}", e.message);
+    }
+}
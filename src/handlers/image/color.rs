@@ -0,0 +1,302 @@
+use image::{DynamicImage, ImageBuffer, Pixel, RgbImage};
+use moxcms::{ColorProfile, DataColorSpace, Layout, ProfileText, TransformExecutor, TransformOptions};
+use serde::Deserialize;
+use std::fmt;
+use std::io::Cursor;
+use zune_jpeg::zune_core::colorspace::ColorSpace as ZuneColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+use super::OutputFormat;
+use crate::handlers::error::AppError;
+
+/// 嵌入 ICC 配置文件的处理方式（CMYK 图片总是按配置文件转换）。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IccMode {
+    /// 忽略配置文件，像素按原值输出
+    #[default]
+    Ignore,
+    /// 按配置文件转换到 sRGB
+    Srgb,
+}
+
+/// 图片的色彩模型、位深与 ICC 配置文件名，用于 `X-Color-Space-*` 响应头。
+#[derive(Debug, Clone)]
+pub(super) struct ColorInfo {
+    pub model: &'static str,
+    pub depth: u8,
+    pub profile: Option<String>,
+}
+
+impl ColorInfo {
+    fn of(img: &DynamicImage) -> Self {
+        let color = img.color();
+        let model = match (color.has_color(), color.has_alpha()) {
+            (true, true) => "RGBA",
+            (true, false) => "RGB",
+            (false, true) => "GrayA",
+            (false, false) => "Gray",
+        };
+        let depth = (color.bits_per_pixel() / u16::from(color.channel_count())) as u8;
+        Self { model, depth, profile: None }
+    }
+
+    /// 按目标格式实际写出的通道与位深描述输出色彩空间；PNG 以编码器选定的色彩类型为准。
+    pub(super) fn output(img: &DynamicImage, format: OutputFormat, png_color: Option<&str>, srgb: bool) -> Self {
+        let mut info = Self::of(img);
+        match (format, png_color.and_then(|c| c.split_once('/'))) {
+            (_, Some((color, depth))) => {
+                info.model = match color {
+                    "Grayscale" => "Gray",
+                    "GrayscaleAlpha" => "GrayA",
+                    "Rgb" => "RGB",
+                    "Indexed" => "Indexed",
+                    _ => "RGBA",
+                };
+                info.depth = depth.parse().unwrap_or(8);
+            }
+            (OutputFormat::PNG | OutputFormat::SVG, None) => info.depth = info.depth.min(16),
+            (OutputFormat::JPEG, None) => (info.model, info.depth) = ("RGB", 8),
+            (OutputFormat::GIF, None) => (info.model, info.depth) = ("Indexed", 8),
+            (_, None) => (info.model, info.depth) = ("RGBA", 8),
+        }
+        info.profile = srgb.then(|| "sRGB".to_string());
+        info
+    }
+}
+
+impl fmt::Display for ColorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}-bit", self.model, self.depth)?;
+        if let Some(profile) = &self.profile {
+            write!(f, " ({profile})")?;
+        }
+        Ok(())
+    }
+}
+
+/// 是否为高位深（16 位整数或浮点）图片。
+pub(super) fn is_high_depth(img: &DynamicImage) -> bool {
+    img.color().bytes_per_pixel() > img.color().channel_count()
+}
+
+/// CMYK JPEG 的解码结果。
+pub(super) enum CmykJpeg {
+    /// 不是 CMYK / YCCK JPEG
+    NotCmyk,
+    /// CMYK 但没有可用的 CMYK 配置文件，只能交给 image 的简化换算
+    NoProfile,
+    /// 已按配置文件转换到 sRGB
+    Converted(DynamicImage, ColorInfo),
+}
+
+/// 读取 JPEG 头，若为 CMYK / YCCK 且带 CMYK 配置文件，按配置文件转换到 sRGB。
+pub(super) fn decode_cmyk_jpeg(bytes: &[u8], icc: Option<&[u8]>) -> Result<CmykJpeg, AppError> {
+    let mut decoder = JpegDecoder::new(Cursor::new(bytes));
+    if decoder.decode_headers().is_err() {
+        return Ok(CmykJpeg::NotCmyk);
+    }
+    let input = match decoder.input_colorspace() {
+        Some(cs @ (ZuneColorSpace::CMYK | ZuneColorSpace::YCCK)) => cs,
+        _ => return Ok(CmykJpeg::NotCmyk),
+    };
+    let Some(profile) = icc
+        .and_then(|icc| ColorProfile::new_from_slice(icc).ok())
+        .filter(|p| p.color_space == DataColorSpace::Cmyk)
+    else {
+        return Ok(CmykJpeg::NoProfile);
+    };
+    let (w, h) = decoder.dimensions().unwrap_or_default();
+
+    // 按原色彩空间输出四通道原始数据，避免解码器自带的简化 CMYK → RGB 换算
+    let options = DecoderOptions::default()
+        .jpeg_set_out_colorspace(input)
+        .set_strict_mode(false)
+        .set_max_width(usize::MAX)
+        .set_max_height(usize::MAX);
+    let mut raw = JpegDecoder::new_with_options(Cursor::new(bytes), options)
+        .decode()
+        .map_err(|e| AppError::bad_request(format!("解析 CMYK JPEG 失败: {e:?}")))?;
+    if input == ZuneColorSpace::YCCK {
+        for px in raw.chunks_exact_mut(4) {
+            let [r, g, b] = ycc_to_rgb(px[0], px[1], px[2]);
+            px[..3].copy_from_slice(&[r, g, b]);
+        }
+    }
+    // 带 Adobe APP14 标记的文件按反相存储（255 表示无墨），其余按原值
+    if has_adobe_marker(bytes) {
+        for v in raw.iter_mut() {
+            *v = 255 - *v;
+        }
+    }
+
+    let transform = profile
+        .create_transform_8bit(Layout::Rgba, &ColorProfile::new_srgb(), Layout::Rgb, TransformOptions::default())
+        .map_err(|e| AppError::bad_request(format!("CMYK 配置文件不可用: {e}")))?;
+    let mut rgb = vec![0u8; w * h * 3];
+    transform
+        .transform(&raw, &mut rgb)
+        .map_err(|e| anyhow::anyhow!("CMYK 转换失败: {e}"))?;
+    let image = RgbImage::from_raw(w as u32, h as u32, rgb)
+        .ok_or_else(|| anyhow::anyhow!("CMYK 转换结果尺寸不符"))?;
+    let info = ColorInfo { model: "CMYK", depth: 8, profile: Some(profile_name(&profile)) };
+    Ok(CmykJpeg::Converted(DynamicImage::ImageRgb8(image), info))
+}
+
+/// 扫描到 SOS 之前的标记段，查找 Adobe APP14。
+fn has_adobe_marker(bytes: &[u8]) -> bool {
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            return false;
+        }
+        let marker = bytes[pos + 1];
+        match marker {
+            // 填充字节
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            0xDA | 0xD9 => return false,
+            // 无长度字段的标记
+            0x01 | 0xD0..=0xD7 => {
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let len = usize::from(u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]));
+        if len < 2 {
+            return false;
+        }
+        if marker == 0xEE && bytes.get(pos + 4..pos + 9) == Some(b"Adobe".as_slice()) {
+            return true;
+        }
+        pos += 2 + len;
+    }
+    false
+}
+
+/// JFIF YCbCr → RGB。
+fn ycc_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let (y, cb, cr) = (f32::from(y), f32::from(cb) - 128.0, f32::from(cr) - 128.0);
+    let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    [
+        clamp(y + 1.402 * cr),
+        clamp(y - 0.344_136 * cb - 0.714_136 * cr),
+        clamp(y + 1.772 * cb),
+    ]
+}
+
+/// 描述解码后的图片色彩；`icc` 为嵌入的配置文件，`mode` 为 Srgb 时转换到 sRGB。
+/// 返回（可能已转换的）图片、输入色彩描述，以及是否已转换。
+pub(super) fn apply_icc(
+    img: DynamicImage,
+    icc: Option<&[u8]>,
+    mode: IccMode,
+) -> Result<(DynamicImage, ColorInfo, bool), AppError> {
+    let mut info = ColorInfo::of(&img);
+    let Some(profile) = icc.and_then(|icc| ColorProfile::new_from_slice(icc).ok()) else {
+        return Ok((img, info, false));
+    };
+    info.profile = Some(profile_name(&profile));
+    if mode != IccMode::Srgb || profile.color_space != DataColorSpace::Rgb || !img.color().has_color() {
+        return Ok((img, info, false));
+    }
+
+    let srgb = ColorProfile::new_srgb();
+    let opts = TransformOptions::default();
+    let err = |e: moxcms::CmsError| AppError::bad_request(format!("ICC 配置文件不可用: {e}"));
+    let img = match img {
+        DynamicImage::ImageRgb8(mut buf) => {
+            let t = profile.create_transform_8bit(Layout::Rgb, &srgb, Layout::Rgb, opts).map_err(err)?;
+            transform_in_place(&mut buf, t.as_ref())?;
+            DynamicImage::ImageRgb8(buf)
+        }
+        DynamicImage::ImageRgba8(mut buf) => {
+            let t = profile.create_transform_8bit(Layout::Rgba, &srgb, Layout::Rgba, opts).map_err(err)?;
+            transform_in_place(&mut buf, t.as_ref())?;
+            DynamicImage::ImageRgba8(buf)
+        }
+        DynamicImage::ImageRgb16(mut buf) => {
+            let t = profile.create_transform_16bit(Layout::Rgb, &srgb, Layout::Rgb, opts).map_err(err)?;
+            transform_in_place(&mut buf, t.as_ref())?;
+            DynamicImage::ImageRgb16(buf)
+        }
+        // 浮点图片按是否带透明度转为 RGB16 / RGBA16 再转换
+        other if !other.color().has_alpha() => {
+            let mut buf = other.to_rgb16();
+            let t = profile.create_transform_16bit(Layout::Rgb, &srgb, Layout::Rgb, opts).map_err(err)?;
+            transform_in_place(&mut buf, t.as_ref())?;
+            DynamicImage::ImageRgb16(buf)
+        }
+        other => {
+            let mut buf = other.to_rgba16();
+            let t = profile.create_transform_16bit(Layout::Rgba, &srgb, Layout::Rgba, opts).map_err(err)?;
+            transform_in_place(&mut buf, t.as_ref())?;
+            DynamicImage::ImageRgba16(buf)
+        }
+    };
+    Ok((img, info, true))
+}
+
+fn transform_in_place<P, T>(
+    buf: &mut ImageBuffer<P, Vec<T>>,
+    transform: &(dyn TransformExecutor<T> + Send + Sync),
+) -> Result<(), AppError>
+where
+    P: Pixel<Subpixel = T>,
+    T: Copy + Default,
+{
+    let src = buf.as_raw().clone();
+    transform
+        .transform(&src, buf)
+        .map_err(|e| anyhow::anyhow!("ICC 转换失败: {e}"))?;
+    Ok(())
+}
+
+/// 配置文件的描述文本，取不到时按色彩空间命名。
+fn profile_name(profile: &ColorProfile) -> String {
+    let text = match &profile.description {
+        Some(ProfileText::PlainString(s)) => Some(s.clone()),
+        Some(ProfileText::Localizable(list)) => list.first().map(|l| l.value.clone()),
+        Some(ProfileText::Description(d)) => Some(d.ascii_string.clone()),
+        None => None,
+    };
+    // 只保留可放进响应头的可打印 ASCII
+    let text: String = text
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+        .collect();
+    match text.trim() {
+        "" => format!("{:?} ICC", profile.color_space),
+        t => t.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, Rgba};
+
+    #[test]
+    fn icc_conversion_keeps_16_bit_layout() {
+        let p3 = ColorProfile::new_display_p3().encode().unwrap();
+        let rgb = image::ImageBuffer::from_pixel(2, 2, Rgb([40_000u16, 20_001, 10_003]));
+        let rgba = image::ImageBuffer::from_pixel(2, 2, Rgba([40_000u16, 20_001, 10_003, 30_000]));
+        let float = image::Rgb32FImage::from_pixel(2, 2, Rgb([0.6, 0.3, 0.15]));
+        for (img, expected) in [
+            (DynamicImage::ImageRgb16(rgb), image::ColorType::Rgb16),
+            (DynamicImage::ImageRgba16(rgba), image::ColorType::Rgba16),
+            (DynamicImage::ImageRgb32F(float), image::ColorType::Rgb16),
+        ] {
+            let before = img.to_rgba16();
+            let (out, _, converted) = apply_icc(img, Some(&p3), IccMode::Srgb).ok().unwrap();
+            assert!(converted);
+            assert_eq!(out.color(), expected);
+            assert_ne!(out.to_rgba16(), before, "应按配置文件转换像素");
+        }
+    }
+}
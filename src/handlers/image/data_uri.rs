@@ -0,0 +1,178 @@
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Multipart, Query, State};
+use axum::response::Response;
+use axum::Json;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::{DecodePaddingMode, Engine};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use super::{attachment, read_convert_upload, run_convert, svg, ImageConfig};
+use crate::handlers::error::AppError;
+
+/// 解码时同时接受有无 `=` 填充的输入。
+const LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+const LENIENT_URL_SAFE: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// 编码结果的输出形式。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataUriOutput {
+    /// `data:<mime>;base64,...`
+    #[default]
+    Uri,
+    /// `background-image: url("data:...");`
+    Css,
+    /// 纯 Base64，不带前缀
+    Base64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct EncodeQuery {
+    #[serde(default)]
+    pub output: DataUriOutput,
+}
+
+#[derive(Serialize)]
+pub struct DataUriResponse {
+    pub mime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// 上传文件大小（字节）
+    pub original_size: usize,
+    /// 编码前的二进制大小（转换后）
+    pub size: usize,
+    /// 输出文本的长度（约为 `size` 的 4/3）
+    pub encoded_size: usize,
+    pub output: String,
+}
+
+/// 图片 → data URI / CSS。带 `format` 时先走 `convert` 的完整管线（缩放、压缩、水印等），
+/// 否则原样编码上传内容。
+pub async fn to_data_uri(
+    State(cfg): State<ImageConfig>,
+    Query(query): Query<EncodeQuery>,
+    mut mp: Multipart,
+) -> Result<Json<DataUriResponse>, AppError> {
+    let upload = read_convert_upload(&mut mp, &cfg).await?;
+    let original_size = upload.bytes.len();
+
+    let (bytes, mime) = match upload.format {
+        Some(format) => {
+            let out = cfg
+                .run(move |cfg| {
+                    run_convert(&upload.bytes, format, upload.options, upload.logo.as_deref(), cfg)
+                })
+                .await?;
+            if out.mime == "application/zip" {
+                return Err(AppError::bad_request("data URI 不支持导出帧 ZIP"));
+            }
+            (out.bytes, out.mime)
+        }
+        None => {
+            let (_, mime) = detect(&upload.bytes)?;
+            (upload.bytes, mime)
+        }
+    };
+    let (width, height) = match dimensions(&bytes) {
+        Some((w, h)) => (Some(w), Some(h)),
+        None => (None, None),
+    };
+
+    let b64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    let output = match query.output {
+        DataUriOutput::Uri => format!("data:{mime};base64,{b64}"),
+        DataUriOutput::Css => format!("background-image: url(\"data:{mime};base64,{b64}\");"),
+        DataUriOutput::Base64 => b64,
+    };
+    Ok(Json(DataUriResponse {
+        mime: mime.to_string(),
+        width,
+        height,
+        original_size,
+        size: bytes.len(),
+        encoded_size: output.len(),
+        output,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct DecodeRequest {
+    /// `data:` URI、CSS `url(...)` 片段或纯 Base64
+    pub data: String,
+}
+
+/// data URI / Base64 → 图片文件下载；格式按内容识别，声明的 MIME 仅作参考。
+pub async fn from_data_uri(req: Result<Json<DecodeRequest>, JsonRejection>) -> Result<Response, AppError> {
+    let Json(req) = req.map_err(AppError::json)?;
+    let (declared, payload) = split_data_uri(&req.data)?;
+    let cleaned: String = payload.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let bytes = LENIENT
+        .decode(&cleaned)
+        .or_else(|_| LENIENT_URL_SAFE.decode(&cleaned))
+        .map_err(|e| AppError::bad_request(format!("Base64 解码失败: {e}")))?;
+
+    let (extension, mime) = detect(&bytes)?;
+    let mut headers = Vec::new();
+    if let Some((w, h)) = dimensions(&bytes) {
+        headers.push(("X-Width", w.to_string()));
+        headers.push(("X-Height", h.to_string()));
+    }
+    headers.push(("X-Detected-Mime", mime.to_string()));
+    if let Some(declared) = declared.filter(|d| !d.eq_ignore_ascii_case(mime)) {
+        headers.push(("X-Declared-Mime", declared));
+    }
+    Ok(attachment(bytes, mime, &format!("image.{extension}"), headers))
+}
+
+/// 拆出 data URI 中声明的 MIME 与 Base64 负载；兼容 CSS `url("...")` 包裹与纯 Base64。
+fn split_data_uri(input: &str) -> Result<(Option<String>, &str), AppError> {
+    let mut text = input.trim();
+    if let Some(start) = text.find("url(") {
+        let inner = &text[start + 4..];
+        let end = inner.rfind(')').unwrap_or(inner.len());
+        text = inner[..end].trim().trim_matches(|c| c == '"' || c == '\'');
+    }
+    let Some(rest) = text.strip_prefix("data:") else {
+        return Ok((None, text));
+    };
+    let Some((meta, payload)) = rest.split_once(',') else {
+        return Err(AppError::bad_request("data URI 缺少 ',' 分隔的数据部分"));
+    };
+    if !meta.split(';').any(|p| p.eq_ignore_ascii_case("base64")) {
+        return Err(AppError::bad_request("仅支持 base64 编码的 data URI"));
+    }
+    let mime = meta.split(';').next().filter(|m| !m.is_empty()).map(str::to_string);
+    Ok((mime, payload))
+}
+
+/// 按内容识别图片格式，返回扩展名与 MIME。
+fn detect(bytes: &[u8]) -> Result<(&'static str, &'static str), AppError> {
+    if svg::is_svg(bytes) {
+        return Ok(("svg", "image/svg+xml"));
+    }
+    let format = image::guess_format(bytes)
+        .map_err(|_| AppError::bad_request("无法识别的图片格式"))?;
+    let extension = match format {
+        ImageFormat::Jpeg => "jpg",
+        other => other.extensions_str().first().copied().unwrap_or("bin"),
+    };
+    Ok((extension, format.to_mime_type()))
+}
+
+/// 读取尺寸（只解析文件头）；SVG 等栅格解码器不认识的格式返回 None。
+fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
@@ -0,0 +1,237 @@
+use axum::extract::{Multipart, State};
+use axum::response::Response;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage, Rgba, RgbaImage};
+use serde::Deserialize;
+
+use super::{
+    attachment, encode, hash, load_image, parse_format, parse_hex_color, read_upload, ImageConfig,
+    OutputFormat,
+};
+use crate::handlers::error::AppError;
+
+/// SSIM 滑动窗口边长与步长。
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: u32 = 4;
+
+/// 两张图尺寸不一致时的对齐方式。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffAlign {
+    /// 将第二张图缩放到第一张图的尺寸
+    #[default]
+    Resize,
+    /// 以两者最大宽高为画布左上角对齐，缺失区域按透明处理（计为差异）
+    Pad,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// 任一通道差值超过该值才算变化像素（0–255），默认 0 即严格比较
+    pub threshold: u8,
+    pub align: DiffAlign,
+    /// 差异像素的高亮颜色（#rrggbb）
+    pub color: String,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0,
+            align: DiffAlign::Resize,
+            color: "#ff0000".into(),
+        }
+    }
+}
+
+pub async fn diff(State(cfg): State<ImageConfig>, mut mp: Multipart) -> Result<Response, AppError> {
+    let mut a: Option<Vec<u8>> = None;
+    let mut b: Option<Vec<u8>> = None;
+    let mut format = OutputFormat::PNG;
+    let mut options = DiffOptions::default();
+
+    while let Some(field) = mp
+        .next_field()
+        .await
+        .map_err(AppError::multipart)?
+    {
+        match field.name().unwrap_or("") {
+            "a" => a = Some(read_upload(field, &cfg).await?),
+            "b" => b = Some(read_upload(field, &cfg).await?),
+            "format" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("读取 format 字段失败: {e}"))?;
+                format = parse_format(&text)
+                    .ok_or_else(|| AppError::bad_request("无效的 format 字段"))?;
+            }
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| AppError::bad_request(format!("options 解析失败: {e}")))?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (Some(a), Some(b)) = (a, b) else {
+        return Err(AppError::bad_request("缺少 a 或 b 字段"));
+    };
+    let highlight = parse_hex_color(&options.color)
+        .ok_or_else(|| AppError::bad_request("color 须为 #rrggbb 格式"))?;
+    cfg.run(move |cfg| {
+        let a = load_image(&a, &Default::default(), cfg)?;
+        let b = load_image(&b, &Default::default(), cfg)?;
+        // ICO 每张图最大 256×256，差异图与对齐后的尺寸相同，编码前先检查
+        if matches!(format, OutputFormat::ICO) {
+            let (w, h) = match options.align {
+                DiffAlign::Resize => a.dimensions(),
+                DiffAlign::Pad => (a.width().max(b.width()), a.height().max(b.height())),
+            };
+            if w > 256 || h > 256 {
+                return Err(AppError::bad_request(format!("ICO 最大 256×256，差异图为 {w}×{h}，请改用其他格式")));
+            }
+        }
+
+        let phash_distance = hash::hamming(hash::phash(&a), hash::phash(&b));
+        let (size_a, size_b) = (a.dimensions(), b.dimensions());
+        let (ra, rb) = align(&a, &b, options.align);
+        let (w, h) = ra.dimensions();
+
+        // 差异图：未变化像素为淡化后的灰度底图，变化像素涂高亮色
+        let mut out = RgbaImage::new(w, h);
+        let mut mismatch = 0u64;
+        for ((pa, pb), po) in ra.pixels().zip(rb.pixels()).zip(out.pixels_mut()) {
+            let changed = pa.0.iter().zip(pb.0.iter()).any(|(x, y)| x.abs_diff(*y) > options.threshold);
+            *po = if changed {
+                mismatch += 1;
+                highlight
+            } else {
+                let [r, g, bl] = flatten(pa);
+                let y = 0.299 * r + 0.587 * g + 0.114 * bl;
+                let v = (255.0 - (255.0 - y) * 0.1).round() as u8;
+                Rgba([v, v, v, 255])
+            };
+        }
+
+        let total = u64::from(w) * u64::from(h);
+        let psnr = psnr(&ra, &rb);
+        let ssim = ssim(&flat_luma(&ra), &flat_luma(&rb));
+        let encoded = encode(&DynamicImage::ImageRgba8(out), format, None)?;
+
+        Ok(attachment(
+            encoded,
+            format.mime(),
+            &format!("diff.{}", format.extension()),
+            vec![
+                ("X-Width", w.to_string()),
+                ("X-Height", h.to_string()),
+                ("X-Size-A", format!("{}x{}", size_a.0, size_a.1)),
+                ("X-Size-B", format!("{}x{}", size_b.0, size_b.1)),
+                ("X-Mismatch", mismatch.to_string()),
+                ("X-Mismatch-Ratio", format!("{:.6}", mismatch as f64 / total.max(1) as f64)),
+                ("X-Psnr", if psnr.is_finite() { format!("{psnr:.2}") } else { "inf".into() }),
+                ("X-Ssim", format!("{ssim:.6}")),
+                ("X-Phash-Distance", phash_distance.to_string()),
+            ],
+        ))
+    })
+    .await
+}
+
+/// 将两张图对齐到同一尺寸，返回 RGBA 缓冲。
+fn align(a: &DynamicImage, b: &DynamicImage, mode: DiffAlign) -> (RgbaImage, RgbaImage) {
+    if a.dimensions() == b.dimensions() {
+        return (a.to_rgba8(), b.to_rgba8());
+    }
+    match mode {
+        DiffAlign::Resize => (
+            a.to_rgba8(),
+            b.resize_exact(a.width(), a.height(), FilterType::Lanczos3).to_rgba8(),
+        ),
+        DiffAlign::Pad => {
+            let (w, h) = (a.width().max(b.width()), a.height().max(b.height()));
+            let pad = |img: &DynamicImage| {
+                let mut canvas = RgbaImage::new(w, h);
+                image::imageops::replace(&mut canvas, &img.to_rgba8(), 0, 0);
+                canvas
+            };
+            (pad(a), pad(b))
+        }
+    }
+}
+
+/// 合成到白底后的 RGB 值（透明区域按白色参与 PSNR / SSIM）。
+fn flatten(p: &Rgba<u8>) -> [f64; 3] {
+    let alpha = f64::from(p[3]) / 255.0;
+    [0, 1, 2].map(|c| f64::from(p[c]) * alpha + 255.0 * (1.0 - alpha))
+}
+
+fn flat_luma(img: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b] = flatten(img.get_pixel(x, y));
+        image::Luma([(0.299 * r + 0.587 * g + 0.114 * b).round() as u8])
+    })
+}
+
+/// RGB 三通道的峰值信噪比（dB）；完全相同时为正无穷。
+fn psnr(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    let mut sum = 0f64;
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        let (fa, fb) = (flatten(pa), flatten(pb));
+        sum += (0..3).map(|c| (fa[c] - fb[c]).powi(2)).sum::<f64>();
+    }
+    let mse = sum / (a.width() as f64 * a.height() as f64 * 3.0).max(1.0);
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0f64.powi(2) / mse).log10()
+    }
+}
+
+/// 亮度通道的平均 SSIM：8×8 窗口、步长 4；图片小于窗口时整图作为一个窗口。
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (w, h) = a.dimensions();
+    let (win_w, win_h) = (SSIM_WINDOW.min(w), SSIM_WINDOW.min(h));
+
+    let mut total = 0f64;
+    let mut windows = 0u32;
+    let mut y = 0;
+    while y + win_h <= h {
+        let mut x = 0;
+        while x + win_w <= w {
+            let n = f64::from(win_w * win_h);
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0f64, 0f64, 0f64, 0f64, 0f64);
+            for dy in 0..win_h {
+                for dx in 0..win_w {
+                    let va = f64::from(a.get_pixel(x + dx, y + dy)[0]);
+                    let vb = f64::from(b.get_pixel(x + dx, y + dy)[0]);
+                    sa += va;
+                    sb += vb;
+                    saa += va * va;
+                    sbb += vb * vb;
+                    sab += va * vb;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb) = (saa / n - ma * ma, sbb / n - mb * mb);
+            let cov = sab / n - ma * mb;
+            total += ((2.0 * ma * mb + C1) * (2.0 * cov + C2))
+                / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+            windows += 1;
+            x += SSIM_STEP;
+        }
+        y += SSIM_STEP;
+    }
+    if windows == 0 {
+        1.0
+    } else {
+        total / f64::from(windows)
+    }
+}
@@ -0,0 +1,248 @@
+use axum::extract::{Multipart, State};
+use axum::Json;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+
+use super::{load_image, read_upload, ImageConfig};
+use crate::handlers::error::AppError;
+
+/// pHash 先缩放到的边长，DCT 后取左上 8×8 低频系数。
+const PHASH_SIZE: usize = 32;
+
+/// 单批最多上传的图片数。
+const MAX_BATCH: usize = 5000;
+
+/// 分组时使用的哈希算法。
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Ahash,
+    Dhash,
+    #[default]
+    Phash,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct HashOptions {
+    pub algorithm: HashAlgorithm,
+    /// 汉明距离不超过该值的图片视为近似重复（0–64）
+    pub threshold: u32,
+}
+
+impl Default for HashOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::Phash,
+            threshold: 10,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct HashResponse {
+    pub images: Vec<ImageHash>,
+    /// 近似重复分组（每组至少两张，值为 `images` 的下标）
+    pub groups: Vec<Vec<usize>>,
+}
+
+/// 单张图片的哈希（16 位十六进制）；解码失败时只返回 `error`，不影响整批。
+#[derive(Serialize)]
+pub struct ImageHash {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dhash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub async fn hash(
+    State(cfg): State<ImageConfig>,
+    mut mp: Multipart,
+) -> Result<Json<HashResponse>, AppError> {
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut options = HashOptions::default();
+
+    while let Some(field) = mp
+        .next_field()
+        .await
+        .map_err(AppError::multipart)?
+    {
+        match field.name().unwrap_or("") {
+            "file" => {
+                if files.len() >= MAX_BATCH {
+                    return Err(AppError::bad_request(format!("图片过多，单批最多 {MAX_BATCH} 张")));
+                }
+                let name = field
+                    .file_name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("image_{}", files.len() + 1));
+                files.push((name, read_upload(field, &cfg).await?));
+            }
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| AppError::bad_request(format!("options 解析失败: {e}")))?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if files.is_empty() {
+        return Err(AppError::bad_request("缺少 file 字段"));
+    }
+
+    // 每张图单独提交一个任务：单个任务受超时限制，大批量也不会整批超时或长期独占任务池
+    let mut images = Vec::with_capacity(files.len());
+    // 参与分组的 (下标, 选定算法的哈希)
+    let mut keys: Vec<(usize, u64)> = Vec::new();
+    for (i, (name, bytes)) in files.into_iter().enumerate() {
+        let algorithm = options.algorithm;
+        let (image, key) = cfg.run(move |cfg| Ok(hash_one(name, &bytes, algorithm, cfg))).await?;
+        keys.extend(key.map(|key| (i, key)));
+        images.push(image);
+    }
+
+    let groups = group(&keys, options.threshold.min(64), images.len());
+    Ok(Json(HashResponse { images, groups }))
+}
+
+/// 计算单张图片的三种哈希，并返回选定算法的哈希用于分组；解码失败时只记录错误。
+fn hash_one(name: String, bytes: &[u8], algorithm: HashAlgorithm, cfg: &ImageConfig) -> (ImageHash, Option<u64>) {
+    let img = match load_image(bytes, &Default::default(), cfg) {
+        Ok(img) => img,
+        Err(e) => {
+            let image = ImageHash {
+                name,
+                width: None,
+                height: None,
+                ahash: None,
+                dhash: None,
+                phash: None,
+                error: Some(e.message),
+            };
+            return (image, None);
+        }
+    };
+    let (a, d, p) = (ahash(&img), dhash(&img), phash(&img));
+    let key = match algorithm {
+        HashAlgorithm::Ahash => a,
+        HashAlgorithm::Dhash => d,
+        HashAlgorithm::Phash => p,
+    };
+    let (w, h) = img.dimensions();
+    let image = ImageHash {
+        name,
+        width: Some(w),
+        height: Some(h),
+        ahash: Some(format!("{a:016x}")),
+        dhash: Some(format!("{d:016x}")),
+        phash: Some(format!("{p:016x}")),
+        error: None,
+    };
+    (image, Some(key))
+}
+
+/// 两两比较汉明距离，用并查集合并距离不超过阈值的图片（传递闭包）。
+fn group(keys: &[(usize, u64)], threshold: u32, len: usize) -> Vec<Vec<usize>> {
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut parent: Vec<usize> = (0..len).collect();
+    for (n, &(i, a)) in keys.iter().enumerate() {
+        for &(j, b) in &keys[n + 1..] {
+            if hamming(a, b) <= threshold {
+                let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+                if ri != rj {
+                    parent[rj.max(ri)] = ri.min(rj);
+                }
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); len];
+    for &(i, _) in keys {
+        let root = find(&mut parent, i);
+        groups[root].push(i);
+    }
+    groups.retain(|g| g.len() > 1);
+    groups
+}
+
+/// 感知哈希（pHash）：灰度 32×32 → 二维 DCT → 低频 8×8 系数与中位数比较得到 64 位。
+pub(super) fn phash(img: &DynamicImage) -> u64 {
+    let n = PHASH_SIZE;
+    let gray = img.resize_exact(n as u32, n as u32, FilterType::Triangle).to_luma8();
+    let pixels: Vec<f64> = gray.pixels().map(|p| f64::from(p[0])).collect();
+
+    // DCT-II 系数表：cos[(2x+1)uπ / 2n]
+    let table: Vec<f64> = (0..8)
+        .flat_map(|u| {
+            (0..n).map(move |x| {
+                ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * n) as f64).cos()
+            })
+        })
+        .collect();
+
+    // 先按行再按列变换，只计算需要的 8×8 低频部分
+    let mut rows = vec![0f64; n * 8];
+    for y in 0..n {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..n).map(|x| pixels[y * n + x] * table[u * n + x]).sum();
+        }
+    }
+    let mut coeffs = [0f64; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coeffs[v * 8 + u] = (0..n).map(|y| rows[y * 8 + u] * table[v * n + y]).sum();
+        }
+    }
+
+    // 直流分量只反映整体亮度，不参与中位数
+    let mut sorted: Vec<f64> = coeffs[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    bits(coeffs.iter().map(|&c| c > median))
+}
+
+/// 均值哈希（aHash）：灰度 8×8，像素亮度高于均值记 1。
+fn ahash(img: &DynamicImage) -> u64 {
+    let gray = img.resize_exact(8, 8, FilterType::Triangle).to_luma8();
+    let mean = gray.pixels().map(|p| u32::from(p[0])).sum::<u32>() / 64;
+    bits(gray.pixels().map(|p| u32::from(p[0]) > mean))
+}
+
+/// 差异哈希（dHash）：灰度 9×8，每行相邻像素左侧更亮记 1。
+fn dhash(img: &DynamicImage) -> u64 {
+    let gray = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    bits((0..8).flat_map(|y| {
+        let gray = &gray;
+        (0..8).map(move |x| gray.get_pixel(x, y)[0] > gray.get_pixel(x + 1, y)[0])
+    }))
+}
+
+/// 两个哈希之间的汉明距离。
+pub(super) fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 按顺序将布尔序列打包为 64 位整数（首位为最高位）。
+fn bits(iter: impl Iterator<Item = bool>) -> u64 {
+    iter.fold(0u64, |acc, b| (acc << 1) | u64::from(b))
+}
@@ -0,0 +1,288 @@
+use axum::extract::{Multipart, State};
+use axum::Json;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use super::{read_upload, ImageConfig};
+use crate::handlers::error::AppError;
+
+/// 主色统计时每个通道保留的高位数（5 位 → 32³ 个桶）。
+const COLOR_BUCKET_BITS: u32 = 5;
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct InfoOptions {
+    /// 返回的主色数量（1–32）
+    pub top_colors: usize,
+}
+
+impl Default for InfoOptions {
+    fn default() -> Self {
+        Self { top_colors: 8 }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ImageInfo {
+    pub format: String,
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub color_type: String,
+    pub bit_depth: u16,
+    pub channels: u8,
+    pub has_alpha: bool,
+    pub frame_count: usize,
+    pub animated: bool,
+    pub file_size: usize,
+    pub histogram: Vec<ChannelHistogram>,
+    pub dominant_colors: Vec<DominantColor>,
+}
+
+#[derive(Serialize)]
+pub struct ChannelHistogram {
+    pub channel: &'static str,
+    pub bins: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct DominantColor {
+    pub hex: String,
+    pub count: u64,
+    pub ratio: f64,
+}
+
+pub async fn info(
+    State(cfg): State<ImageConfig>,
+    mut mp: Multipart,
+) -> Result<Json<ImageInfo>, AppError> {
+    let mut bytes: Option<Vec<u8>> = None;
+    let mut options = InfoOptions::default();
+
+    while let Some(field) = mp
+        .next_field()
+        .await
+        .map_err(AppError::multipart)?
+    {
+        match field.name().unwrap_or("") {
+            "file" => bytes = Some(read_upload(field, &cfg).await?),
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| AppError::bad_request(format!("options 解析失败: {e}")))?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(bytes) = bytes else {
+        return Err(AppError::bad_request("缺少 file 字段"));
+    };
+    let top_colors = options.top_colors.clamp(1, 32);
+    let info = cfg
+        .run(move |cfg| inspect(&bytes, top_colors, cfg))
+        .await?;
+    Ok(Json(info))
+}
+
+fn inspect(bytes: &[u8], top_colors: usize, cfg: &ImageConfig) -> Result<ImageInfo, AppError> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| anyhow::anyhow!("读取图片失败: {e}"))?;
+    let Some(format) = reader.format() else {
+        return Err(AppError::bad_request("无法识别的图片格式"));
+    };
+    // 超出内存上限与像素预算时和其他图片接口一样返回 413
+    let decode_err = |e: image::ImageError| cfg.decode_error("解析图片失败", e);
+    let mut limits = cfg.decode_limits();
+    reader.limits(limits.clone());
+    let mut decoder = reader.into_decoder().map_err(decode_err)?;
+    let (w, h) = decoder.dimensions();
+    cfg.check_pixels(w, h, 1)?;
+    limits.reserve(decoder.total_bytes()).map_err(decode_err)?;
+    decoder.set_limits(limits).map_err(decode_err)?;
+    let color = decoder.original_color_type();
+    let img = DynamicImage::from_decoder(decoder).map_err(decode_err)?;
+
+    let channels = color.channel_count();
+    let frame_count = count_frames(bytes, format);
+
+    Ok(ImageInfo {
+        format: format!("{format:?}"),
+        mime: format.to_mime_type().to_string(),
+        width: img.width(),
+        height: img.height(),
+        color_type: format!("{color:?}"),
+        bit_depth: color.bits_per_pixel() / u16::from(channels.max(1)),
+        channels,
+        has_alpha: img.color().has_alpha(),
+        frame_count,
+        animated: frame_count > 1,
+        file_size: bytes.len(),
+        histogram: histogram(&img),
+        dominant_colors: dominant_colors(&img, top_colors),
+    })
+}
+
+/// 统计动画帧数；仅 GIF / APNG / WebP 可能多帧，其余格式固定为 1。
+/// 只读取容器结构、不解码像素，避免小文件里的大量帧占满 CPU。
+fn count_frames(bytes: &[u8], format: ImageFormat) -> usize {
+    let frames = match format {
+        ImageFormat::Gif => gif_frames(bytes),
+        ImageFormat::Png => apng_frames(bytes),
+        ImageFormat::WebP => webp_frames(bytes),
+        _ => 1,
+    };
+    frames.max(1)
+}
+
+/// 逐块跳过 GIF 数据，统计图像描述符（0x2C）个数；文件截断时按已读到的帧计。
+fn gif_frames(b: &[u8]) -> usize {
+    // 6 字节签名 + 7 字节逻辑屏幕描述符，其后可能跟全局颜色表
+    let Some(&flags) = b.get(10) else {
+        return 1;
+    };
+    let mut pos = 13 + gif_color_table_len(flags);
+    let mut frames = 0;
+    loop {
+        let next = match b.get(pos) {
+            // 扩展块：引导符 + 标签 + 子块
+            Some(0x21) => gif_skip_sub_blocks(b, pos + 2),
+            // 图像描述符 10 字节 + 局部颜色表 + LZW 最小码长 + 子块
+            Some(0x2C) => {
+                frames += 1;
+                b.get(pos + 9)
+                    .and_then(|&flags| gif_skip_sub_blocks(b, pos + 11 + gif_color_table_len(flags)))
+            }
+            _ => None,
+        };
+        match next {
+            Some(next) => pos = next,
+            None => return frames,
+        }
+    }
+}
+
+fn gif_color_table_len(flags: u8) -> usize {
+    if flags & 0x80 != 0 {
+        3 << ((flags & 0x07) + 1)
+    } else {
+        0
+    }
+}
+
+/// 跳过以 0 长度结尾的子块序列，返回其后的位置。
+fn gif_skip_sub_blocks(b: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *b.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            return Some(pos);
+        }
+        pos += len;
+    }
+}
+
+/// 读取 APNG 的 acTL 块中声明的帧数；acTL 必须出现在首个 IDAT 之前。
+fn apng_frames(b: &[u8]) -> usize {
+    let mut pos = 8;
+    while let Some(header) = b.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        match &header[4..8] {
+            b"acTL" => {
+                return b
+                    .get(pos + 8..pos + 12)
+                    .map_or(1, |n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]) as usize);
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+        }
+        // 长度 + 类型 + 数据 + CRC
+        pos = pos.saturating_add(12).saturating_add(len);
+    }
+    1
+}
+
+/// 统计 WebP（RIFF）中的 ANMF 块数，静态 WebP 没有 ANMF。
+fn webp_frames(b: &[u8]) -> usize {
+    let mut pos = 12;
+    let mut frames = 0;
+    while let Some(header) = b.get(pos..pos + 8) {
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if &header[..4] == b"ANMF" {
+            frames += 1;
+        }
+        // 块数据按偶数字节对齐
+        pos = pos.saturating_add(8).saturating_add(len + (len & 1));
+    }
+    frames
+}
+
+/// 逐通道 256 档直方图：灰度图统计 L(+A)，彩色图统计 R/G/B(+A)；高位深先降到 8 位。
+fn histogram(img: &DynamicImage) -> Vec<ChannelHistogram> {
+    let has_alpha = img.color().has_alpha();
+    let names: &[&'static str] = match (img.color().has_color(), has_alpha) {
+        (true, true) => &["r", "g", "b", "a"],
+        (true, false) => &["r", "g", "b"],
+        (false, true) => &["l", "a"],
+        (false, false) => &["l"],
+    };
+    let mut bins = vec![vec![0u64; 256]; names.len()];
+
+    if img.color().has_color() {
+        for p in img.to_rgba8().pixels() {
+            for (c, bin) in bins.iter_mut().enumerate() {
+                bin[p[c] as usize] += 1;
+            }
+        }
+    } else {
+        for p in img.to_luma_alpha8().pixels() {
+            for (c, bin) in bins.iter_mut().enumerate() {
+                bin[p[c] as usize] += 1;
+            }
+        }
+    }
+
+    names
+        .iter()
+        .zip(bins)
+        .map(|(&channel, bins)| ChannelHistogram { channel, bins })
+        .collect()
+}
+
+/// 按 RGB 高位分桶统计主色，桶内取平均色；完全透明的像素不计入。
+fn dominant_colors(img: &DynamicImage, top: usize) -> Vec<DominantColor> {
+    let shift = 8 - COLOR_BUCKET_BITS;
+    let side = 1usize << COLOR_BUCKET_BITS;
+    // 每个桶：[计数, R 累加, G 累加, B 累加]
+    let mut buckets = vec![[0u64; 4]; side * side * side];
+    let mut total = 0u64;
+
+    for p in img.to_rgba8().pixels() {
+        if p[3] == 0 {
+            continue;
+        }
+        let [r, g, b] = [p[0] as usize, p[1] as usize, p[2] as usize];
+        let idx = ((r >> shift) * side + (g >> shift)) * side + (b >> shift);
+        let bucket = &mut buckets[idx];
+        bucket[0] += 1;
+        bucket[1] += r as u64;
+        bucket[2] += g as u64;
+        bucket[3] += b as u64;
+        total += 1;
+    }
+
+    let mut used: Vec<&[u64; 4]> = buckets.iter().filter(|b| b[0] > 0).collect();
+    used.sort_unstable_by(|a, b| b[0].cmp(&a[0]));
+    used.into_iter()
+        .take(top)
+        .map(|&[count, r, g, b]| DominantColor {
+            hex: format!("#{:02x}{:02x}{:02x}", r / count, g / count, b / count),
+            count,
+            ratio: count as f64 / total as f64,
+        })
+        .collect()
+}
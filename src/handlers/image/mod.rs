@@ -0,0 +1,919 @@
+use axum::extract::multipart::Field;
+use axum::extract::{Multipart, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use image::codecs::{
+    bmp::BmpEncoder, ico::IcoEncoder, jpeg::JpegEncoder, webp::WebPEncoder,
+};
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageEncoder, ImageFormat, ImageReader, Rgba};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use crate::handlers::error::AppError;
+
+mod anim;
+mod color;
+mod data_uri;
+mod diff;
+mod hash;
+mod inspect;
+mod png_opt;
+mod pool;
+mod resize;
+mod sprite;
+mod svg;
+mod watermark;
+
+pub use anim::animate;
+pub use data_uri::{from_data_uri, to_data_uri};
+pub use diff::diff;
+pub use hash::hash;
+pub use inspect::info;
+pub use pool::WorkerPool;
+pub use sprite::sprite;
+
+use anim::AnimationMode;
+use color::{CmykJpeg, ColorInfo, IccMode};
+use png_opt::PngOptions;
+use resize::{do_resize, ResizeOptions};
+pub(crate) use svg::{render_text, SvgOptions};
+use watermark::WatermarkOptions;
+
+/// `max_bytes` 模式下的最大编码尝试次数（含缩小尺寸后的重试）。
+const MAX_FIT_ATTEMPTS: u32 = 48;
+/// `max_bytes` 模式下缩小尺寸的下限（最短边像素）。
+const MIN_FIT_SIDE: u32 = 16;
+
+/// 图片处理的运行时上限，由命令行参数配置（见 `cli::Cli`）。
+#[derive(Debug, Clone)]
+pub struct ImageConfig {
+    /// 单个上传文件的大小上限（字节）
+    pub max_upload_bytes: usize,
+    /// 解码后的像素预算（动图按全部帧累计）
+    pub max_pixels: u64,
+    /// CPU 密集处理（解码 / 缩放 / 编码）所用的阻塞任务池
+    pub pool: Arc<WorkerPool>,
+}
+
+impl ImageConfig {
+    /// 在任务池中执行处理逻辑，避免阻塞异步运行时。
+    pub(crate) async fn run<T, F>(&self, job: F) -> Result<T, AppError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ImageConfig) -> Result<T, AppError> + Send + 'static,
+    {
+        let cfg = self.clone();
+        self.pool.run(move || job(&cfg)).await
+    }
+
+    /// 解码器的内存上限：按像素预算、每像素最多 16 字节（RGBA 32F）估算。
+    pub(crate) fn decode_limits(&self) -> image::Limits {
+        let mut limits = image::Limits::default();
+        limits.max_alloc = Some(self.max_pixels.saturating_mul(16));
+        limits
+    }
+
+    /// 解码错误：超出内存上限时返回 413，其余按 `context` 前缀返回 400。
+    pub(crate) fn decode_error(&self, context: &str, e: image::ImageError) -> AppError {
+        match e {
+            image::ImageError::Limits(_) => AppError::too_large(
+                "pixel_limit_exceeded",
+                format!("图片解码超出内存上限: {e}"),
+                self.max_pixels,
+            ),
+            e => AppError::bad_request(format!("{context}: {e}")),
+        }
+    }
+
+    /// 校验 `w × h × frames` 是否超出像素预算。
+    pub(crate) fn check_pixels(&self, w: u32, h: u32, frames: u64) -> Result<(), AppError> {
+        let pixels = (u64::from(w) * u64::from(h)).saturating_mul(frames);
+        if pixels > self.max_pixels {
+            return Err(AppError::too_large(
+                "pixel_limit_exceeded",
+                format!(
+                    "图片解码后像素过多（{w}x{h}{}），上限 {} 百万像素",
+                    if frames > 1 { format!(" × {frames} 帧") } else { String::new() },
+                    self.max_pixels / 1_000_000
+                ),
+                self.max_pixels,
+            ));
+        }
+        Ok(())
+    }
+
+    /// 校验多张图片累计的像素数是否超出预算。
+    pub(crate) fn check_pixel_total(&self, pixels: u64) -> Result<(), AppError> {
+        if pixels > self.max_pixels {
+            return Err(AppError::too_large(
+                "pixel_limit_exceeded",
+                format!(
+                    "图片累计像素过多（{pixels}），上限 {} 百万像素",
+                    self.max_pixels / 1_000_000
+                ),
+                self.max_pixels,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum OutputFormat {
+    PNG,
+    JPEG,
+    GIF,
+    WEBP,
+    BMP,
+    ICO,
+    SVG,
+}
+
+impl OutputFormat {
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::PNG => "png",
+            OutputFormat::JPEG => "jpg",
+            OutputFormat::GIF => "gif",
+            OutputFormat::WEBP => "webp",
+            OutputFormat::BMP => "bmp",
+            OutputFormat::ICO => "ico",
+            OutputFormat::SVG => "svg",
+        }
+    }
+
+    pub(crate) fn mime(&self) -> &'static str {
+        match self {
+            OutputFormat::PNG => "image/png",
+            OutputFormat::JPEG => "image/jpeg",
+            OutputFormat::GIF => "image/gif",
+            OutputFormat::WEBP => "image/webp",
+            OutputFormat::BMP => "image/bmp",
+            OutputFormat::ICO => "image/x-icon",
+            OutputFormat::SVG => "image/svg+xml",
+        }
+    }
+
+    /// 可按质量有损编码（JPEG，以及选择有损模式的 WebP）。
+    fn is_lossy(&self) -> bool {
+        matches!(self, OutputFormat::JPEG | OutputFormat::WEBP)
+    }
+
+    /// 是否可输出动画（GIF、APNG、动画 WebP）。
+    fn supports_animation(&self) -> bool {
+        matches!(self, OutputFormat::GIF | OutputFormat::PNG | OutputFormat::WEBP)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConvertOptions {
+    pub quality: Option<u8>,
+    /// WebP 输出是否无损（默认无损，设为 false 时按 quality 有损编码；`max_bytes` 模式下始终有损）
+    #[serde(default = "default_lossless")]
+    pub lossless: bool,
+    pub resize: Option<ResizeOptions>,
+    /// 输出文件大小上限（字节）：JPEG / WebP（含 SVG 内嵌）二分搜索质量，其余格式按原参数编码；
+    /// 仍超限时报错，`allow_downscale` 为 true 时改为按比例缩小尺寸重试
+    pub max_bytes: Option<usize>,
+    /// `max_bytes` 模式下最低质量仍超限时，允许按比例缩小尺寸继续尝试
+    #[serde(default)]
+    pub allow_downscale: bool,
+    /// 动图输入的处理方式：保留全部帧 / 只取首帧 / 导出帧 ZIP
+    #[serde(default)]
+    pub animation: AnimationMode,
+    /// PNG 输出的压缩 / 滤波 / 调色板量化参数
+    pub png: Option<PngOptions>,
+    /// SVG 输入的光栅化尺寸 / DPI，以及 SVG 输出时内嵌的栅格格式
+    pub svg: Option<SvgOptions>,
+    /// 文字或图片水印（图片通过 `watermark` 字段上传）
+    pub watermark: Option<WatermarkOptions>,
+    /// 嵌入 ICC 配置文件的处理：ignore（默认）/ srgb（转换到 sRGB）
+    #[serde(default)]
+    pub icc: IccMode,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            quality: None,
+            lossless: true,
+            resize: None,
+            max_bytes: None,
+            allow_downscale: false,
+            animation: AnimationMode::default(),
+            png: None,
+            svg: None,
+            watermark: None,
+            icc: IccMode::default(),
+        }
+    }
+}
+
+fn default_lossless() -> bool {
+    true
+}
+
+/// 编码参数：`quality` 用于 JPEG 与有损 WebP，`webp_lossy` 为 false 时 WebP 始终无损，
+/// `png` 为 PNG 输出的压缩参数（缺省为默认参数）。
+#[derive(Default, Clone, Copy)]
+pub(crate) struct EncodeParams<'a> {
+    pub quality: Option<u8>,
+    pub webp_lossy: bool,
+    pub png: Option<&'a PngOptions>,
+}
+
+impl EncodeParams<'_> {
+    /// 实际生效的质量（仅有损编码），用于响应头。
+    fn effective_quality(&self, format: OutputFormat) -> Option<u8> {
+        match format {
+            OutputFormat::JPEG => self.quality,
+            OutputFormat::WEBP if self.webp_lossy => self.quality,
+            _ => None,
+        }
+    }
+}
+
+/// 编码结果：`quality` 为实际采用的质量（仅有损格式），`attempts` 为编码次数，
+/// `png_color` 为 PNG 输出实际采用的色彩类型 / 位深。
+struct Encoded {
+    bytes: Vec<u8>,
+    quality: Option<u8>,
+    attempts: u32,
+    png_color: Option<String>,
+}
+
+/// `convert` 类端点的上传内容：图片、目标格式、参数及可选的水印图片。
+struct ConvertUpload {
+    bytes: Vec<u8>,
+    original_name: String,
+    format: Option<OutputFormat>,
+    options: ConvertOptions,
+    logo: Option<Vec<u8>>,
+}
+
+/// 转换结果：输出内容、MIME、扩展名及附加响应头。
+struct Converted {
+    bytes: Vec<u8>,
+    mime: &'static str,
+    extension: &'static str,
+    headers: Vec<(&'static str, String)>,
+}
+
+pub async fn convert(
+    State(cfg): State<ImageConfig>,
+    mut mp: Multipart,
+) -> Result<Response, AppError> {
+    let ConvertUpload {
+        bytes,
+        original_name,
+        format,
+        options,
+        logo,
+    } = read_convert_upload(&mut mp, &cfg).await?;
+    let Some(format) = format else {
+        return Ok(bad_request("缺少或无效的 format 字段"));
+    };
+    let out = cfg
+        .run(move |cfg| run_convert(&bytes, format, options, logo.as_deref(), cfg))
+        .await?;
+    let out_name = output_name(&original_name, out.extension);
+    Ok(attachment(out.bytes, out.mime, &out_name, out.headers))
+}
+
+/// 读取 `file` / `watermark` / `format` / `options` 字段。
+async fn read_convert_upload(
+    mp: &mut Multipart,
+    cfg: &ImageConfig,
+) -> Result<ConvertUpload, AppError> {
+    let mut bytes: Option<Vec<u8>> = None;
+    let mut format: Option<OutputFormat> = None;
+    let mut options = ConvertOptions::default();
+    let mut original_name: String = "image".into();
+    let mut logo: Option<Vec<u8>> = None;
+
+    while let Some(field) = mp
+        .next_field()
+        .await
+        .map_err(AppError::multipart)?
+    {
+        let name = field.name().unwrap_or("").to_string();
+        match name.as_str() {
+            "file" => {
+                if let Some(fname) = field.file_name() {
+                    original_name = fname.to_string();
+                }
+                bytes = Some(read_upload(field, cfg).await?);
+            }
+            "watermark" => logo = Some(read_upload(field, cfg).await?),
+            "format" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("读取 format 字段失败: {e}"))?;
+                format = parse_format(&text);
+            }
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| anyhow::anyhow!("options 解析失败: {e}"))?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(bytes) = bytes else {
+        return Err(AppError::bad_request("缺少 file 字段"));
+    };
+    Ok(ConvertUpload {
+        bytes,
+        original_name,
+        format,
+        options,
+        logo,
+    })
+}
+
+/// 转换管线：动图按帧处理（保留 / 导出 ZIP），静态图解码 → 缩放 → 水印 → 编码。
+fn run_convert(
+    bytes: &[u8],
+    format: OutputFormat,
+    mut options: ConvertOptions,
+    logo: Option<&[u8]>,
+    cfg: &ImageConfig,
+) -> Result<Converted, AppError> {
+    // 只上传了水印图片时使用默认水印参数
+    let wm_opts = options
+        .watermark
+        .take()
+        .or_else(|| logo.is_some().then(WatermarkOptions::default));
+
+    // 输出格式不支持动画且不导出帧时直接按静态图处理，避免白白解码全部帧
+    let keep_frames = match options.animation {
+        AnimationMode::First => false,
+        AnimationMode::Keep => format.supports_animation(),
+        AnimationMode::Extract => true,
+    };
+    if keep_frames {
+        if let Some(frames) = anim::decode_frames(bytes, cfg)? {
+            let mut frames = anim::resize_frames(frames, options.resize.as_ref(), cfg)?;
+            let (w, h) = frames[0].buffer().dimensions();
+            if let Some(wm) = wm_opts.as_ref() {
+                let mark = watermark::prepare(wm, logo, w, cfg)?;
+                for frame in frames.iter_mut() {
+                    watermark::apply(frame.buffer_mut(), &mark, wm);
+                }
+            }
+            let headers = vec![
+                ("X-Width", w.to_string()),
+                ("X-Height", h.to_string()),
+                ("X-Frames", frames.len().to_string()),
+            ];
+            if options.animation == AnimationMode::Extract {
+                return Ok(Converted {
+                    bytes: anim::frames_zip(&frames)?,
+                    mime: "application/zip",
+                    extension: "frames.zip",
+                    headers,
+                });
+            }
+            if options.max_bytes.is_some() {
+                return Err(AppError::bad_request("动图输出暂不支持 max_bytes"));
+            }
+            return Ok(Converted {
+                bytes: anim::encode_animation(&frames, format, options.quality, options.lossless, 0)?,
+                mime: format.mime(),
+                extension: format.extension(),
+                headers,
+            });
+        }
+    }
+
+    let mut svg_opts = options.svg.clone().unwrap_or_default();
+    // ICO 未指定尺寸时 SVG 直接按 256×256 渲染，避免先小图光栅化再放大
+    if matches!(format, OutputFormat::ICO) && svg_opts.width.is_none() && svg_opts.height.is_none() {
+        svg_opts.width = Some(256);
+        svg_opts.height = Some(256);
+    }
+    let decoded = decode_image(bytes, &svg_opts, options.icc, cfg)?;
+    let img = decoded.image;
+
+    let mut img = if let Some(resize) = options.resize.as_ref() {
+        do_resize(&img, resize, cfg)?
+    } else if matches!(format, OutputFormat::ICO) {
+        img.resize_exact(256, 256, image::imageops::FilterType::Lanczos3)
+    } else {
+        img
+    };
+    if let Some(wm) = wm_opts.as_ref() {
+        let mark = watermark::prepare(wm, logo, img.width(), cfg)?;
+        // 16 位 / 浮点底图按 RGBA16 叠加，避免降为 8 位丢失精度；原图不带透明度时仍输出 RGB16
+        img = if color::is_high_depth(&img) {
+            let mut rgba = img.to_rgba16();
+            watermark::apply(&mut rgba, &DynamicImage::ImageRgba8(mark).to_rgba16(), wm);
+            let out = DynamicImage::ImageRgba16(rgba);
+            if img.color().has_alpha() {
+                out
+            } else {
+                DynamicImage::ImageRgb16(out.to_rgb16())
+            }
+        } else {
+            let mut rgba = img.to_rgba8();
+            watermark::apply(&mut rgba, &mark, wm);
+            DynamicImage::ImageRgba8(rgba)
+        };
+    }
+
+    let embed = svg::embed_format(options.svg.as_ref())?;
+    let params = EncodeParams {
+        quality: options.quality,
+        webp_lossy: !options.lossless,
+        png: options.png.as_ref(),
+    };
+    let encoded = match options.max_bytes {
+        Some(max_bytes) => encode_within(&mut img, format, embed, params, max_bytes, options.allow_downscale)?,
+        None => encode_output(&img, format, embed, params)?,
+    };
+
+    let (w, h) = img.dimensions();
+    let mut headers = vec![("X-Width", w.to_string()), ("X-Height", h.to_string())];
+    if let Some(q) = encoded.quality {
+        headers.push(("X-Quality", q.to_string()));
+    }
+    headers.push(("X-Attempts", encoded.attempts.to_string()));
+    let color_out = ColorInfo::output(&img, format, encoded.png_color.as_deref(), decoded.srgb);
+    headers.push(("X-Color-Space-In", decoded.color.to_string()));
+    headers.push(("X-Color-Space-Out", color_out.to_string()));
+    if let Some(warning) = decoded.warning {
+        headers.push(("X-Color-Warning", warning.to_string()));
+    }
+    if let Some(color) = encoded.png_color {
+        headers.push(("X-Png-Color", color));
+    }
+    headers.push(("X-Original-Size", bytes.len().to_string()));
+
+    Ok(Converted {
+        bytes: encoded.bytes,
+        mime: format.mime(),
+        extension: format.extension(),
+        headers,
+    })
+}
+
+/// 由上传文件名生成 ASCII 安全的输出文件名。
+fn output_name(original_name: &str, extension: &str) -> String {
+    let file_stem = std::path::Path::new(original_name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    format!("{}.{}", sanitize_stem(file_stem), extension)
+}
+
+/// 构造附件下载响应：Content-Type / Content-Disposition、X-File-Name / X-File-Size，
+/// 以及调用方追加的 `X-*` 头（统一加入 Access-Control-Expose-Headers）。
+pub(crate) fn attachment(
+    body: Vec<u8>,
+    mime: &'static str,
+    out_name: &str,
+    extra: Vec<(&'static str, String)>,
+) -> Response {
+    // out_name 已净化为 ASCII，header 构造不会失败；仍用安全回退避免任何 panic。
+    let safe = |s: String| HeaderValue::from_str(&s).unwrap_or_else(|_| HeaderValue::from_static("image"));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        safe(format!("attachment; filename=\"{out_name}\"")),
+    );
+    headers.insert("X-File-Name", safe(out_name.to_string()));
+    headers.insert("X-File-Size", safe(body.len().to_string()));
+    let mut exposed = vec!["X-File-Name", "X-File-Size"];
+    for (name, value) in extra {
+        headers.insert(name, safe(value));
+        exposed.push(name);
+    }
+    headers.insert("Access-Control-Expose-Headers", safe(exposed.join(", ")));
+
+    (StatusCode::OK, headers, body).into_response()
+}
+
+/// 解码结果：图片及其输入色彩信息。
+struct Decoded {
+    image: DynamicImage,
+    color: ColorInfo,
+    /// 像素已按 ICC 配置文件转换到 sRGB
+    srgb: bool,
+    /// 色彩换算的警告，写入 `X-Color-Warning` 响应头
+    warning: Option<&'static str>,
+}
+
+/// 解码上传的图片（忽略嵌入的 RGB 配置文件）。
+pub(crate) fn load_image(
+    bytes: &[u8],
+    svg_opts: &SvgOptions,
+    cfg: &ImageConfig,
+) -> Result<DynamicImage, AppError> {
+    decode_image(bytes, svg_opts, IccMode::Ignore, cfg).map(|d| d.image)
+}
+
+/// 只读文件头获取尺寸，不解码像素；SVG 或无法识别的格式返回 None。
+pub(crate) fn header_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if svg::is_svg(bytes) {
+        return None;
+    }
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// 解码上传的图片；SVG 按 `svg_opts` 光栅化，其余交给 image 自动识别格式。
+/// 先读文件头校验像素预算，再带内存上限解码；CMYK JPEG 与 `icc` 为 Srgb 时按嵌入的配置文件转换。
+fn decode_image(
+    bytes: &[u8],
+    svg_opts: &SvgOptions,
+    icc: IccMode,
+    cfg: &ImageConfig,
+) -> Result<Decoded, AppError> {
+    if svg::is_svg(bytes) {
+        let image = svg::rasterize(bytes, svg_opts, cfg)?;
+        let (image, color, srgb) = color::apply_icc(image, None, icc)?;
+        return Ok(Decoded { image, color, srgb, warning: None });
+    }
+    let bad = |e: image::ImageError| AppError::bad_request(format!("解析图片失败: {e}"));
+    let decode_err = |e: image::ImageError| cfg.decode_error("解析图片失败", e);
+    let reader = || ImageReader::new(Cursor::new(bytes)).with_guessed_format();
+    let (w, h) = reader()?.into_dimensions().map_err(bad)?;
+    cfg.check_pixels(w, h, 1)?;
+
+    let mut reader = reader()?;
+    let format = reader.format();
+    let mut limits = cfg.decode_limits();
+    reader.limits(limits.clone());
+    let mut decoder = reader.into_decoder().map_err(decode_err)?;
+    limits.reserve(decoder.total_bytes()).map_err(decode_err)?;
+    decoder.set_limits(limits).map_err(decode_err)?;
+    let profile = decoder.icc_profile().ok().flatten();
+
+    let mut warning = None;
+    if format == Some(ImageFormat::Jpeg) {
+        match color::decode_cmyk_jpeg(bytes, profile.as_deref())? {
+            CmykJpeg::Converted(image, color) => return Ok(Decoded { image, color, srgb: true, warning }),
+            CmykJpeg::NoProfile => warning = Some("cmyk-without-profile"),
+            CmykJpeg::NotCmyk => {}
+        }
+    }
+    let image = DynamicImage::from_decoder(decoder).map_err(decode_err)?;
+    let (image, mut color, srgb) = color::apply_icc(image, profile.as_deref(), icc)?;
+    if warning.is_some() {
+        color.model = "CMYK";
+    }
+    Ok(Decoded { image, color, srgb, warning })
+}
+
+/// 逐块读取上传的文件字段，超过大小上限立即中止（413）。
+pub(crate) async fn read_upload(mut field: Field<'_>, cfg: &ImageConfig) -> Result<Vec<u8>, AppError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await.map_err(AppError::multipart)? {
+        if data.len() + chunk.len() > cfg.max_upload_bytes {
+            return Err(AppError::too_large(
+                "upload_too_large",
+                format!("文件过大，最大 {}MB", cfg.max_upload_bytes / (1024 * 1024)),
+                cfg.max_upload_bytes as u64,
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+pub(crate) fn parse_format(text: &str) -> Option<OutputFormat> {
+    match text.trim().to_ascii_uppercase().as_str() {
+        "PNG" => Some(OutputFormat::PNG),
+        "JPEG" | "JPG" => Some(OutputFormat::JPEG),
+        "GIF" => Some(OutputFormat::GIF),
+        "WEBP" => Some(OutputFormat::WEBP),
+        "BMP" => Some(OutputFormat::BMP),
+        "ICO" => Some(OutputFormat::ICO),
+        "SVG" => Some(OutputFormat::SVG),
+        _ => None,
+    }
+}
+
+/// 解析 `#rrggbb` 颜色（`#` 可省略）。
+pub(crate) fn parse_hex_color(text: &str) -> Option<Rgba<u8>> {
+    let hex = text.trim().strip_prefix('#').unwrap_or(text.trim());
+    if hex.len() != 6 {
+        return None;
+    }
+    let v = u32::from_str_radix(hex, 16).ok()?;
+    Some(Rgba([(v >> 16) as u8, (v >> 8) as u8, v as u8, 255]))
+}
+
+/// 以默认参数编码：WebP 无损，PNG 使用默认压缩参数。
+pub(crate) fn encode(img: &DynamicImage, format: OutputFormat, quality: Option<u8>) -> anyhow::Result<Vec<u8>> {
+    encode_with(img, format, EncodeParams { quality, ..Default::default() })
+}
+
+pub(crate) fn encode_with(img: &DynamicImage, format: OutputFormat, params: EncodeParams<'_>) -> anyhow::Result<Vec<u8>> {
+    let quality = params.quality;
+    let mut buf = Cursor::new(Vec::<u8>::new());
+    match format {
+        OutputFormat::JPEG => {
+            let q = quality.unwrap_or(90).clamp(1, 100);
+            let encoder = JpegEncoder::new_with_quality(&mut buf, q);
+            let rgb = img.to_rgb8();
+            encoder.write_image(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                image::ExtendedColorType::Rgb8,
+            )?;
+        }
+        OutputFormat::PNG => {
+            let defaults = PngOptions::default();
+            return Ok(png_opt::encode_png(img, params.png.unwrap_or(&defaults))?.bytes);
+        }
+        OutputFormat::GIF => {
+            img.write_to(&mut buf, ImageFormat::Gif)?;
+        }
+        OutputFormat::WEBP => {
+            let rgba = img.to_rgba8();
+            // image 自带的 WebP 编码器只支持无损；选择有损模式时改用 libwebp 按质量编码。
+            if params.webp_lossy {
+                let q = quality.unwrap_or(90).clamp(1, 100);
+                let mem = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height())
+                    .encode_simple(false, f32::from(q))
+                    .map_err(|e| anyhow::anyhow!("WebP 编码失败: {e:?}"))?;
+                return Ok(mem.to_vec());
+            }
+            let encoder = WebPEncoder::new_lossless(&mut buf);
+            encoder.write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ExtendedColorType::Rgba8,
+            )?;
+        }
+        OutputFormat::BMP => {
+            let encoder = BmpEncoder::new(&mut buf);
+            let rgba = img.to_rgba8();
+            encoder.write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ExtendedColorType::Rgba8,
+            )?;
+        }
+        OutputFormat::SVG => {
+            return svg::embed(img, OutputFormat::PNG, params);
+        }
+        OutputFormat::ICO => {
+            let encoder = IcoEncoder::new(&mut buf);
+            let rgba = img.to_rgba8();
+            encoder.write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ExtendedColorType::Rgba8,
+            )?;
+        }
+    }
+    Ok(buf.into_inner())
+}
+
+/// 按输出格式编码一次：PNG 记录实际采用的色彩类型，SVG 以 `embed` 格式内嵌栅格图。
+fn encode_output(
+    img: &DynamicImage,
+    format: OutputFormat,
+    embed: OutputFormat,
+    params: EncodeParams<'_>,
+) -> Result<Encoded, AppError> {
+    Ok(match format {
+        OutputFormat::PNG => {
+            let png = png_opt::encode_png(img, params.png.unwrap_or(&PngOptions::default()))?;
+            Encoded {
+                bytes: png.bytes,
+                quality: None,
+                attempts: 1,
+                png_color: Some(png.color),
+            }
+        }
+        OutputFormat::SVG => Encoded {
+            bytes: svg::embed(img, embed, params)?,
+            quality: params.effective_quality(embed),
+            attempts: 1,
+            png_color: None,
+        },
+        _ => Encoded {
+            bytes: encode_with(img, format, params)?,
+            quality: params.effective_quality(format),
+            attempts: 1,
+            png_color: None,
+        },
+    })
+}
+
+/// 在 `max_bytes` 内编码：有损格式先试起始质量，超限则在 [1, 起始质量) 内二分搜索
+/// 满足上限的最高质量；最低质量仍超限且允许缩小时，按体积比例缩小尺寸后重试。
+/// 缩小后的图片写回 `img`，以便响应头报告实际尺寸。SVG 输出按内嵌格式 `embed` 判断是否有损。
+fn encode_within(
+    img: &mut DynamicImage,
+    format: OutputFormat,
+    embed: OutputFormat,
+    params: EncodeParams<'_>,
+    max_bytes: usize,
+    allow_downscale: bool,
+) -> Result<Encoded, AppError> {
+    if max_bytes == 0 {
+        return Err(AppError::bad_request("max_bytes 必须大于 0"));
+    }
+    let ceiling = params.quality.unwrap_or(90).clamp(1, 100);
+    // 有损格式按质量搜索（WebP 此时改为有损编码），其余格式沿用调用方的参数
+    let at = |quality: u8| EncodeParams { quality: Some(quality), webp_lossy: true, ..params };
+    let lossy = match format {
+        OutputFormat::SVG => embed.is_lossy(),
+        _ => format.is_lossy(),
+    };
+    let mut attempts = 0u32;
+
+    loop {
+        let smallest = if lossy {
+            attempts += 1;
+            let top = encode_output(img, format, embed, at(ceiling))?;
+            if top.bytes.len() <= max_bytes {
+                return Ok(Encoded { attempts, ..top });
+            }
+
+            let (mut lo, mut hi) = (1i32, i32::from(ceiling) - 1);
+            let mut best: Option<Encoded> = None;
+            let mut smallest = top.bytes.len();
+            while lo <= hi && attempts < MAX_FIT_ATTEMPTS {
+                let mid = (lo + hi) / 2;
+                attempts += 1;
+                let out = encode_output(img, format, embed, at(mid as u8))?;
+                smallest = smallest.min(out.bytes.len());
+                if out.bytes.len() <= max_bytes {
+                    best = Some(out);
+                    lo = mid + 1;
+                } else {
+                    hi = mid - 1;
+                }
+            }
+            if let Some(best) = best {
+                return Ok(Encoded { attempts, ..best });
+            }
+            smallest
+        } else {
+            attempts += 1;
+            let out = encode_output(img, format, embed, params)?;
+            if out.bytes.len() <= max_bytes {
+                return Ok(Encoded { attempts, ..out });
+            }
+            out.bytes.len()
+        };
+
+        let (w, h) = img.dimensions();
+        if !allow_downscale || attempts >= MAX_FIT_ATTEMPTS || w.min(h) <= MIN_FIT_SIDE {
+            return Err(AppError::bad_request(format!(
+                "无法压缩到 {max_bytes} 字节以内（{w}x{h} 下最小为 {smallest} 字节）"
+            )));
+        }
+        // 体积大致与像素数成正比：按面积比缩小，每轮至少缩小 10%。
+        let ratio = ((max_bytes as f64 / smallest as f64).sqrt() * 0.95).min(0.9);
+        let nw = ((w as f64 * ratio).round() as u32).max(MIN_FIT_SIDE.min(w));
+        let nh = ((h as f64 * ratio).round() as u32).max(MIN_FIT_SIDE.min(h));
+        *img = img.resize_exact(nw, nh, image::imageops::FilterType::Lanczos3);
+    }
+}
+
+fn bad_request(msg: &str) -> Response {
+    (StatusCode::BAD_REQUEST, msg.to_string()).into_response()
+}
+
+/// 将用户文件名净化为 ASCII 安全的 stem（用于响应头，避免非 ASCII 文件名导致 panic）。
+fn sanitize_stem(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let trimmed = cleaned.trim_matches('_');
+    if trimmed.is_empty() {
+        "image".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 指定像素预算的测试配置。
+    pub(super) fn config(max_pixels: u64) -> ImageConfig {
+        ImageConfig {
+            max_upload_bytes: 1 << 20,
+            max_pixels,
+            pool: Arc::new(WorkerPool::new(1, 1, std::time::Duration::from_secs(10))),
+        }
+    }
+
+    /// 伪随机噪声图：难以压缩，便于测试体积上限。
+    pub(super) fn noise(w: u32, h: u32) -> DynamicImage {
+        let mut state = 0x2545_f491_u32;
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(w, h, |_, _| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [r, g, b, _] = state.to_be_bytes();
+            image::Rgb([r, g, b])
+        }))
+    }
+
+    fn error_code(e: &AppError) -> Option<&str> {
+        e.detail.as_ref()?.get("error")?.as_str()
+    }
+
+    #[test]
+    fn pixel_budget_counts_frames_and_totals() {
+        let cfg = config(100);
+        assert!(cfg.check_pixels(10, 10, 1).is_ok());
+        let e = cfg.check_pixels(10, 10, 2).err().unwrap();
+        assert_eq!(e.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error_code(&e), Some("pixel_limit_exceeded"));
+        assert_eq!(e.detail.as_ref().unwrap()["limit"], 100);
+        // 宽高相乘不会溢出
+        assert!(cfg.check_pixels(u32::MAX, u32::MAX, u64::from(u32::MAX)).is_err());
+
+        assert!(cfg.check_pixel_total(100).is_ok());
+        let e = cfg.check_pixel_total(101).err().unwrap();
+        assert_eq!(e.status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn decode_limit_errors_are_413_and_others_400() {
+        let cfg = config(1 << 20);
+        let limits = image::error::LimitError::from_kind(image::error::LimitErrorKind::InsufficientMemory);
+        let e = cfg.decode_error("解析图片失败", image::ImageError::Limits(limits));
+        assert_eq!(e.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error_code(&e), Some("pixel_limit_exceeded"));
+
+        let e = load_image(b"not an image", &Default::default(), &cfg).err().unwrap();
+        assert_eq!(e.status, StatusCode::BAD_REQUEST);
+        assert!(e.message.starts_with("解析图片失败"), "{}", e.message);
+
+        // 头部尺寸超出预算时不解码像素，直接 413
+        let png = encode(&noise(64, 64), OutputFormat::PNG, None).unwrap();
+        assert!(load_image(&png, &Default::default(), &config(64 * 64)).is_ok());
+        let e = load_image(&png, &Default::default(), &config(64 * 64 - 1)).err().unwrap();
+        assert_eq!(e.status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn encode_within_searches_quality_below_the_limit() {
+        let mut img = noise(96, 96);
+        let params = EncodeParams::default();
+        let full = encode_with(&img, OutputFormat::JPEG, params).unwrap().len();
+        let max_bytes = full / 2;
+
+        let out = encode_within(&mut img, OutputFormat::JPEG, OutputFormat::JPEG, params, max_bytes, false)
+            .unwrap_or_else(|e| panic!("{}", e.message));
+        assert!(out.bytes.len() <= max_bytes);
+        assert!(out.quality.is_some_and(|q| q < 90));
+        assert!(out.attempts > 1 && out.attempts <= MAX_FIT_ATTEMPTS);
+        assert_eq!(img.dimensions(), (96, 96));
+    }
+
+    #[test]
+    fn encode_within_downscales_lossless_output_only_when_allowed() {
+        let params = EncodeParams::default();
+        let mut img = noise(96, 96);
+        let max_bytes = encode_with(&img, OutputFormat::PNG, params).unwrap().len() / 3;
+
+        let e = encode_within(&mut img, OutputFormat::PNG, OutputFormat::PNG, params, max_bytes, false)
+            .err()
+            .unwrap();
+        assert_eq!(e.status, StatusCode::BAD_REQUEST);
+        assert_eq!(img.dimensions(), (96, 96));
+
+        let out = encode_within(&mut img, OutputFormat::PNG, OutputFormat::PNG, params, max_bytes, true)
+            .unwrap_or_else(|e| panic!("{}", e.message));
+        assert!(out.bytes.len() <= max_bytes);
+        assert!(out.png_color.is_some());
+        let (w, h) = img.dimensions();
+        assert!(w < 96 && w == h && w >= MIN_FIT_SIDE, "{w}x{h}");
+
+        let e = encode_within(&mut img, OutputFormat::PNG, OutputFormat::PNG, params, 0, true).err().unwrap();
+        assert_eq!(e.status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,445 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use super::color;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Balanced,
+    High,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+    /// 逐一尝试全部滤波方式，保留最小结果
+    Auto,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PngOptions {
+    pub compression: PngCompression,
+    pub filter: PngFilter,
+    /// 有损调色板量化的颜色数（2–256）；不设置时只做无损优化
+    pub colors: Option<u16>,
+    /// 量化时使用 Floyd–Steinberg 抖动
+    pub dither: bool,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            compression: PngCompression::default(),
+            filter: PngFilter::default(),
+            colors: None,
+            dither: true,
+        }
+    }
+}
+
+pub(super) struct PngEncoded {
+    pub bytes: Vec<u8>,
+    /// 实际采用的色彩类型与位深，如 `Indexed/4`、`Rgb/8`
+    pub color: String,
+}
+
+/// 待编码的原始扫描数据（已按位深打包）。
+struct Raw {
+    color: png::ColorType,
+    depth: png::BitDepth,
+    data: Vec<u8>,
+    palette: Option<Vec<u8>>,
+    trns: Option<Vec<u8>>,
+}
+
+/// 优化编码 PNG：无损模式下枚举可用的最小色彩类型 / 位深（调色板、灰度、RGB、RGBA），
+/// 结合所选压缩级别与滤波方式逐一编码，保留最小结果；指定 `colors` 时先做调色板量化。
+/// 16 位 / 浮点输入在无损模式下保留 16 位，除非像素值本身可无损降为 8 位。
+pub(super) fn encode_png(img: &DynamicImage, opts: &PngOptions) -> anyhow::Result<PngEncoded> {
+    let (width, height) = img.dimensions();
+    let wide = (opts.colors.is_none() && color::is_high_depth(img))
+        .then(|| img.to_rgba16())
+        .filter(|rgba| rgba.as_raw().iter().any(|v| v % 257 != 0));
+    let candidates = match (opts.colors, wide) {
+        (Some(colors), _) => vec![quantize(&img.to_rgba8(), usize::from(colors.clamp(2, 256)), opts.dither)],
+        (None, Some(rgba)) => vec![wide_candidate(&rgba)],
+        (None, None) => lossless_candidates(&img.to_rgba8()),
+    };
+    let filters: &[png::Filter] = match opts.filter {
+        PngFilter::None => &[png::Filter::NoFilter],
+        PngFilter::Sub => &[png::Filter::Sub],
+        PngFilter::Up => &[png::Filter::Up],
+        PngFilter::Avg => &[png::Filter::Avg],
+        PngFilter::Paeth => &[png::Filter::Paeth],
+        PngFilter::Adaptive => &[png::Filter::Adaptive],
+        PngFilter::Auto => &[
+            png::Filter::NoFilter,
+            png::Filter::Sub,
+            png::Filter::Up,
+            png::Filter::Avg,
+            png::Filter::Paeth,
+            png::Filter::Adaptive,
+        ],
+    };
+    let compression = match opts.compression {
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Balanced => png::Compression::Balanced,
+        PngCompression::High => png::Compression::High,
+    };
+
+    let mut best: Option<PngEncoded> = None;
+    for raw in &candidates {
+        for &filter in filters {
+            let bytes = write_png(raw, width, height, compression, filter)?;
+            if best.as_ref().is_none_or(|b| bytes.len() < b.bytes.len()) {
+                best = Some(PngEncoded {
+                    bytes,
+                    color: format!("{:?}/{}", raw.color, raw.depth as u8),
+                });
+            }
+        }
+    }
+    best.ok_or_else(|| anyhow::anyhow!("PNG 编码失败"))
+}
+
+fn write_png(
+    raw: &Raw,
+    width: u32,
+    height: u32,
+    compression: png::Compression,
+    filter: png::Filter,
+) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, width, height);
+    encoder.set_color(raw.color);
+    encoder.set_depth(raw.depth);
+    encoder.set_compression(compression);
+    encoder.set_filter(filter);
+    if let Some(palette) = &raw.palette {
+        encoder.set_palette(palette.clone());
+    }
+    if let Some(trns) = &raw.trns {
+        encoder.set_trns(trns.clone());
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&raw.data)?;
+    writer.finish()?;
+    Ok(buf)
+}
+
+/// 无损候选：颜色数 ≤ 256 时加入调色板；灰度图加入灰度（尽量降位深），否则 RGB / RGBA。
+fn lossless_candidates(rgba: &RgbaImage) -> Vec<Raw> {
+    let opaque = rgba.pixels().all(|p| p[3] == 255);
+    let gray = rgba.pixels().all(|p| p[0] == p[1] && p[1] == p[2]);
+    let mut out = Vec::new();
+
+    if let Some(palette) = exact_palette(rgba) {
+        let index: HashMap<[u8; 4], u8> =
+            palette.iter().enumerate().map(|(i, c)| (*c, i as u8)).collect();
+        let indices: Vec<u8> = rgba.pixels().map(|p| index[&p.0]).collect();
+        out.push(indexed(rgba.width(), &palette, &indices));
+    }
+
+    if gray && opaque {
+        let values: Vec<u8> = rgba.pixels().map(|p| p[0]).collect();
+        let bits = [1u8, 2, 4]
+            .into_iter()
+            .find(|&b| {
+                let step = 255 / ((1u16 << b) - 1) as u8;
+                values.iter().all(|v| v % step == 0)
+            })
+            .unwrap_or(8);
+        let step = 255 / ((1u16 << bits) - 1) as u8;
+        let scaled: Vec<u8> = values.iter().map(|v| v / step).collect();
+        out.push(Raw {
+            color: png::ColorType::Grayscale,
+            depth: bit_depth(bits),
+            data: pack(&scaled, rgba.width() as usize, bits),
+            palette: None,
+            trns: None,
+        });
+    } else if gray {
+        out.push(Raw {
+            color: png::ColorType::GrayscaleAlpha,
+            depth: png::BitDepth::Eight,
+            data: rgba.pixels().flat_map(|p| [p[0], p[3]]).collect(),
+            palette: None,
+            trns: None,
+        });
+    } else if opaque {
+        out.push(Raw {
+            color: png::ColorType::Rgb,
+            depth: png::BitDepth::Eight,
+            data: rgba.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect(),
+            palette: None,
+            trns: None,
+        });
+    } else {
+        out.push(Raw {
+            color: png::ColorType::Rgba,
+            depth: png::BitDepth::Eight,
+            data: rgba.as_raw().clone(),
+            palette: None,
+            trns: None,
+        });
+    }
+    out
+}
+
+/// 16 位候选：按是否灰度 / 是否不透明选最少通道，样本按大端序写出。
+fn wide_candidate(rgba: &image::ImageBuffer<Rgba<u16>, Vec<u16>>) -> Raw {
+    let opaque = rgba.pixels().all(|p| p[3] == u16::MAX);
+    let gray = rgba.pixels().all(|p| p[0] == p[1] && p[1] == p[2]);
+    let (color, channels): (png::ColorType, &[usize]) = match (gray, opaque) {
+        (true, true) => (png::ColorType::Grayscale, &[0]),
+        (true, false) => (png::ColorType::GrayscaleAlpha, &[0, 3]),
+        (false, true) => (png::ColorType::Rgb, &[0, 1, 2]),
+        (false, false) => (png::ColorType::Rgba, &[0, 1, 2, 3]),
+    };
+    Raw {
+        color,
+        depth: png::BitDepth::Sixteen,
+        data: rgba
+            .pixels()
+            .flat_map(|p| channels.iter().flat_map(move |&c| p[c].to_be_bytes()))
+            .collect(),
+        palette: None,
+        trns: None,
+    }
+}
+
+/// 统计不同颜色，超过 256 种返回 `None`；带透明度的颜色排在前面，便于截短 tRNS。
+fn exact_palette(rgba: &RgbaImage) -> Option<Vec<[u8; 4]>> {
+    let mut seen: HashSet<[u8; 4]> = HashSet::new();
+    for p in rgba.pixels() {
+        seen.insert(p.0);
+        if seen.len() > 256 {
+            return None;
+        }
+    }
+    let mut palette: Vec<[u8; 4]> = seen.into_iter().collect();
+    palette.sort_unstable_by_key(|c| (c[3] == 255, *c));
+    Some(palette)
+}
+
+fn indexed(width: u32, palette: &[[u8; 4]], indices: &[u8]) -> Raw {
+    let bits = match palette.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+    let plte: Vec<u8> = palette.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let mut trns: Vec<u8> = palette.iter().map(|c| c[3]).collect();
+    while trns.last() == Some(&255) {
+        trns.pop();
+    }
+    Raw {
+        color: png::ColorType::Indexed,
+        depth: bit_depth(bits),
+        data: pack(indices, width as usize, bits),
+        palette: Some(plte),
+        trns: (!trns.is_empty()).then_some(trns),
+    }
+}
+
+fn bit_depth(bits: u8) -> png::BitDepth {
+    match bits {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    }
+}
+
+/// 将每像素一个样本的数据按位深打包，每行按字节对齐（高位在前）。
+fn pack(samples: &[u8], width: usize, bits: u8) -> Vec<u8> {
+    if bits == 8 {
+        return samples.to_vec();
+    }
+    let per_byte = 8 / bits as usize;
+    let row_bytes = width.div_ceil(per_byte);
+    let mut out = Vec::with_capacity(row_bytes * samples.len() / width.max(1));
+    for row in samples.chunks(width) {
+        let mut packed = vec![0u8; row_bytes];
+        for (x, &v) in row.iter().enumerate() {
+            let shift = 8 - bits as usize * (x % per_byte + 1);
+            packed[x / per_byte] |= v << shift;
+        }
+        out.extend_from_slice(&packed);
+    }
+    out
+}
+
+// =====================================================================
+// 有损量化：中位切分（RGBA 四通道）+ 可选 Floyd–Steinberg 抖动
+// =====================================================================
+
+/// 量化统计时每通道保留的高位数，降低不同颜色的数量。
+const QUANT_BITS: u32 = 5;
+
+#[derive(Clone, Copy)]
+struct ColorBin {
+    sum: [u64; 4],
+    count: u64,
+}
+
+impl ColorBin {
+    fn mean(&self) -> [u8; 4] {
+        let n = self.count.max(1);
+        self.sum.map(|s| (s / n) as u8)
+    }
+}
+
+fn quantize(rgba: &RgbaImage, colors: usize, dither: bool) -> Raw {
+    let palette = median_cut(rgba, colors);
+    let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+    let mut nearest_cache: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut nearest = |c: [u8; 4]| -> u8 {
+        let key = c.map(|v| v & 0xfc);
+        *nearest_cache
+            .entry(key)
+            .or_insert_with(|| nearest_index(&palette, key))
+    };
+
+    let mut indices = vec![0u8; w * h];
+    if dither {
+        // 只保留当前行与下一行的误差缓冲（左右各留一格边界）
+        let mut cur = vec![[0f32; 4]; w + 2];
+        let mut next = vec![[0f32; 4]; w + 2];
+        for y in 0..h {
+            for x in 0..w {
+                let p = rgba.get_pixel(x as u32, y as u32).0;
+                let e = cur[x + 1];
+                let want: [u8; 4] =
+                    std::array::from_fn(|c| (p[c] as f32 + e[c]).round().clamp(0.0, 255.0) as u8);
+                let idx = nearest(want);
+                indices[y * w + x] = idx;
+                let got = palette[idx as usize];
+                for c in 0..4 {
+                    let err = want[c] as f32 - got[c] as f32;
+                    cur[x + 2][c] += err * 7.0 / 16.0;
+                    next[x][c] += err * 3.0 / 16.0;
+                    next[x + 1][c] += err * 5.0 / 16.0;
+                    next[x + 2][c] += err / 16.0;
+                }
+            }
+            std::mem::swap(&mut cur, &mut next);
+            next.iter_mut().for_each(|e| *e = [0.0; 4]);
+        }
+    } else {
+        for (i, p) in rgba.pixels().enumerate() {
+            indices[i] = nearest(p.0);
+        }
+    }
+
+    indexed(rgba.width(), &palette, &indices)
+}
+
+fn nearest_index(palette: &[[u8; 4]], c: [u8; 4]) -> u8 {
+    let dist = |p: &[u8; 4]| -> i32 {
+        (0..4)
+            .map(|i| {
+                let d = p[i] as i32 - c[i] as i32;
+                d * d
+            })
+            .sum()
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| dist(p))
+        .map(|(i, _)| i as u8)
+        .unwrap_or(0)
+}
+
+/// 中位切分：反复选取「像素数 × 最大通道跨度」最大的盒子，沿跨度最大的通道在加权中位数处切开。
+fn median_cut(rgba: &RgbaImage, colors: usize) -> Vec<[u8; 4]> {
+    let shift = 8 - QUANT_BITS;
+    let mut bins: HashMap<[u8; 4], ColorBin> = HashMap::new();
+    for p in rgba.pixels() {
+        let bin = bins.entry(p.0.map(|v| v >> shift)).or_insert(ColorBin {
+            sum: [0; 4],
+            count: 0,
+        });
+        for c in 0..4 {
+            bin.sum[c] += p[c] as u64;
+        }
+        bin.count += 1;
+    }
+
+    let mut boxes: Vec<Vec<ColorBin>> = vec![bins.into_values().collect()];
+    while boxes.len() < colors {
+        let scored = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, span) = widest_channel(b);
+                let count: u64 = b.iter().map(|e| e.count).sum();
+                (i, channel, span as u64 * count)
+            })
+            .max_by_key(|&(_, _, score)| score);
+        let Some((i, channel, _)) = scored else {
+            break;
+        };
+
+        let mut bx = boxes.swap_remove(i);
+        bx.sort_unstable_by_key(|e| e.mean()[channel]);
+        let total: u64 = bx.iter().map(|e| e.count).sum();
+        let mut acc = 0u64;
+        let mut split = 1;
+        for (k, e) in bx.iter().enumerate() {
+            acc += e.count;
+            if acc * 2 >= total {
+                split = (k + 1).clamp(1, bx.len() - 1);
+                break;
+            }
+        }
+        let rest = bx.split_off(split);
+        boxes.push(bx);
+        boxes.push(rest);
+    }
+
+    let mut palette: Vec<[u8; 4]> = boxes
+        .iter()
+        .map(|b| {
+            let merged = b.iter().fold(ColorBin { sum: [0; 4], count: 0 }, |mut acc, e| {
+                for c in 0..4 {
+                    acc.sum[c] += e.sum[c];
+                }
+                acc.count += e.count;
+                acc
+            });
+            merged.mean()
+        })
+        .collect();
+    palette.sort_unstable_by_key(|c| (c[3] == 255, *c));
+    palette.dedup();
+    palette
+}
+
+fn widest_channel(bins: &[ColorBin]) -> (usize, u8) {
+    (0..4)
+        .map(|c| {
+            let (lo, hi) = bins.iter().fold((255u8, 0u8), |(lo, hi), e| {
+                let v = e.mean()[c];
+                (lo.min(v), hi.max(v))
+            });
+            (c, hi - lo)
+        })
+        .max_by_key(|&(_, span)| span)
+        .unwrap_or((0, 0))
+}
@@ -0,0 +1,92 @@
+use axum::http::StatusCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::handlers::error::AppError;
+
+/// 图片处理的阻塞任务池：同时最多 `workers` 个任务在 blocking 线程上执行，
+/// 其余最多 `max_queue` 个排队等待，队列满时直接返回 503。
+#[derive(Debug)]
+pub struct WorkerPool {
+    permits: Arc<Semaphore>,
+    waiting: AtomicUsize,
+    max_queue: usize,
+    timeout: Duration,
+}
+
+impl WorkerPool {
+    pub fn new(workers: usize, max_queue: usize, timeout: Duration) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(workers.max(1))),
+            waiting: AtomicUsize::new(0),
+            max_queue,
+            timeout,
+        }
+    }
+
+    /// 在 blocking 线程上执行 `job`，排队与执行总时长超过 `timeout` 返回 504。
+    /// 超时后任务仍会跑完并一直占用名额，保证同时执行的任务数不超过上限。
+    pub(super) async fn run<T, F>(&self, job: F) -> Result<T, AppError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, AppError> + Send + 'static,
+    {
+        let task = async {
+            let permit = match self.permits.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    // 排队计数随 guard 释放，等待中超时被取消也能正确归还
+                    let queued = Queued::enter(&self.waiting);
+                    if queued.position >= self.max_queue {
+                        return Err(AppError::coded(
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            "server_busy",
+                            "图片处理任务过多，请稍后重试",
+                        ));
+                    }
+                    self.permits
+                        .clone()
+                        .acquire_owned()
+                        .await
+                        .map_err(|_| anyhow::anyhow!("任务池已关闭"))?
+                }
+            };
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                job()
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("图片处理任务异常退出: {e}"))?
+        };
+
+        tokio::time::timeout(self.timeout, task).await.map_err(|_| {
+            AppError::coded(
+                StatusCode::GATEWAY_TIMEOUT,
+                "timeout",
+                format!("图片处理超时（超过 {} 秒）", self.timeout.as_secs()),
+            )
+        })?
+    }
+}
+
+/// 排队中的任务计数 guard。
+struct Queued<'a> {
+    counter: &'a AtomicUsize,
+    /// 进入队列前已在等待的任务数
+    position: usize,
+}
+
+impl<'a> Queued<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        let position = counter.fetch_add(1, Ordering::SeqCst);
+        Self { counter, position }
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::SeqCst);
+    }
+}
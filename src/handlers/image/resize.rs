@@ -0,0 +1,227 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba, RgbaImage};
+use serde::Deserialize;
+
+use super::{color, parse_hex_color, ImageConfig};
+use super::watermark::Gravity;
+use crate::handlers::error::AppError;
+
+/// `scale` 百分比的上限。
+const MAX_SCALE_PERCENT: f32 = 1000.0;
+
+/// 重采样滤镜，从快到慢、从锐利到平滑。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// 缩放模式，语义同 CSS `object-fit`（另加 sharp 的 inside / outside）。
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFit {
+    /// 等比缩放到框内，空白处用 `background` 填充，输出尺寸等于框
+    Contain,
+    /// 等比缩放到铺满框，超出部分按 `gravity` 裁剪
+    Cover,
+    /// 拉伸到框的尺寸，不保持比例
+    Fill,
+    /// 等比缩放到框内，不填充
+    Inside,
+    /// 等比缩放到覆盖框，不裁剪
+    Outside,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ResizeOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// 按百分比缩放（如 50 表示缩小一半），与 width / height 互斥
+    pub scale: Option<f32>,
+    /// 旧参数：未指定 `fit` 时，true 等同 inside，false 等同 fill
+    #[serde(default)]
+    pub keep_aspect_ratio: bool,
+    pub fit: Option<ResizeFit>,
+    #[serde(default)]
+    pub filter: ResizeFilter,
+    /// cover 的裁剪方位 / contain 的放置方位，默认居中
+    pub gravity: Option<Gravity>,
+    /// contain 的填充色（#rrggbb），默认透明
+    pub background: Option<String>,
+    /// 不放大：目标尺寸大于原图时保持原尺寸
+    #[serde(default)]
+    pub no_upscale: bool,
+}
+
+/// 缩放计划：先缩放到 `scaled`，再裁剪（cover）或填充（contain）到 `canvas`。
+struct Plan {
+    scaled: (u32, u32),
+    canvas: (u32, u32),
+    fit: ResizeFit,
+}
+
+impl ResizeOptions {
+    /// 根据原图尺寸计算缩放计划；无需缩放时返回 None。
+    fn plan(&self, (ow, oh): (u32, u32)) -> Result<Option<Plan>, AppError> {
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err(AppError::bad_request("resize.width / height 须大于 0"));
+        }
+        let (ow_f, oh_f) = (f64::from(ow), f64::from(oh));
+        let px = |v: f64| (v.round() as u32).max(1);
+
+        let (fit, target) = match (self.scale, self.width, self.height) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                return Err(AppError::bad_request("resize.scale 与 width / height 不能同时使用"));
+            }
+            (Some(s), None, None) => {
+                if !(s > 0.0 && s <= MAX_SCALE_PERCENT) {
+                    return Err(AppError::bad_request(format!(
+                        "resize.scale 须在 (0, {MAX_SCALE_PERCENT}] 之间（百分比）"
+                    )));
+                }
+                let s = f64::from(s) / 100.0;
+                (ResizeFit::Fill, (px(ow_f * s), px(oh_f * s)))
+            }
+            (None, None, None) => return Ok(None),
+            (None, w, h) => {
+                let fit = self.fit.unwrap_or(if self.keep_aspect_ratio {
+                    ResizeFit::Inside
+                } else {
+                    ResizeFit::Fill
+                });
+                let target = match (w, h) {
+                    (Some(w), Some(h)) => (w, h),
+                    // 只给一边时：fill 保留另一边原尺寸，其余模式按比例推算另一边
+                    (Some(w), None) if fit == ResizeFit::Fill => (w, oh),
+                    (None, Some(h)) if fit == ResizeFit::Fill => (ow, h),
+                    (Some(w), None) => (w, px(oh_f * f64::from(w) / ow_f)),
+                    (None, Some(h)) => (px(ow_f * f64::from(h) / oh_f), h),
+                    (None, None) => unreachable!(),
+                };
+                (fit, target)
+            }
+        };
+
+        let (tw, th) = target;
+        let (rx, ry) = (f64::from(tw) / ow_f, f64::from(th) / oh_f);
+        let plan = match fit {
+            ResizeFit::Fill => {
+                let scaled = if self.no_upscale { (tw.min(ow), th.min(oh)) } else { (tw, th) };
+                Plan { scaled, canvas: scaled, fit }
+            }
+            ResizeFit::Contain | ResizeFit::Inside | ResizeFit::Cover | ResizeFit::Outside => {
+                let mut ratio = if matches!(fit, ResizeFit::Contain | ResizeFit::Inside) {
+                    rx.min(ry)
+                } else {
+                    rx.max(ry)
+                };
+                if self.no_upscale {
+                    ratio = ratio.min(1.0);
+                }
+                let scaled = (px(ow_f * ratio), px(oh_f * ratio));
+                let canvas = match fit {
+                    ResizeFit::Contain => (tw, th),
+                    ResizeFit::Cover => (tw.min(scaled.0), th.min(scaled.1)),
+                    _ => scaled,
+                };
+                Plan { scaled, canvas, fit }
+            }
+        };
+        Ok(Some(plan))
+    }
+
+    fn apply(&self, img: &DynamicImage, plan: &Plan) -> Result<DynamicImage, AppError> {
+        let (sw, sh) = plan.scaled;
+        let scaled = if img.dimensions() == (sw, sh) {
+            img.clone()
+        } else {
+            img.resize_exact(sw, sh, self.filter.into())
+        };
+        let gravity = self.gravity.unwrap_or(Gravity::Center);
+        let (cw, ch) = plan.canvas;
+        Ok(match plan.fit {
+            ResizeFit::Cover if (cw, ch) != (sw, sh) => {
+                let (x, y) = gravity.place((sw, sh), (cw, ch), 0);
+                scaled.crop_imm(x.max(0) as u32, y.max(0) as u32, cw, ch)
+            }
+            ResizeFit::Contain if (cw, ch) != (sw, sh) => {
+                let background = match self.background.as_deref() {
+                    Some(text) => parse_hex_color(text)
+                        .ok_or_else(|| AppError::bad_request("resize.background 须为 #rrggbb 格式"))?,
+                    None => Rgba([0, 0, 0, 0]),
+                };
+                let (x, y) = gravity.place((cw, ch), (sw, sh), 0);
+                if color::is_high_depth(&scaled) {
+                    let background = Rgba(background.0.map(|v| u16::from(v) * 257));
+                    let mut canvas = ImageBuffer::from_pixel(cw, ch, background);
+                    image::imageops::overlay(&mut canvas, &scaled.to_rgba16(), x, y);
+                    DynamicImage::ImageRgba16(canvas)
+                } else {
+                    let mut canvas = RgbaImage::from_pixel(cw, ch, background);
+                    image::imageops::overlay(&mut canvas, &scaled.to_rgba8(), x, y);
+                    DynamicImage::ImageRgba8(canvas)
+                }
+            }
+            _ => scaled,
+        })
+    }
+
+    /// 按缩放图与画布中较大者校验像素上限。
+    fn check(plan: &Plan, frames: u64, cfg: &ImageConfig) -> Result<(), AppError> {
+        let (sw, sh) = plan.scaled;
+        let (cw, ch) = plan.canvas;
+        if u64::from(sw) * u64::from(sh) >= u64::from(cw) * u64::from(ch) {
+            cfg.check_pixels(sw, sh, frames)
+        } else {
+            cfg.check_pixels(cw, ch, frames)
+        }
+    }
+}
+
+/// 按 `opts` 缩放单张图片。
+pub(super) fn do_resize(
+    img: &DynamicImage,
+    opts: &ResizeOptions,
+    cfg: &ImageConfig,
+) -> Result<DynamicImage, AppError> {
+    match opts.plan(img.dimensions())? {
+        Some(plan) => {
+            ResizeOptions::check(&plan, 1, cfg)?;
+            opts.apply(img, &plan)
+        }
+        None => Ok(img.clone()),
+    }
+}
+
+/// 对同尺寸的一组帧应用同一缩放计划，像素上限按帧累计。
+pub(super) fn resize_all(
+    images: Vec<DynamicImage>,
+    opts: &ResizeOptions,
+    cfg: &ImageConfig,
+) -> Result<Vec<DynamicImage>, AppError> {
+    let Some(first) = images.first() else {
+        return Ok(images);
+    };
+    let Some(plan) = opts.plan(first.dimensions())? else {
+        return Ok(images);
+    };
+    ResizeOptions::check(&plan, images.len() as u64, cfg)?;
+    images.iter().map(|img| opts.apply(img, &plan)).collect()
+}
@@ -0,0 +1,404 @@
+use axum::extract::{Multipart, State};
+use axum::response::Response;
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+
+use super::{
+    attachment, encode_with, header_dimensions, load_image, output_name, parse_format, read_upload, sanitize_stem,
+    EncodeParams, ImageConfig, OutputFormat,
+};
+use crate::handlers::error::AppError;
+
+/// 单张精灵图最多合并的图片数。
+const MAX_SPRITES: usize = 2000;
+/// 精灵图画布的最大边长。
+const MAX_SHEET_SIDE: u32 = 8192;
+/// 图片间距上限（像素）。
+const MAX_PADDING: u32 = 1024;
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct SpriteOptions {
+    /// 图片之间的间距（像素）
+    pub padding: u32,
+    /// 裁掉每张图四周的全透明边，坐标表中记录原始尺寸与偏移
+    pub trim: bool,
+    /// 画布最大宽度，不填则按总面积自动估算接近正方形的宽度
+    pub max_width: Option<u32>,
+    /// CSS 类名前缀
+    pub prefix: String,
+    /// WebP 有损编码质量，不填为无损
+    pub quality: Option<u8>,
+}
+
+impl Default for SpriteOptions {
+    fn default() -> Self {
+        Self {
+            padding: 2,
+            trim: false,
+            max_width: None,
+            prefix: "sprite".into(),
+            quality: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SpriteSheet {
+    image: String,
+    width: u32,
+    height: u32,
+    sprites: Vec<SpriteEntry>,
+}
+
+/// 单张图在精灵图中的位置；`offset_*` / `source_*` 描述裁边前的原始画布。
+#[derive(Serialize)]
+struct SpriteEntry {
+    name: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    trimmed: bool,
+    offset_x: u32,
+    offset_y: u32,
+    source_width: u32,
+    source_height: u32,
+}
+
+struct Sprite {
+    name: String,
+    image: RgbaImage,
+    offset: (u32, u32),
+    source: (u32, u32),
+}
+
+pub async fn sprite(State(cfg): State<ImageConfig>, mut mp: Multipart) -> Result<Response, AppError> {
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut format = OutputFormat::PNG;
+    let mut options = SpriteOptions::default();
+
+    while let Some(field) = mp
+        .next_field()
+        .await
+        .map_err(AppError::multipart)?
+    {
+        match field.name().unwrap_or("") {
+            "file" => {
+                if files.len() >= MAX_SPRITES {
+                    return Err(AppError::bad_request(format!("图片过多，最多 {MAX_SPRITES} 张")));
+                }
+                let name = field
+                    .file_name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("image_{}", files.len() + 1));
+                files.push((name, read_upload(field, &cfg).await?));
+            }
+            "format" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("读取 format 字段失败: {e}"))?;
+                format = match parse_format(&text) {
+                    Some(f @ (OutputFormat::PNG | OutputFormat::WEBP)) => f,
+                    _ => return Err(AppError::bad_request("format 须为 PNG / WEBP")),
+                };
+            }
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| AppError::bad_request(format!("options 解析失败: {e}")))?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if files.is_empty() {
+        return Err(AppError::bad_request("缺少 file 字段"));
+    }
+    if options.padding > MAX_PADDING {
+        return Err(AppError::bad_request(format!("padding 不能超过 {MAX_PADDING}")));
+    }
+
+    cfg.run(move |cfg| {
+        let mut used_names = HashSet::new();
+        let mut sprites = Vec::with_capacity(files.len());
+        // 解码前先按文件头校验单边上限与累计像素，避免大量大图全部解码后才被拒绝
+        let mut total = 0u64;
+        for (file_name, bytes) in &files {
+            if let Some((w, h)) = header_dimensions(bytes) {
+                check_side(file_name, w, h)?;
+                cfg.check_pixel_total(total + u64::from(w) * u64::from(h))?;
+            }
+            let img = load_image(bytes, &Default::default(), cfg).map_err(|mut e| {
+                e.message = format!("{file_name}: {}", e.message);
+                e
+            })?;
+            let source = img.dimensions();
+            check_side(file_name, source.0, source.1)?;
+            total += u64::from(source.0) * u64::from(source.1);
+            cfg.check_pixel_total(total)?;
+            let (image, offset) = if options.trim {
+                trim(&img)
+            } else {
+                (img.to_rgba8(), (0, 0))
+            };
+            sprites.push(Sprite {
+                name: unique_name(file_name, &mut used_names),
+                image,
+                offset,
+                source,
+            });
+        }
+
+        let sizes: Vec<(u32, u32)> = sprites.iter().map(|s| s.image.dimensions()).collect();
+        let Packing { positions, width, height } = pack(&sizes, options.padding, options.max_width)?;
+        cfg.check_pixels(width, height, 1)?;
+
+        let mut sheet = RgbaImage::new(width, height);
+        for (sprite, &(x, y)) in sprites.iter().zip(&positions) {
+            image::imageops::replace(&mut sheet, &sprite.image, i64::from(x), i64::from(y));
+        }
+        let image_name = format!("sprite.{}", format.extension());
+        let params = EncodeParams {
+            quality: options.quality,
+            webp_lossy: options.quality.is_some_and(|q| q < 100),
+            png: None,
+        };
+        let encoded = encode_with(&DynamicImage::ImageRgba8(sheet), format, params)?;
+
+        let manifest = SpriteSheet {
+            image: image_name.clone(),
+            width,
+            height,
+            sprites: sprites
+                .iter()
+                .zip(&positions)
+                .map(|(s, &(x, y))| SpriteEntry {
+                    name: s.name.clone(),
+                    x,
+                    y,
+                    width: s.image.width(),
+                    height: s.image.height(),
+                    trimmed: s.image.dimensions() != s.source,
+                    offset_x: s.offset.0,
+                    offset_y: s.offset.1,
+                    source_width: s.source.0,
+                    source_height: s.source.1,
+                })
+                .collect(),
+        };
+        let css = stylesheet(&manifest, &sanitize_stem(&options.prefix));
+
+        let zip = (|| -> anyhow::Result<Vec<u8>> {
+            use zip::write::SimpleFileOptions;
+            let opts = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            zip.start_file(image_name.as_str(), opts)?;
+            zip.write_all(&encoded)?;
+            zip.start_file("sprite.json", opts)?;
+            zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+            zip.start_file("sprite.css", opts)?;
+            zip.write_all(css.as_bytes())?;
+            Ok(zip.finish()?.into_inner())
+        })()?;
+
+        Ok(attachment(
+            zip,
+            "application/zip",
+            &output_name("sprite", "zip"),
+            vec![
+                ("X-Width", width.to_string()),
+                ("X-Height", height.to_string()),
+                ("X-Sprites", sprites.len().to_string()),
+            ],
+        ))
+    })
+    .await
+}
+
+/// 单张图的宽高不能超过画布上限。
+fn check_side(file_name: &str, w: u32, h: u32) -> Result<(), AppError> {
+    if w.max(h) > MAX_SHEET_SIDE {
+        return Err(AppError::bad_request(format!(
+            "{file_name}: 图片尺寸 {w}x{h} 超过画布上限 {MAX_SHEET_SIDE}"
+        )));
+    }
+    Ok(())
+}
+
+/// 裁掉四周 alpha 为 0 的像素，返回裁剪后的图与左上角偏移；全透明图保留 1×1。
+fn trim(img: &DynamicImage) -> (RgbaImage, (u32, u32)) {
+    let rgba = img.to_rgba8();
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, p) in rgba.enumerate_pixels() {
+        if p[3] != 0 {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+    }
+    if x0 > x1 {
+        return (RgbaImage::new(1, 1), (0, 0));
+    }
+    let cropped = image::imageops::crop_imm(&rgba, x0, y0, x1 - x0 + 1, y1 - y0 + 1).to_image();
+    (cropped, (x0, y0))
+}
+
+/// 装箱结果：每张图的左上角坐标（与输入顺序一致）及画布尺寸。
+struct Packing {
+    positions: Vec<(u32, u32)>,
+    width: u32,
+    height: u32,
+}
+
+/// 货架式装箱（FFDH）：按高度降序逐个放入第一个放得下的货架，放不下时在底部开新货架。
+fn pack(
+    sizes: &[(u32, u32)],
+    padding: u32,
+    max_width: Option<u32>,
+) -> Result<Packing, AppError> {
+    let widest = sizes.iter().map(|s| s.0).max().unwrap_or(1);
+    let tallest = sizes.iter().map(|s| s.1).max().unwrap_or(1);
+    if widest > MAX_SHEET_SIDE {
+        return Err(AppError::bad_request(format!("图片宽度 {widest} 超过画布上限 {MAX_SHEET_SIDE}")));
+    }
+    if tallest > MAX_SHEET_SIDE {
+        return Err(AppError::bad_request(format!("图片高度 {tallest} 超过画布上限 {MAX_SHEET_SIDE}")));
+    }
+    let area: f64 = sizes
+        .iter()
+        .map(|&(w, h)| f64::from(w + padding) * f64::from(h + padding))
+        .sum();
+    let limit = max_width
+        .unwrap_or_else(|| (area.sqrt() * 1.1).ceil() as u32)
+        .clamp(widest, MAX_SHEET_SIDE);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].1.cmp(&sizes[a].1).then(sizes[b].0.cmp(&sizes[a].0)));
+
+    // 每个货架：(y, 高度, 已用宽度)
+    let mut shelves: Vec<(u32, u32, u32)> = Vec::new();
+    let mut positions = vec![(0, 0); sizes.len()];
+    let mut height = 0u32;
+    for i in order {
+        let (w, h) = sizes[i];
+        let (x, y) = match shelves.iter_mut().find(|s| h <= s.1 && s.2 + w <= limit) {
+            Some(shelf) => {
+                let pos = (shelf.2, shelf.0);
+                shelf.2 += w + padding;
+                pos
+            }
+            None => {
+                let y = if shelves.is_empty() { 0 } else { height + padding };
+                shelves.push((y, h, w + padding));
+                height = y + h;
+                // 边长与间距都有上限，逐个货架检查即可保证累加不溢出
+                if height > MAX_SHEET_SIDE {
+                    return Err(AppError::bad_request(format!(
+                        "精灵图高度 {height} 超过上限 {MAX_SHEET_SIDE}，请减少图片或增大 max_width"
+                    )));
+                }
+                (0, y)
+            }
+        };
+        positions[i] = (x, y);
+    }
+
+    let width = positions
+        .iter()
+        .zip(sizes)
+        .map(|(&(x, _), &(w, _))| x + w)
+        .max()
+        .unwrap_or(1);
+    Ok(Packing {
+        positions,
+        width: width.max(1),
+        height: height.max(1),
+    })
+}
+
+/// 由文件名生成唯一的 CSS 友好名称，重名时追加序号。
+fn unique_name(file_name: &str, used: &mut HashSet<String>) -> String {
+    let stem = std::path::Path::new(file_name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    let base = sanitize_stem(stem).to_ascii_lowercase();
+    let mut name = base.clone();
+    let mut n = 2;
+    while !used.insert(name.clone()) {
+        name = format!("{base}_{n}");
+        n += 1;
+    }
+    name
+}
+
+fn stylesheet(sheet: &SpriteSheet, prefix: &str) -> String {
+    let mut css = format!(
+        ".{prefix} {{\n  display: inline-block;\n  background-image: url(\"{}\");\n  background-repeat: no-repeat;\n}}\n",
+        sheet.image
+    );
+    let offset = |v: u32| if v == 0 { "0".to_string() } else { format!("-{v}px") };
+    for s in &sheet.sprites {
+        css.push_str(&format!(
+            "\n.{prefix}-{} {{\n  width: {}px;\n  height: {}px;\n  background-position: {} {};\n}}\n",
+            s.name,
+            s.width,
+            s.height,
+            offset(s.x),
+            offset(s.y)
+        ));
+    }
+    css
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn overlaps(a: ((u32, u32), (u32, u32)), b: ((u32, u32), (u32, u32))) -> bool {
+        let (((ax, ay), (aw, ah)), ((bx, by), (bw, bh))) = (a, b);
+        ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah
+    }
+
+    #[test]
+    fn packed_images_stay_inside_the_sheet_without_overlap() {
+        let sizes = [(30, 20), (10, 40), (25, 25), (50, 5), (8, 8), (40, 12)];
+        for (padding, max_width) in [(0, None), (4, None), (2, Some(60))] {
+            let packing = pack(&sizes, padding, max_width).ok().unwrap();
+            if let Some(max_width) = max_width {
+                assert!(packing.width <= max_width);
+            }
+            let boxes: Vec<_> = packing.positions.iter().copied().zip(sizes).collect();
+            for (i, &((x, y), (w, h))) in boxes.iter().enumerate() {
+                assert!(x + w <= packing.width && y + h <= packing.height);
+                // 间距计入占用区域后仍互不重叠
+                for &other in &boxes[i + 1..] {
+                    let padded = |((x, y), (w, h)): ((u32, u32), (u32, u32))| ((x, y), (w + padding, h + padding));
+                    assert!(!overlaps(padded(boxes[i]), padded(other)), "{:?} 与 {:?} 重叠", boxes[i], other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sheets_beyond_the_side_limit_are_rejected() {
+        let e = pack(&[(MAX_SHEET_SIDE + 1, 1)], 0, None).err().unwrap();
+        assert_eq!(e.status, StatusCode::BAD_REQUEST);
+        let e = pack(&[(1, MAX_SHEET_SIDE + 1)], 0, None).err().unwrap();
+        assert_eq!(e.status, StatusCode::BAD_REQUEST);
+
+        // 每个货架只放得下一张，高度累加超过上限
+        let tall = vec![(MAX_SHEET_SIDE, MAX_SHEET_SIDE / 2); 3];
+        let e = pack(&tall, 0, Some(MAX_SHEET_SIDE)).err().unwrap();
+        assert!(e.message.contains("精灵图高度"), "{}", e.message);
+        assert!(pack(&tall[..2], 0, Some(MAX_SHEET_SIDE)).is_ok());
+    }
+}
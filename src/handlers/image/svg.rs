@@ -0,0 +1,247 @@
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, ImageReader, Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use super::{encode_with, parse_format, EncodeParams, ImageConfig, OutputFormat};
+use crate::handlers::error::AppError;
+
+/// 光栅化输出的最大边长，避免超大画布耗尽内存。
+const MAX_SVG_SIDE: u32 = 8192;
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct SvgOptions {
+    /// 光栅化目标宽 / 高（像素）；只给一边时按比例计算另一边
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// 未指定宽高时按 SVG 自身尺寸 × dpi / 96 输出，默认 96
+    pub dpi: Option<f32>,
+    /// 输出 SVG 时内嵌的栅格格式：PNG（默认）/ JPEG / WEBP
+    pub embed: Option<String>,
+}
+
+/// 粗略判断上传内容是否为 SVG：跳过 BOM 与空白后以 `<svg` 开头，
+/// 或以 `<?xml` / `<!--` / `<!DOCTYPE` 开头且前 4KB 内出现 `<svg`。
+pub(super) fn is_svg(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(4096)];
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with("<svg")
+        || ((text.starts_with("<?xml") || text.starts_with("<!--") || text.starts_with("<!DOCTYPE"))
+            && text.contains("<svg"))
+}
+
+/// 内置字体（Tuffy，公有领域），保证没有系统字体的环境也能渲染文字水印。
+const EMBEDDED_FONT: &[u8] = include_bytes!("fonts/Tuffy.ttf");
+const EMBEDDED_FONT_FAMILY: &str = "Tuffy";
+
+/// 内置字体 + 系统字体只加载一次，供 SVG 中的 `<text>` 与文字水印使用。
+fn fontdb() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_font_data(EMBEDDED_FONT.to_vec());
+            db.load_system_fonts();
+            db.set_sans_serif_family(EMBEDDED_FONT_FAMILY);
+            Arc::new(db)
+        })
+        .clone()
+}
+
+/// `cfg` 为 None 时忽略全部 `<image>`；否则只接受 data URI，内嵌栅格图按像素预算与内存上限
+/// 试解码，超限时记入 `rejected` 并丢弃该图片。嵌套的 SVG 不再允许内嵌图片。
+fn usvg_options(cfg: Option<&ImageConfig>, rejected: &Rejected) -> usvg::Options<'static> {
+    let resolve_data: usvg::ImageHrefDataResolverFn<'static> = match cfg {
+        None => Box::new(|_, _, _| None),
+        Some(cfg) => {
+            let (cfg, rejected) = (cfg.clone(), rejected.clone());
+            Box::new(move |mime, data, _| {
+                if mime == "image/svg+xml" || is_svg(&data) {
+                    let opts = usvg_options(None, &Rejected::default());
+                    return usvg::Tree::from_data(&data, &opts).ok().map(usvg::ImageKind::SVG);
+                }
+                match embedded_raster(data, &cfg) {
+                    Ok(kind) => kind,
+                    Err(e) => {
+                        rejected.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(e);
+                        None
+                    }
+                }
+            })
+        }
+    };
+    usvg::Options {
+        image_href_resolver: usvg::ImageHrefResolver {
+            resolve_data,
+            resolve_string: Box::new(|_, _| None),
+        },
+        fontdb: fontdb(),
+        ..Default::default()
+    }
+}
+
+/// 解析 SVG 时被拒绝的内嵌图片错误（只保留第一个）。
+type Rejected = Arc<Mutex<Option<AppError>>>;
+
+/// 校验内嵌的栅格图：读文件头校验像素预算，再带内存上限完整解码一次，通过后交给 resvg 渲染。
+fn embedded_raster(data: Arc<Vec<u8>>, cfg: &ImageConfig) -> Result<Option<usvg::ImageKind>, AppError> {
+    let reader = || ImageReader::new(Cursor::new(data.as_slice())).with_guessed_format();
+    let kind: fn(Arc<Vec<u8>>) -> usvg::ImageKind = match reader()?.format() {
+        Some(ImageFormat::Png) => usvg::ImageKind::PNG,
+        Some(ImageFormat::Jpeg) => usvg::ImageKind::JPEG,
+        Some(ImageFormat::Gif) => usvg::ImageKind::GIF,
+        Some(ImageFormat::WebP) => usvg::ImageKind::WEBP,
+        _ => return Ok(None),
+    };
+    let (w, h) = reader()?
+        .into_dimensions()
+        .map_err(|e| AppError::bad_request(format!("SVG 内嵌图片解析失败: {e}")))?;
+    cfg.check_pixels(w, h, 1)?;
+    let mut reader = reader()?;
+    reader.limits(cfg.decode_limits());
+    reader.decode().map_err(|e| cfg.decode_error("SVG 内嵌图片解析失败", e))?;
+    Ok(Some(kind(data)))
+}
+
+/// 用 resvg 将 SVG 渲染为 RGBA 图片。`<image>` 只允许 data URI，不读取本地文件；
+/// 内嵌图片超出像素预算时返回 413。
+pub(super) fn rasterize(
+    bytes: &[u8],
+    opts: &SvgOptions,
+    cfg: &ImageConfig,
+) -> Result<DynamicImage, AppError> {
+    let rejected = Rejected::default();
+    let tree = usvg::Tree::from_data(bytes, &usvg_options(Some(cfg), &rejected))
+        .map_err(|e| AppError::bad_request(format!("SVG 解析失败: {e}")))?;
+    if let Some(e) = rejected.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        return Err(e);
+    }
+
+    let size = tree.size();
+    let (sw, sh) = (size.width(), size.height());
+    let scale = opts.dpi.unwrap_or(96.0).clamp(1.0, 2400.0) / 96.0;
+    let (w, h) = match (opts.width, opts.height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, (w as f32 * sh / sw).round() as u32),
+        (None, Some(h)) => ((h as f32 * sw / sh).round() as u32, h),
+        (None, None) => ((sw * scale).round() as u32, (sh * scale).round() as u32),
+    };
+    let (w, h) = (w.max(1), h.max(1));
+    if w > MAX_SVG_SIDE || h > MAX_SVG_SIDE {
+        return Err(AppError::bad_request(format!(
+            "光栅化尺寸 {w}x{h} 过大，单边最大 {MAX_SVG_SIDE}"
+        )));
+    }
+    cfg.check_pixels(w, h, 1)?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(w, h)
+        .ok_or_else(|| anyhow::anyhow!("创建 {w}x{h} 画布失败"))?;
+    let transform = tiny_skia::Transform::from_scale(w as f32 / sw, h as f32 / sh);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    Ok(DynamicImage::ImageRgba8(to_rgba(&pixmap)?))
+}
+
+/// 渲染单行文字并裁到字形边界；`width` 指定时按该宽度等比缩放字号。
+/// 字体优先用内置 Tuffy，缺字（如中文）时回退到系统字体。
+pub(crate) fn render_text(
+    text: &str,
+    font_size: f32,
+    color: Rgba<u8>,
+    width: Option<u32>,
+) -> Result<RgbaImage, AppError> {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let [r, g, b, _] = color.0;
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1\" height=\"1\">\
+         <text font-family=\"{EMBEDDED_FONT_FAMILY}, sans-serif\" font-size=\"{font_size}\" \
+         fill=\"#{r:02x}{g:02x}{b:02x}\" xml:space=\"preserve\">{escaped}</text></svg>"
+    );
+    let tree = usvg::Tree::from_data(svg.as_bytes(), &usvg_options(None, &Rejected::default()))
+        .map_err(|e| anyhow::anyhow!("文字渲染失败: {e}"))?;
+
+    let bbox = tree.root().abs_bounding_box();
+    if bbox.width() <= 0.0 || bbox.height() <= 0.0 {
+        return Err(AppError::bad_request("水印文字没有可渲染的字形"));
+    }
+    let k = width.map_or(1.0, |w| w as f32 / bbox.width());
+    let (w, h) = (
+        (bbox.width() * k).ceil() as u32,
+        (bbox.height() * k).ceil() as u32,
+    );
+    if w > MAX_SVG_SIDE || h > MAX_SVG_SIDE {
+        return Err(AppError::bad_request(format!("水印文字尺寸 {w}x{h} 过大")));
+    }
+    let mut pixmap = tiny_skia::Pixmap::new(w.max(1), h.max(1))
+        .ok_or_else(|| anyhow::anyhow!("创建 {w}x{h} 画布失败"))?;
+    let transform = tiny_skia::Transform::from_scale(k, k).pre_translate(-bbox.x(), -bbox.y());
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    to_rgba(&pixmap)
+}
+
+/// tiny-skia 使用预乘 alpha，转回直通 alpha 的 RGBA 图。
+fn to_rgba(pixmap: &tiny_skia::Pixmap) -> Result<RgbaImage, AppError> {
+    let raw: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(pixmap.width(), pixmap.height(), raw)
+        .ok_or_else(|| anyhow::anyhow!("SVG 像素数据异常").into())
+}
+
+/// 解析 `embed` 参数：内嵌格式只允许 PNG / JPEG / WEBP，默认 PNG。
+pub(super) fn embed_format(opts: Option<&SvgOptions>) -> Result<OutputFormat, AppError> {
+    match opts.and_then(|o| o.embed.as_deref()).map(parse_format) {
+        None => Ok(OutputFormat::PNG),
+        Some(Some(f @ (OutputFormat::PNG | OutputFormat::JPEG | OutputFormat::WEBP))) => Ok(f),
+        Some(_) => Err(AppError::bad_request("svg.embed 须为 PNG / JPEG / WEBP")),
+    }
+}
+
+/// 将栅格图编码后以 data URI 内嵌进 SVG 的 `<image>` 元素。
+pub(super) fn embed(
+    img: &DynamicImage,
+    inner: OutputFormat,
+    params: EncodeParams<'_>,
+) -> anyhow::Result<Vec<u8>> {
+    let data = encode_with(img, inner, params)?;
+    let b64 = base64::engine::general_purpose::STANDARD.encode(data);
+    let (w, h) = (img.width(), img.height());
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <image width=\"{w}\" height=\"{h}\" xlink:href=\"data:{};base64,{b64}\"/></svg>\n",
+        inner.mime()
+    );
+    Ok(svg.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::image::tests::config;
+
+    fn with_image(side: u32) -> Vec<u8> {
+        let img = DynamicImage::new_luma8(side, side);
+        embed(&img, OutputFormat::PNG, EncodeParams::default()).unwrap()
+    }
+
+    #[test]
+    fn embedded_images_are_held_to_the_pixel_budget() {
+        let cfg = config(10_000);
+        let opts = SvgOptions { width: Some(10), height: Some(10), ..Default::default() };
+        assert!(rasterize(&with_image(50), &opts, &cfg).is_ok());
+        let err = rasterize(&with_image(200), &opts, &cfg).err().unwrap();
+        assert_eq!(err.status, axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
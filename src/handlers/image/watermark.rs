@@ -0,0 +1,161 @@
+use image::imageops::FilterType;
+use image::{GenericImage, RgbaImage};
+use serde::Deserialize;
+
+use super::{load_image, parse_hex_color, svg, ImageConfig};
+use crate::handlers::error::AppError;
+
+/// 文字水印的最大字符数。
+const MAX_TEXT_CHARS: usize = 200;
+/// 水印边距 / 平铺间距上限（像素）。
+const MAX_MARGIN: u32 = 10_000;
+
+/// 九宫格方位，用于水印位置（以及按方位裁剪）。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gravity {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl Gravity {
+    /// 在 `outer` 内按方位放置 `inner` 时的左上角坐标，贴边一侧保留 `margin`。
+    /// `inner` 大于 `outer` 时坐标可能为负。
+    pub(super) fn place(self, outer: (u32, u32), inner: (u32, u32), margin: u32) -> (i64, i64) {
+        let axis = |o: u32, i: u32, start: bool, end: bool| -> i64 {
+            let (o, i, m) = (i64::from(o), i64::from(i), i64::from(margin));
+            match (start, end) {
+                (true, false) => m,
+                (false, true) => o - i - m,
+                _ => (o - i) / 2,
+            }
+        };
+        use Gravity::*;
+        let left = matches!(self, Left | TopLeft | BottomLeft);
+        let right = matches!(self, Right | TopRight | BottomRight);
+        let top = matches!(self, Top | TopLeft | TopRight);
+        let bottom = matches!(self, Bottom | BottomLeft | BottomRight);
+        (
+            axis(outer.0, inner.0, left, right),
+            axis(outer.1, inner.1, top, bottom),
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WatermarkOptions {
+    /// 文字水印内容；不填时使用上传的 `watermark` 图片
+    pub text: Option<String>,
+    pub font_size: f32,
+    /// 文字颜色（#rrggbb）
+    pub color: String,
+    pub gravity: Gravity,
+    /// 与边缘的距离；平铺时为水印之间的间距
+    pub margin: u32,
+    /// 不透明度（0–1）
+    pub opacity: f32,
+    /// 水印宽度占底图宽度的比例（0–1），不填则使用原图尺寸 / 字号
+    pub scale: Option<f32>,
+    /// 平铺整张图
+    pub tile: bool,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        Self {
+            text: None,
+            font_size: 32.0,
+            color: "#ffffff".into(),
+            gravity: Gravity::BottomRight,
+            margin: 16,
+            opacity: 0.5,
+            scale: None,
+            tile: false,
+        }
+    }
+}
+
+/// 生成水印图（已乘上不透明度）。`base_width` 为底图宽度，用于 `scale`。
+pub(super) fn prepare(
+    opts: &WatermarkOptions,
+    logo: Option<&[u8]>,
+    base_width: u32,
+    cfg: &ImageConfig,
+) -> Result<RgbaImage, AppError> {
+    if !(0.0..=1.0).contains(&opts.opacity) {
+        return Err(AppError::bad_request("watermark.opacity 须在 0–1 之间"));
+    }
+    if opts.margin > MAX_MARGIN {
+        return Err(AppError::bad_request(format!("watermark.margin 不能超过 {MAX_MARGIN}")));
+    }
+    if opts.scale.is_some_and(|s| !(s > 0.0 && s <= 1.0)) {
+        return Err(AppError::bad_request("watermark.scale 须在 (0, 1] 之间"));
+    }
+    let width = opts
+        .scale
+        .map(|s| ((base_width as f32 * s).round() as u32).max(1));
+
+    let mut mark = match (opts.text.as_deref().filter(|t| !t.trim().is_empty()), logo) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::bad_request("水印文字与水印图片只能二选一"));
+        }
+        (Some(text), None) => {
+            if text.chars().count() > MAX_TEXT_CHARS {
+                return Err(AppError::bad_request(format!(
+                    "水印文字过长，最多 {MAX_TEXT_CHARS} 个字符"
+                )));
+            }
+            if !(1.0..=1000.0).contains(&opts.font_size) {
+                return Err(AppError::bad_request("watermark.font_size 须在 1–1000 之间"));
+            }
+            let color = parse_hex_color(&opts.color)
+                .ok_or_else(|| AppError::bad_request("watermark.color 须为 #rrggbb 格式"))?;
+            svg::render_text(text, opts.font_size, color, width)?
+        }
+        (None, Some(bytes)) => {
+            let img = load_image(bytes, &Default::default(), cfg)?;
+            match width {
+                Some(w) => {
+                    let h = ((img.height() as f64 * w as f64 / img.width() as f64).round() as u32).max(1);
+                    img.resize_exact(w, h, FilterType::Lanczos3).to_rgba8()
+                }
+                None => img.to_rgba8(),
+            }
+        }
+        (None, None) => {
+            return Err(AppError::bad_request("水印需要 text 或上传 watermark 图片"));
+        }
+    };
+
+    for p in mark.pixels_mut() {
+        p[3] = (f32::from(p[3]) * opts.opacity).round() as u8;
+    }
+    Ok(mark)
+}
+
+/// 将水印按方位（或平铺）叠加到底图上；底图与水印的像素类型须一致（8 位或 16 位 RGBA）。
+pub(super) fn apply<I: GenericImage>(base: &mut I, mark: &I, opts: &WatermarkOptions) {
+    let (bw, bh) = base.dimensions();
+    let (mw, mh) = mark.dimensions();
+    if opts.tile {
+        let step = |side: u32| (u64::from(side) + u64::from(opts.margin)) as usize;
+        let (step_x, step_y) = (step(mw), step(mh));
+        for y in (0..bh).step_by(step_y.max(1)) {
+            for x in (0..bw).step_by(step_x.max(1)) {
+                image::imageops::overlay(base, mark, i64::from(x), i64::from(y));
+            }
+        }
+    } else {
+        let (x, y) = opts.gravity.place((bw, bh), (mw, mh), opts.margin);
+        image::imageops::overlay(base, mark, x, y);
+    }
+}
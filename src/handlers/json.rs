@@ -453,7 +453,7 @@ fn run_jq(json: &str, expr: &str) -> Result<String, AppError> {
     use jaq_core::{data, unwrap_valr, Compiler, Ctx, Vars};
     use jaq_json::{read, Val};
 
-    let input = read::parse_single(&json.as_bytes())
+    let input = read::parse_single(json.as_bytes())
         .map_err(|e| AppError::bad_request(format!("JSON 解析错误: {e:?}")))?;
 
     let program = File {
@@ -2,3 +2,4 @@ pub mod dns;
 pub mod error;
 pub mod image;
 pub mod json;
+pub mod qrcode;
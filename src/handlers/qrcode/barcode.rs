@@ -0,0 +1,456 @@
+//! 一维条码：Code128 与 EAN-13 的编码（生成模块序列）和按扫描行游程解码。
+
+/// Code128 符号宽度表（条-空-条-空-条-空），下标即码值；106 为终止符（后跟 2 宽的终止条）。
+const CODE128: [[u8; 6]; 107] = [
+    [2, 1, 2, 2, 2, 2], [2, 2, 2, 1, 2, 2], [2, 2, 2, 2, 2, 1], [1, 2, 1, 2, 2, 3], [1, 2, 1, 3, 2, 2],
+    [1, 3, 1, 2, 2, 2], [1, 2, 2, 2, 1, 3], [1, 2, 2, 3, 1, 2], [1, 3, 2, 2, 1, 2], [2, 2, 1, 2, 1, 3],
+    [2, 2, 1, 3, 1, 2], [2, 3, 1, 2, 1, 2], [1, 1, 2, 2, 3, 2], [1, 2, 2, 1, 3, 2], [1, 2, 2, 2, 3, 1],
+    [1, 1, 3, 2, 2, 2], [1, 2, 3, 1, 2, 2], [1, 2, 3, 2, 2, 1], [2, 2, 3, 2, 1, 1], [2, 2, 1, 1, 3, 2],
+    [2, 2, 1, 2, 3, 1], [2, 1, 3, 2, 1, 2], [2, 2, 3, 1, 1, 2], [3, 1, 2, 1, 3, 1], [3, 1, 1, 2, 2, 2],
+    [3, 2, 1, 1, 2, 2], [3, 2, 1, 2, 2, 1], [3, 1, 2, 2, 1, 2], [3, 2, 2, 1, 1, 2], [3, 2, 2, 2, 1, 1],
+    [2, 1, 2, 1, 2, 3], [2, 1, 2, 3, 2, 1], [2, 3, 2, 1, 2, 1], [1, 1, 1, 3, 2, 3], [1, 3, 1, 1, 2, 3],
+    [1, 3, 1, 3, 2, 1], [1, 1, 2, 3, 1, 3], [1, 3, 2, 1, 1, 3], [1, 3, 2, 3, 1, 1], [2, 1, 1, 3, 1, 3],
+    [2, 3, 1, 1, 1, 3], [2, 3, 1, 3, 1, 1], [1, 1, 2, 1, 3, 3], [1, 1, 2, 3, 3, 1], [1, 3, 2, 1, 3, 1],
+    [1, 1, 3, 1, 2, 3], [1, 1, 3, 3, 2, 1], [1, 3, 3, 1, 2, 1], [3, 1, 3, 1, 2, 1], [2, 1, 1, 3, 3, 1],
+    [2, 3, 1, 1, 3, 1], [2, 1, 3, 1, 1, 3], [2, 1, 3, 3, 1, 1], [2, 1, 3, 1, 3, 1], [3, 1, 1, 1, 2, 3],
+    [3, 1, 1, 3, 2, 1], [3, 3, 1, 1, 2, 1], [3, 1, 2, 1, 1, 3], [3, 1, 2, 3, 1, 1], [3, 3, 2, 1, 1, 1],
+    [3, 1, 4, 1, 1, 1], [2, 2, 1, 4, 1, 1], [4, 3, 1, 1, 1, 1], [1, 1, 1, 2, 2, 4], [1, 1, 1, 4, 2, 2],
+    [1, 2, 1, 1, 2, 4], [1, 2, 1, 4, 2, 1], [1, 4, 1, 1, 2, 2], [1, 4, 1, 2, 2, 1], [1, 1, 2, 2, 1, 4],
+    [1, 1, 2, 4, 1, 2], [1, 2, 2, 1, 1, 4], [1, 2, 2, 4, 1, 1], [1, 4, 2, 1, 1, 2], [1, 4, 2, 2, 1, 1],
+    [2, 4, 1, 2, 1, 1], [2, 2, 1, 1, 1, 4], [4, 1, 3, 1, 1, 1], [2, 4, 1, 1, 1, 2], [1, 3, 4, 1, 1, 1],
+    [1, 1, 1, 2, 4, 2], [1, 2, 1, 1, 4, 2], [1, 2, 1, 2, 4, 1], [1, 1, 4, 2, 1, 2], [1, 2, 4, 1, 1, 2],
+    [1, 2, 4, 2, 1, 1], [4, 1, 1, 2, 1, 2], [4, 2, 1, 1, 1, 2], [4, 2, 1, 2, 1, 1], [2, 1, 2, 1, 4, 1],
+    [2, 1, 4, 1, 2, 1], [4, 1, 2, 1, 2, 1], [1, 1, 1, 1, 4, 3], [1, 1, 1, 3, 4, 1], [1, 3, 1, 1, 4, 1],
+    [1, 1, 4, 1, 1, 3], [1, 1, 4, 3, 1, 1], [4, 1, 1, 1, 1, 3], [4, 1, 1, 3, 1, 1], [1, 1, 3, 1, 4, 1],
+    [1, 1, 4, 1, 3, 1], [3, 1, 1, 1, 4, 1], [4, 1, 1, 1, 3, 1], [2, 1, 1, 4, 1, 2], [2, 1, 1, 2, 1, 4],
+    [2, 1, 1, 2, 3, 2], [2, 3, 3, 1, 1, 1],
+];
+
+const START_A: usize = 103;
+const START_B: usize = 104;
+const START_C: usize = 105;
+const STOP: usize = 106;
+const CODE_C: usize = 99;
+const CODE_B: usize = 100;
+const CODE_A: usize = 101;
+const FNC1: usize = 102;
+const SHIFT: usize = 98;
+
+/// EAN 数字的 L 码宽度（空-条-空-条）；R 码宽度相同但以条开头，G 码为其逆序。
+const EAN_L: [[u8; 4]; 10] = [
+    [3, 2, 1, 1], [2, 2, 2, 1], [2, 1, 2, 2], [1, 4, 1, 1], [1, 1, 3, 2],
+    [1, 2, 3, 1], [1, 1, 1, 4], [1, 3, 1, 2], [1, 2, 1, 3], [3, 1, 1, 2],
+];
+
+/// 首位数字决定左侧 6 位的奇偶（L / G）组合，true 表示 G。
+const EAN_PARITY: [[bool; 6]; 10] = {
+    const L: bool = false;
+    const G: bool = true;
+    [
+        [L, L, L, L, L, L], [L, L, G, L, G, G], [L, L, G, G, L, G], [L, L, G, G, G, L], [L, G, L, L, G, G],
+        [L, G, G, L, L, G], [L, G, G, G, L, L], [L, G, L, G, L, G], [L, G, L, G, G, L], [L, G, G, L, G, L],
+    ]
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Symbology {
+    Code128,
+    Ean13,
+}
+
+impl Symbology {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Code128 => "code128",
+            Self::Ean13 => "ean13",
+        }
+    }
+}
+
+/// 按宽度序列（条开头）展开为模块序列，true 为条。
+fn push_widths(out: &mut Vec<bool>, widths: &[u8], bar_first: bool) {
+    let mut bar = bar_first;
+    for &w in widths {
+        out.extend(std::iter::repeat_n(bar, w as usize));
+        bar = !bar;
+    }
+}
+
+/// Code128 编码：连续 4 位以上数字切到 C 集，控制字符用 A 集，其余用 B 集。
+pub(super) fn encode_code128(text: &str) -> Result<Vec<bool>, String> {
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        return Err("内容不能为空".into());
+    }
+    if let Some(c) = text.chars().find(|c| !c.is_ascii()) {
+        return Err(format!("Code128 只支持 ASCII 字符，不支持 '{c}'"));
+    }
+    let digit_run = |i: usize| bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+
+    let mut codes = Vec::new();
+    let mut set = if digit_run(0) >= 4 && digit_run(0).is_multiple_of(2) {
+        START_C
+    } else if bytes[0] < 32 {
+        START_A
+    } else {
+        START_B
+    };
+    codes.push(set);
+    let mut i = 0;
+    while i < bytes.len() {
+        if set == START_C {
+            if digit_run(i) >= 2 {
+                codes.push(((bytes[i] - b'0') * 10 + (bytes[i + 1] - b'0')) as usize);
+                i += 2;
+                continue;
+            }
+            set = if bytes[i] < 32 { START_A } else { START_B };
+            codes.push(if set == START_A { CODE_A } else { CODE_B });
+            continue;
+        }
+        let run = digit_run(i);
+        if run >= 4 && run.is_multiple_of(2) {
+            set = START_C;
+            codes.push(CODE_C);
+            continue;
+        }
+        let b = bytes[i];
+        match set {
+            START_A if b >= 96 => {
+                set = START_B;
+                codes.push(CODE_B);
+                continue;
+            }
+            START_B if b < 32 => {
+                set = START_A;
+                codes.push(CODE_A);
+                continue;
+            }
+            _ => {}
+        }
+        codes.push(if b < 32 { b as usize + 64 } else { b as usize - 32 });
+        i += 1;
+    }
+    let checksum = codes
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| i.max(1) * c)
+        .sum::<usize>()
+        % 103;
+    codes.push(checksum);
+    codes.push(STOP);
+
+    let mut out = Vec::new();
+    for &c in &codes {
+        push_widths(&mut out, &CODE128[c], true);
+    }
+    push_widths(&mut out, &[2], true);
+    Ok(out)
+}
+
+/// EAN-13 编码：接受 12 位（自动补校验位）或 13 位（校验位须正确）数字。
+pub(super) fn encode_ean13(text: &str) -> Result<(Vec<bool>, String), String> {
+    let text = text.trim();
+    if !text.bytes().all(|b| b.is_ascii_digit()) || !(12..=13).contains(&text.len()) {
+        return Err("EAN-13 须为 12 或 13 位数字".into());
+    }
+    let digits: Vec<u8> = text.bytes().map(|b| b - b'0').collect();
+    let check = ean_check_digit(&digits[..12]);
+    if digits.len() == 13 && digits[12] != check {
+        return Err(format!("EAN-13 校验位错误，应为 {check}"));
+    }
+    let mut digits = digits[..12].to_vec();
+    digits.push(check);
+
+    let mut out = Vec::new();
+    push_widths(&mut out, &[1, 1, 1], true);
+    for (i, &d) in digits[1..7].iter().enumerate() {
+        let mut w = EAN_L[d as usize];
+        if EAN_PARITY[digits[0] as usize][i] {
+            w.reverse();
+        }
+        push_widths(&mut out, &w, false);
+    }
+    push_widths(&mut out, &[1, 1, 1, 1, 1], false);
+    for &d in &digits[7..] {
+        push_widths(&mut out, &EAN_L[d as usize], true);
+    }
+    push_widths(&mut out, &[1, 1, 1], true);
+    let text = digits.iter().map(|d| char::from(b'0' + d)).collect();
+    Ok((out, text))
+}
+
+fn ean_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| u32::from(d) * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// 一行上识别到的条码：内容及起止游程在该行中的像素位置。
+pub(super) struct Scanned {
+    pub kind: Symbology,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 解码一行游程（首个游程为浅色，可能为 0）；返回该行识别到的全部条码。
+pub(super) fn scan_row(runs: &[usize]) -> Vec<Scanned> {
+    let offsets: Vec<usize> = runs
+        .iter()
+        .scan(0, |acc, &r| {
+            let start = *acc;
+            *acc += r;
+            Some(start)
+        })
+        .collect();
+    let widths: Vec<f64> = runs.iter().map(|&r| r as f64).collect();
+    let mut out = Vec::new();
+    let mut i = 1;
+    while i < widths.len() {
+        let found = decode_ean13(&widths, i)
+            .map(|(text, n)| (Symbology::Ean13, text, n))
+            .or_else(|| decode_code128(&widths, i).map(|(text, n)| (Symbology::Code128, text, n)));
+        if let Some((kind, text, n)) = found {
+            let last = i + n - 1;
+            out.push(Scanned { kind, text, start: offsets[i], end: offsets[last] + runs[last] });
+            // 跳过尾部空白，下一个候选仍从条开始
+            i += n + 1;
+        } else {
+            i += 2;
+        }
+    }
+    out
+}
+
+/// 窗口内宽度与模式的偏差（按模块宽归一化后的绝对差之和）。
+fn mismatch(widths: &[f64], pattern: &[u8]) -> f64 {
+    let total: f64 = widths.iter().sum();
+    let modules: u32 = pattern.iter().map(|&p| u32::from(p)).sum();
+    let unit = total / f64::from(modules);
+    widths
+        .iter()
+        .zip(pattern)
+        .map(|(w, &p)| (w / unit - f64::from(p)).abs())
+        .sum()
+}
+
+/// 起始位置前须有至少 `modules` 个模块宽的空白（静区）。
+fn has_quiet_zone(widths: &[f64], start: usize, unit: f64, modules: f64) -> bool {
+    start == 0 || widths[start - 1] >= unit * modules
+}
+
+/// 从 `start`（条）开始解 EAN-13，返回内容与消耗的游程数。
+fn decode_ean13(widths: &[f64], start: usize) -> Option<(String, usize)> {
+    const RUNS: usize = 3 + 24 + 5 + 24 + 3;
+    let w = widths.get(start..start + RUNS)?;
+    let unit = w.iter().sum::<f64>() / 95.0;
+    if !has_quiet_zone(widths, start, unit, 5.0) || mismatch(&w[..3], &[1, 1, 1]) > 1.2 {
+        return None;
+    }
+    if mismatch(&w[27..32], &[1, 1, 1, 1, 1]) > 1.5 || mismatch(&w[56..59], &[1, 1, 1]) > 1.2 {
+        return None;
+    }
+    let best = |chunk: &[f64], candidates: &mut dyn Iterator<Item = (u8, bool, [u8; 4])>| {
+        candidates
+            .map(|(d, g, p)| (mismatch(chunk, &p), d, g))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .filter(|(m, _, _)| *m < 1.5)
+    };
+
+    let mut digits = Vec::with_capacity(13);
+    let mut parity = [false; 6];
+    for (k, p) in parity.iter_mut().enumerate() {
+        let chunk = &w[3 + k * 4..7 + k * 4];
+        let mut candidates = (0..10u8).flat_map(|d| {
+            let l = EAN_L[d as usize];
+            let mut g = l;
+            g.reverse();
+            [(d, false, l), (d, true, g)]
+        });
+        let (_, d, g) = best(chunk, &mut candidates)?;
+        digits.push(d);
+        *p = g;
+    }
+    for k in 0..6 {
+        let chunk = &w[32 + k * 4..36 + k * 4];
+        let mut candidates = (0..10u8).map(|d| (d, false, EAN_L[d as usize]));
+        digits.push(best(chunk, &mut candidates)?.1);
+    }
+    let first = EAN_PARITY.iter().position(|p| *p == parity)? as u8;
+    digits.insert(0, first);
+    if ean_check_digit(&digits[..12]) != digits[12] {
+        return None;
+    }
+    Some((digits.iter().map(|d| char::from(b'0' + d)).collect(), RUNS))
+}
+
+/// 从 `start`（条）开始解 Code128，返回内容与消耗的游程数。
+fn decode_code128(widths: &[f64], start: usize) -> Option<(String, usize)> {
+    let symbol = |at: usize| -> Option<usize> {
+        let w = widths.get(at..at + 6)?;
+        (0..CODE128.len())
+            .map(|c| (mismatch(w, &CODE128[c]), c))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .filter(|(m, _)| *m < 1.6)
+            .map(|(_, c)| c)
+    };
+    let first = symbol(start)?;
+    if !(START_A..=START_C).contains(&first) {
+        return None;
+    }
+    let unit = widths[start..start + 6].iter().sum::<f64>() / 11.0;
+    if !has_quiet_zone(widths, start, unit, 5.0) {
+        return None;
+    }
+
+    let mut codes = vec![first];
+    let mut at = start + 6;
+    loop {
+        let c = symbol(at)?;
+        at += 6;
+        if c == STOP {
+            // 终止符后还有一根 2 模块宽的终止条
+            widths.get(at)?;
+            at += 1;
+            break;
+        }
+        if codes.len() > 200 || c > FNC1 {
+            return None;
+        }
+        codes.push(c);
+    }
+    let (&checksum, data) = codes.split_last()?;
+    if data.len() < 2 {
+        return None;
+    }
+    let expected = data.iter().enumerate().map(|(i, &c)| i.max(1) * c).sum::<usize>() % 103;
+    if expected != checksum {
+        return None;
+    }
+    Some((code128_text(data), at - start))
+}
+
+/// 按码集状态机把码值还原为文本；FNC1 转为 GS（首位 FNC1 表示 GS1 数据，忽略）。
+fn code128_text(codes: &[usize]) -> String {
+    let mut set = codes[0];
+    let mut shift = false;
+    let mut out = String::new();
+    for (i, &c) in codes.iter().enumerate().skip(1) {
+        let current = match (shift, set) {
+            (true, START_A) => START_B,
+            (true, START_B) => START_A,
+            (_, s) => s,
+        };
+        shift = false;
+        if c == FNC1 {
+            if i > 1 {
+                out.push('\u{1d}');
+            }
+            continue;
+        }
+        match current {
+            START_C => match c {
+                0..=99 => out.push_str(&format!("{c:02}")),
+                CODE_B => set = START_B,
+                CODE_A => set = START_A,
+                _ => {}
+            },
+            _ => match c {
+                0..=95 => {
+                    let b = if current == START_A && c >= 64 { c - 64 } else { c + 32 };
+                    out.push(char::from(b as u8));
+                }
+                SHIFT => shift = true,
+                CODE_C => set = START_C,
+                CODE_B if current == START_A => set = START_B,
+                CODE_A if current == START_B => set = START_A,
+                // FNC2 / FNC3 / FNC4 不影响文本
+                _ => {}
+            },
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 模块序列转为一行游程：两侧各 10 模块静区，每模块 `scale` 像素。
+    fn to_runs(bars: &[bool], scale: usize) -> Vec<usize> {
+        let mut runs = vec![10 * scale];
+        let mut dark = false;
+        for &bar in bars {
+            if bar != dark {
+                runs.push(0);
+                dark = bar;
+            }
+            *runs.last_mut().unwrap() += scale;
+        }
+        runs.push(10 * scale);
+        runs
+    }
+
+    fn scan(runs: &[usize]) -> Vec<(Symbology, String)> {
+        scan_row(runs).into_iter().map(|s| (s.kind, s.text)).collect()
+    }
+
+    #[test]
+    fn code128_round_trip() {
+        for text in ["Hello, World!", "1234567890", "abc12345678xyz", "TAB\tEND", "A"] {
+            let bars = encode_code128(text).unwrap();
+            for scale in 1..=3 {
+                assert_eq!(scan(&to_runs(&bars, scale)), [(Symbology::Code128, text.to_string())], "{text:?} x{scale}");
+            }
+        }
+    }
+
+    #[test]
+    fn code128_rejects_bad_checksum() {
+        let bars = encode_code128("CHECK").unwrap();
+        let mut runs = to_runs(&bars, 2);
+        // 把第二个符号（'C'）换成 'D'，校验和不再匹配
+        let d = CODE128[usize::from(b'D' - 32)];
+        for (i, &w) in d.iter().enumerate() {
+            runs[7 + i] = usize::from(w) * 2;
+        }
+        assert!(scan(&runs).is_empty());
+    }
+
+    #[test]
+    fn ean13_round_trip() {
+        let (bars, text) = encode_ean13("400638133393").unwrap();
+        assert_eq!(text, "4006381333931");
+        assert_eq!(bars.len(), 95);
+        for scale in 1..=3 {
+            assert_eq!(scan(&to_runs(&bars, scale)), [(Symbology::Ean13, text.clone())]);
+        }
+        // 13 位输入原样接受
+        let (again, _) = encode_ean13("4006381333931").unwrap();
+        assert_eq!(again, bars);
+    }
+
+    #[test]
+    fn ean13_check_digit() {
+        assert_eq!(ean_check_digit(&[4, 0, 0, 6, 3, 8, 1, 3, 3, 3, 9, 3]), 1);
+        assert_eq!(ean_check_digit(&[9, 7, 8, 0, 3, 0, 6, 4, 0, 6, 1, 5]), 7);
+        assert!(encode_ean13("4006381333932").unwrap_err().contains("应为 1"));
+        assert!(encode_ean13("40063813339").is_err());
+        assert!(encode_ean13("40063813339x").is_err());
+    }
+
+    #[test]
+    fn ean13_scan_rejects_bad_check_digit() {
+        let (bars, _) = encode_ean13("400638133393").unwrap();
+        let mut runs = to_runs(&bars, 2);
+        // 右半区最后一位（校验位 1）改写为 2
+        for (i, &w) in EAN_L[2].iter().enumerate() {
+            runs[1 + 52 + i] = usize::from(w) * 2;
+        }
+        assert!(!scan(&runs).iter().any(|(kind, _)| *kind == Symbology::Ean13));
+    }
+}
@@ -0,0 +1,580 @@
+//! 在图片中定位 QR 码：二值化、查找定位图案、组合三元组、按透视变换采样出模块矩阵。
+
+use image::GrayImage;
+
+use super::qr::{self, BitMatrix, QrContent};
+
+/// 参与组合的定位图案候选上限。
+const MAX_FINDERS: usize = 40;
+
+/// 二值化后的图片，true 为深色。
+pub(super) struct Binary {
+    pub width: usize,
+    pub height: usize,
+    bits: Vec<bool>,
+}
+
+impl Binary {
+    pub fn get(&self, x: i64, y: i64) -> Option<bool> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(self.bits[y as usize * self.width + x as usize])
+    }
+
+    /// 全局 Otsu 阈值，适合截图等光照均匀的图片。
+    pub fn otsu(gray: &GrayImage) -> Self {
+        let mut hist = [0u64; 256];
+        for p in gray.pixels() {
+            hist[p[0] as usize] += 1;
+        }
+        let total = gray.pixels().len() as f64;
+        let sum_all: f64 = hist.iter().enumerate().map(|(i, &c)| i as f64 * c as f64).sum();
+        let (mut sum_b, mut weight_b) = (0.0, 0.0);
+        let (mut best, mut threshold) = (0.0, 127u8);
+        for (t, &count) in hist.iter().enumerate() {
+            weight_b += count as f64;
+            if weight_b == 0.0 {
+                continue;
+            }
+            let weight_f = total - weight_b;
+            if weight_f == 0.0 {
+                break;
+            }
+            sum_b += t as f64 * count as f64;
+            let (mean_b, mean_f) = (sum_b / weight_b, (sum_all - sum_b) / weight_f);
+            let between = weight_b * weight_f * (mean_b - mean_f).powi(2);
+            if between > best {
+                best = between;
+                threshold = t as u8;
+            }
+        }
+        Self {
+            width: gray.width() as usize,
+            height: gray.height() as usize,
+            bits: gray.pixels().map(|p| p[0] <= threshold).collect(),
+        }
+    }
+
+    /// 局部均值阈值，应对拍照等光照不均的图片。
+    pub fn adaptive(gray: &GrayImage) -> Self {
+        let (w, h) = (gray.width() as usize, gray.height() as usize);
+        let mut integral = vec![0u64; (w + 1) * (h + 1)];
+        for y in 0..h {
+            let mut row = 0u64;
+            for x in 0..w {
+                row += u64::from(gray.get_pixel(x as u32, y as u32)[0]);
+                integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row;
+            }
+        }
+        let r = (w.max(h) / 16).max(8);
+        let mut bits = Vec::with_capacity(w * h);
+        for y in 0..h {
+            let (y0, y1) = (y.saturating_sub(r), (y + r + 1).min(h));
+            for x in 0..w {
+                let (x0, x1) = (x.saturating_sub(r), (x + r + 1).min(w));
+                let sum = integral[y1 * (w + 1) + x1] + integral[y0 * (w + 1) + x0]
+                    - integral[y0 * (w + 1) + x1]
+                    - integral[y1 * (w + 1) + x0];
+                let count = ((x1 - x0) * (y1 - y0)) as u64;
+                let v = u64::from(gray.get_pixel(x as u32, y as u32)[0]);
+                // 比局部均值暗 8% 以上视为深色
+                bits.push(v * count * 100 < sum * 92);
+            }
+        }
+        Self { width: w, height: h, bits }
+    }
+
+    pub fn inverted(&self) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            bits: self.bits.iter().map(|b| !b).collect(),
+        }
+    }
+
+    /// 第 `y` 行的游程长度，首个游程为浅色（可能为 0）。
+    pub fn row_runs(&self, y: usize) -> Vec<usize> {
+        let row = &self.bits[y * self.width..(y + 1) * self.width];
+        runs(row.iter().copied())
+    }
+
+    /// 第 `x` 列的游程长度，首个游程为浅色（可能为 0）。
+    pub fn column_runs(&self, x: usize) -> Vec<usize> {
+        runs((0..self.height).map(|y| self.bits[y * self.width + x]))
+    }
+}
+
+/// 计算游程长度，首个游程约定为浅色。
+fn runs(bits: impl Iterator<Item = bool>) -> Vec<usize> {
+    let mut out = vec![0usize];
+    let mut dark = false;
+    for bit in bits {
+        if bit != dark {
+            out.push(0);
+            dark = bit;
+        }
+        *out.last_mut().unwrap() += 1;
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl Point {
+    fn dist(self, o: Point) -> f64 {
+        ((self.x - o.x).powi(2) + (self.y - o.y).powi(2)).sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finder {
+    center: Point,
+    module: f64,
+    count: u32,
+}
+
+/// 识别出的 QR 码及其四角坐标（左上、右上、右下、左下）。
+pub(super) struct Located {
+    pub content: QrContent,
+    pub corners: [[f64; 2]; 4],
+}
+
+/// 查找并解码图中所有 QR 码。
+pub(super) fn find_qr_codes(b: &Binary) -> Vec<Located> {
+    let finders = find_finders(b);
+    let mut triples = Vec::new();
+    for i in 0..finders.len() {
+        for j in i + 1..finders.len() {
+            for k in j + 1..finders.len() {
+                if let Some(t) = triple(&finders, [i, j, k]) {
+                    triples.push(t);
+                }
+            }
+        }
+    }
+    triples.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+    let mut used = vec![false; finders.len()];
+    let mut out = Vec::new();
+    for t in triples {
+        if t.ids.iter().any(|&i| used[i]) {
+            continue;
+        }
+        if let Some(found) = decode_triple(b, &t) {
+            t.ids.iter().for_each(|&i| used[i] = true);
+            out.push(found);
+        }
+    }
+    out
+}
+
+/// 1:1:3:1:1 比例校验。
+fn ratio_ok(c: &[f64; 5]) -> bool {
+    let total: f64 = c.iter().sum();
+    if total < 7.0 {
+        return false;
+    }
+    let m = total / 7.0;
+    let v = m / 2.0;
+    (m - c[0]).abs() < v
+        && (m - c[1]).abs() < v
+        && (3.0 * m - c[2]).abs() < 3.0 * v
+        && (m - c[3]).abs() < v
+        && (m - c[4]).abs() < v
+}
+
+/// 沿方向 (dx, dy) 穿过 (cx, cy) 统计五段游程，返回游程与中心相对 (cx, cy) 的偏移步数。
+fn cross_check(b: &Binary, cx: i64, cy: i64, dx: i64, dy: i64, max: f64) -> Option<([f64; 5], f64)> {
+    if b.get(cx, cy) != Some(true) {
+        return None;
+    }
+    let mut c = [0f64; 5];
+    let at = |i: i64| b.get(cx + i * dx, cy + i * dy);
+
+    let mut i = 0;
+    while at(-i) == Some(true) {
+        c[2] += 1.0;
+        i += 1;
+    }
+    for (idx, want) in [(1, false), (0, true)] {
+        while at(-i) == Some(want) {
+            c[idx] += 1.0;
+            i += 1;
+            if c[idx] > max {
+                return None;
+            }
+        }
+    }
+    let mut j = 1;
+    while at(j) == Some(true) {
+        c[2] += 1.0;
+        j += 1;
+    }
+    for (idx, want) in [(3, false), (4, true)] {
+        while at(j) == Some(want) {
+            c[idx] += 1.0;
+            j += 1;
+            if c[idx] > max {
+                return None;
+            }
+        }
+    }
+    if c.contains(&0.0) || !ratio_ok(&c) {
+        return None;
+    }
+    Some((c, j as f64 - c[4] - c[3] - c[2] / 2.0))
+}
+
+/// 逐行扫描 1:1:3:1:1 图案，纵横交叉确认后合并相近的候选。
+fn find_finders(b: &Binary) -> Vec<Finder> {
+    let step = if b.height > 1200 { 2 } else { 1 };
+    let mut found: Vec<Finder> = Vec::new();
+    for y in (0..b.height).step_by(step) {
+        let runs = b.row_runs(y);
+        // runs[0] 为浅色，奇数下标为深色
+        let mut start = runs[0];
+        for i in 1..runs.len() {
+            if i % 2 == 1 && i + 4 < runs.len() {
+                let c = [runs[i], runs[i + 1], runs[i + 2], runs[i + 3], runs[i + 4]].map(|v| v as f64);
+                if ratio_ok(&c) {
+                    let total: f64 = c.iter().sum();
+                    let offset = runs[i] + runs[i + 1];
+                    let cx = (start + offset) as f64 + c[2] / 2.0;
+                    if let Some(f) = confirm(b, cx, y as f64, total) {
+                        merge(&mut found, f);
+                    }
+                }
+            }
+            start += runs[i];
+        }
+    }
+    let confirmed = found.iter().filter(|f| f.count >= 2).count();
+    if confirmed >= 3 {
+        found.retain(|f| f.count >= 2);
+    }
+    found.sort_by_key(|f| std::cmp::Reverse(f.count));
+    found.truncate(MAX_FINDERS);
+    found
+}
+
+/// 纵向、横向、对角三次交叉确认，返回精确中心与模块尺寸。
+fn confirm(b: &Binary, cx: f64, cy: f64, total: f64) -> Option<Finder> {
+    let max = total;
+    let (vc, dy) = cross_check(b, cx as i64, cy as i64, 0, 1, max)?;
+    let v_total: f64 = vc.iter().sum();
+    if 5.0 * (v_total - total).abs() >= 2.0 * total {
+        return None;
+    }
+    let cy = (cy as i64) as f64 + dy;
+    let (hc, dx) = cross_check(b, cx as i64, cy as i64, 1, 0, max)?;
+    let h_total: f64 = hc.iter().sum();
+    let cx = (cx as i64) as f64 + dx;
+    cross_check(b, cx as i64, cy as i64, 1, 1, max * 1.5)?;
+    Some(Finder {
+        center: Point { x: cx, y: cy },
+        module: (v_total + h_total) / 14.0,
+        count: 1,
+    })
+}
+
+fn merge(found: &mut Vec<Finder>, f: Finder) {
+    for e in found.iter_mut() {
+        if (e.center.x - f.center.x).abs() <= e.module
+            && (e.center.y - f.center.y).abs() <= e.module
+            && (e.module - f.module).abs() <= e.module.max(1.0)
+        {
+            let n = f64::from(e.count);
+            e.center.x = (e.center.x * n + f.center.x) / (n + 1.0);
+            e.center.y = (e.center.y * n + f.center.y) / (n + 1.0);
+            e.module = (e.module * n + f.module) / (n + 1.0);
+            e.count += 1;
+            return;
+        }
+    }
+    found.push(f);
+}
+
+struct Triple {
+    ids: [usize; 3],
+    top_left: Point,
+    top_right: Point,
+    bottom_left: Point,
+    module: f64,
+    score: f64,
+}
+
+/// 三个定位图案是否构成近似等腰直角三角形；按叉积确定右上与左下。
+fn triple(finders: &[Finder], ids: [usize; 3]) -> Option<Triple> {
+    let f = ids.map(|i| finders[i]);
+    let modules = f.map(|x| x.module);
+    let (min, max) = modules.iter().fold((f64::MAX, 0f64), |(lo, hi), &m| (lo.min(m), hi.max(m)));
+    if max > min * 1.5 {
+        return None;
+    }
+    let module = modules.iter().sum::<f64>() / 3.0;
+
+    // 直角顶点为最长边所对的点
+    let sides = [
+        f[1].center.dist(f[2].center),
+        f[0].center.dist(f[2].center),
+        f[0].center.dist(f[1].center),
+    ];
+    let corner = (0..3).max_by(|&a, &b| sides[a].total_cmp(&sides[b]))?;
+    let (p, q) = ((corner + 1) % 3, (corner + 2) % 3);
+    let (a, b, c) = (sides[p], sides[q], sides[corner]);
+    if a.min(b) < 10.0 * module {
+        return None;
+    }
+    let legs = (a - b).abs() / a.max(b);
+    let hyp = (c - (a * a + b * b).sqrt()).abs() / c;
+    if legs > 0.25 || hyp > 0.15 {
+        return None;
+    }
+
+    let tl = f[corner].center;
+    let (mut tr, mut bl) = (f[p].center, f[q].center);
+    let cross = (tr.x - tl.x) * (bl.y - tl.y) - (tr.y - tl.y) * (bl.x - tl.x);
+    if cross < 0.0 {
+        std::mem::swap(&mut tr, &mut bl);
+    }
+    Some(Triple {
+        ids,
+        top_left: tl,
+        top_right: tr,
+        bottom_left: bl,
+        module,
+        score: legs + hyp,
+    })
+}
+
+fn decode_triple(b: &Binary, t: &Triple) -> Option<Located> {
+    // 横纵游程在旋转时会偏长，改沿两定位图案连线方向测量模块尺寸
+    let along = |a: Point, c: Point| {
+        let pair = (finder_width(b, a, c, t.module), finder_width(b, c, a, t.module));
+        match pair {
+            (Some(x), Some(y)) => (x + y) / 14.0,
+            _ => t.module,
+        }
+    };
+    let (du, dv) = (t.top_left.dist(t.top_right), t.top_left.dist(t.bottom_left));
+    let est = (du / along(t.top_left, t.top_right) + dv / along(t.top_left, t.bottom_left)) / 2.0 + 7.0;
+    let base = (((est - 1.0) / 4.0).round() as i64) * 4 + 1;
+    for dim in [base, base + 4, base - 4] {
+        if !(21..=177).contains(&dim) {
+            continue;
+        }
+        let dim = dim as f64;
+        let span = dim - 7.0;
+        let u = ((t.top_right.x - t.top_left.x) / span, (t.top_right.y - t.top_left.y) / span);
+        let v = ((t.bottom_left.x - t.top_left.x) / span, (t.bottom_left.y - t.top_left.y) / span);
+        let affine = |mx: f64, my: f64| Point {
+            x: t.top_left.x + (mx - 3.5) * u.0 + (my - 3.5) * v.0,
+            y: t.top_left.y + (mx - 3.5) * u.1 + (my - 3.5) * v.1,
+        };
+
+        let src = [(3.5, 3.5), (dim - 3.5, 3.5), (3.5, dim - 3.5)];
+        let dst = [t.top_left, t.top_right, t.bottom_left];
+        let mut fourths = Vec::new();
+        if dim > 21.0 {
+            if let Some(p) = find_alignment(b, affine(dim - 6.5, dim - 6.5), u, v) {
+                fourths.push(((dim - 6.5, dim - 6.5), p));
+            }
+        }
+        fourths.push(((dim - 3.5, dim - 3.5), affine(dim - 3.5, dim - 3.5)));
+
+        for (s4, d4) in fourths {
+            let h = Homography::solve(
+                [src[0], src[1], src[2], s4],
+                [dst[0], dst[1], dst[2], d4].map(|p| (p.x, p.y)),
+            )?;
+            let size = dim as usize;
+            let mut matrix = BitMatrix::new(size);
+            for my in 0..size {
+                for mx in 0..size {
+                    let (x, y) = h.map(mx as f64 + 0.5, my as f64 + 0.5);
+                    let dark = b.get(x.floor() as i64, y.floor() as i64).unwrap_or(false);
+                    matrix.set(mx, my, dark);
+                }
+            }
+            if let Some(content) = qr::decode(&matrix) {
+                let corners = [(0.0, 0.0), (dim, 0.0), (dim, dim), (0.0, dim)].map(|(x, y)| {
+                    let (x, y) = h.map(x, y);
+                    [x, y]
+                });
+                return Some(Located { content, corners });
+            }
+        }
+    }
+    None
+}
+
+/// 从定位图案中心 `from` 朝 `to` 方向、及其反方向穿过 深-浅-深 三段到达外沿，
+/// 返回两侧距离之和（即 7 个模块的长度）。
+fn finder_width(b: &Binary, from: Point, to: Point, module: f64) -> Option<f64> {
+    let len = from.dist(to);
+    let dir = ((to.x - from.x) / len, (to.y - from.y) / len);
+    let limit = (module * 8.0).ceil() as usize;
+    let reach = |sign: f64| {
+        let mut state = 0;
+        for i in 0..limit {
+            let x = from.x + sign * dir.0 * i as f64;
+            let y = from.y + sign * dir.1 * i as f64;
+            let dark = b.get(x.floor() as i64, y.floor() as i64)?;
+            match (state, dark) {
+                (0, false) | (1, true) => state += 1,
+                (2, false) => return Some(i as f64),
+                _ => {}
+            }
+        }
+        None
+    };
+    Some(reach(1.0)? + reach(-1.0)?)
+}
+
+/// 在预估位置附近搜索右下对齐图案（5×5：深-浅-深同心方框），返回最佳中心。
+/// 透视较强时仿射预估偏差较大，搜索半径按 4 / 8 / 16 个模块逐步放大。
+fn find_alignment(b: &Binary, est: Point, u: (f64, f64), v: (f64, f64)) -> Option<Point> {
+    let module = (u.0.hypot(u.1) + v.0.hypot(v.1)) / 2.0;
+    let step = ((module / 3.0).floor() as usize).max(1);
+    let score_at = |c: Point| {
+        let mut score = 0;
+        for a in -2i32..=2 {
+            for bb in -2i32..=2 {
+                let want = a.abs().max(bb.abs()) != 1;
+                let x = c.x + f64::from(a) * u.0 + f64::from(bb) * v.0;
+                let y = c.y + f64::from(a) * u.1 + f64::from(bb) * v.1;
+                if b.get(x.floor() as i64, y.floor() as i64) == Some(want) {
+                    score += 1;
+                }
+            }
+        }
+        score
+    };
+    for allowance in [4.0, 8.0, 16.0] {
+        let radius = (module * allowance).ceil() as i64;
+        let mut best: Option<(u32, f64, Point)> = None;
+        for oy in (-radius..=radius).step_by(step) {
+            for ox in (-radius..=radius).step_by(step) {
+                let c = Point { x: est.x + ox as f64, y: est.y + oy as f64 };
+                let score = score_at(c);
+                let dist = c.dist(est);
+                if best.is_none_or(|(s, d, _)| score > s || (score == s && dist < d)) {
+                    best = Some((score, dist, c));
+                }
+            }
+        }
+        if let Some((_, _, p)) = best.filter(|(s, _, _)| *s >= 23) {
+            return Some(p);
+        }
+    }
+    None
+}
+
+/// 四点透视变换（模块坐标 → 像素坐标）。
+struct Homography([f64; 8]);
+
+impl Homography {
+    fn solve(src: [(f64, f64); 4], dst: [(f64, f64); 4]) -> Option<Self> {
+        let mut m = [[0f64; 9]; 8];
+        for i in 0..4 {
+            let ((x, y), (tx, ty)) = (src[i], dst[i]);
+            m[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -x * tx, -y * tx, tx];
+            m[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -x * ty, -y * ty, ty];
+        }
+        for col in 0..8 {
+            let pivot = (col..8).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+            if m[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(col, pivot);
+            let pivot_row = m[col];
+            for (r, row) in m.iter_mut().enumerate() {
+                if r != col {
+                    let factor = row[col] / pivot_row[col];
+                    for (v, p) in row.iter_mut().zip(pivot_row).skip(col) {
+                        *v -= factor * p;
+                    }
+                }
+            }
+        }
+        let mut h = [0f64; 8];
+        for (i, v) in h.iter_mut().enumerate() {
+            *v = m[i][8] / m[i][i];
+        }
+        Some(Self(h))
+    }
+
+    fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        let h = &self.0;
+        let w = h[6] * x + h[7] * y + 1.0;
+        ((h[0] * x + h[1] * y + h[2]) / w, (h[3] * x + h[4] * y + h[5]) / w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+    use qrcode::{Color, EcLevel, QrCode};
+
+    /// 按 `scale` 像素/模块渲染并绕中心旋转 `degrees`，四周留 4 模块空白。
+    fn render(code: &QrCode, scale: f64, degrees: f64) -> GrayImage {
+        let modules = code.width();
+        let colors = code.to_colors();
+        let half = (modules as f64 / 2.0 + 4.0) * scale * std::f64::consts::SQRT_2;
+        let side = (half * 2.0).ceil() as u32;
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        GrayImage::from_fn(side, side, |px, py| {
+            // 反向旋转到模块坐标，最近邻采样
+            let (dx, dy) = (f64::from(px) + 0.5 - half, f64::from(py) + 0.5 - half);
+            let x = (dx * cos + dy * sin) / scale + modules as f64 / 2.0;
+            let y = (-dx * sin + dy * cos) / scale + modules as f64 / 2.0;
+            let dark = x >= 0.0
+                && y >= 0.0
+                && (x as usize) < modules
+                && (y as usize) < modules
+                && colors[y as usize * modules + x as usize] == Color::Dark;
+            Luma([if dark { 0 } else { 255 }])
+        })
+    }
+
+    fn decode_all(img: &GrayImage) -> Vec<String> {
+        find_qr_codes(&Binary::otsu(img)).into_iter().map(|l| l.content.text).collect()
+    }
+
+    #[test]
+    fn finds_codes_at_every_ec_level() {
+        let text = "https://github.com/zzhtl/dev-tools";
+        for level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+            let code = QrCode::with_error_correction_level(text, level).unwrap();
+            assert_eq!(decode_all(&render(&code, 4.0, 0.0)), [text], "{level:?}");
+        }
+    }
+
+    #[test]
+    fn finds_rotated_and_scaled_codes() {
+        let text = "旋转与缩放 0123456789";
+        let code = QrCode::with_error_correction_level(text, EcLevel::M).unwrap();
+        for scale in [3.0, 4.5, 8.0] {
+            for degrees in [0.0, 15.0, 45.0, 90.0, 180.0, 300.0] {
+                assert_eq!(decode_all(&render(&code, scale, degrees)), [text], "scale={scale} rotate={degrees}");
+            }
+        }
+    }
+
+    #[test]
+    fn reports_corners_in_reading_order() {
+        let code = QrCode::with_error_correction_level("corners", EcLevel::L).unwrap();
+        let found = find_qr_codes(&Binary::otsu(&render(&code, 5.0, 90.0)));
+        let [tl, tr, br, bl] = found[0].corners;
+        // 顺时针旋转 90° 后：左上角在图片右上、右上角在右下、左下角在左上
+        assert!(tl[0] > bl[0] && (tl[1] - bl[1]).abs() < 5.0);
+        assert!(tr[1] > tl[1] && (tr[0] - tl[0]).abs() < 5.0);
+        assert!(br[0] < tr[0] && br[1] > bl[1]);
+    }
+}
@@ -0,0 +1,459 @@
+use axum::extract::{Multipart, State};
+use axum::response::Response;
+use axum::Json;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::handlers::error::AppError;
+use crate::handlers::image::{
+    attachment, encode, load_image, parse_format, parse_hex_color, read_upload, render_text, ImageConfig,
+    OutputFormat, SvgOptions,
+};
+
+mod barcode;
+mod detect;
+mod qr;
+mod reed_solomon;
+
+use detect::Binary;
+
+/// 生成内容的长度上限（字符）。
+const MAX_TEXT_LEN: usize = 4096;
+/// 每模块像素数上限。
+const MAX_SCALE: u32 = 64;
+/// 小于该边长的图片解码前先放大，便于定位细小的模块。
+const MIN_DECODE_SIDE: u32 = 400;
+
+/// 码制：QR 码或一维条码。
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeKind {
+    #[default]
+    Qr,
+    Code128,
+    Ean13,
+}
+
+/// QR 纠错等级，可恢复约 7% / 15% / 25% / 30% 的损坏。
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum EcLevel {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl From<EcLevel> for qrcode::EcLevel {
+    fn from(level: EcLevel) -> Self {
+        match level {
+            EcLevel::L => qrcode::EcLevel::L,
+            EcLevel::M => qrcode::EcLevel::M,
+            EcLevel::Q => qrcode::EcLevel::Q,
+            EcLevel::H => qrcode::EcLevel::H,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct GenerateOptions {
+    pub text: String,
+    pub kind: CodeKind,
+    /// QR 纠错等级，默认 M；上传 logo 时默认 H
+    pub ec_level: Option<EcLevel>,
+    /// 静区宽度（模块数），QR 默认 4，条码默认 10
+    pub margin: Option<u32>,
+    /// 每模块像素数，QR 默认 8，条码默认 2
+    pub scale: Option<u32>,
+    /// 前景色 / 背景色（#rrggbb）
+    pub foreground: String,
+    pub background: String,
+    /// 条码的条高（像素），默认 60 倍模块宽
+    pub height: Option<u32>,
+    /// logo 宽度占 QR 码边长的比例，最大 0.3
+    pub logo_scale: f32,
+    /// 条码下方是否显示文字
+    pub show_text: bool,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            kind: CodeKind::Qr,
+            ec_level: None,
+            margin: None,
+            scale: None,
+            foreground: "#000000".into(),
+            background: "#ffffff".into(),
+            height: None,
+            logo_scale: 0.2,
+            show_text: true,
+        }
+    }
+}
+
+/// 渲染结果及附加响应头。
+struct Rendered {
+    image: RgbaImage,
+    headers: Vec<(&'static str, String)>,
+}
+
+/// 生成 QR 码 / Code128 / EAN-13，字段：`options`（JSON）、`format`（PNG / SVG 等，默认 PNG）、
+/// 可选的 `logo`（QR 中心图标）。
+pub async fn generate(State(cfg): State<ImageConfig>, mut mp: Multipart) -> Result<Response, AppError> {
+    let mut options = GenerateOptions::default();
+    let mut format = OutputFormat::PNG;
+    let mut logo = None;
+
+    while let Some(field) = mp.next_field().await.map_err(AppError::multipart)? {
+        match field.name().unwrap_or("") {
+            "options" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.is_empty() {
+                    options = serde_json::from_str(&text)
+                        .map_err(|e| AppError::bad_request(format!("options 解析失败: {e}")))?;
+                }
+            }
+            "format" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("读取 format 字段失败: {e}"))?;
+                format = match parse_format(&text) {
+                    Some(OutputFormat::ICO) | None => {
+                        return Err(AppError::bad_request("format 须为 PNG / JPEG / GIF / WEBP / BMP / SVG"));
+                    }
+                    Some(f) => f,
+                };
+            }
+            "logo" => logo = Some(read_upload(field, &cfg).await?),
+            _ => {}
+        }
+    }
+
+    let out = cfg
+        .run(move |cfg| {
+            let Rendered { image, mut headers } = render(&options, logo.as_deref(), cfg)?;
+            headers.push(("X-Width", image.width().to_string()));
+            headers.push(("X-Height", image.height().to_string()));
+            let bytes = encode(&DynamicImage::ImageRgba8(image), format, None)?;
+            Ok((bytes, headers, options.kind))
+        })
+        .await?;
+    let (bytes, headers, kind) = out;
+    let name = match kind {
+        CodeKind::Qr => "qrcode",
+        CodeKind::Code128 => "code128",
+        CodeKind::Ean13 => "ean13",
+    };
+    Ok(attachment(bytes, format.mime(), &format!("{name}.{}", format.extension()), headers))
+}
+
+fn render(opts: &GenerateOptions, logo: Option<&[u8]>, cfg: &ImageConfig) -> Result<Rendered, AppError> {
+    if opts.text.is_empty() {
+        return Err(AppError::bad_request("options.text 不能为空"));
+    }
+    if opts.text.chars().count() > MAX_TEXT_LEN {
+        return Err(AppError::bad_request(format!("options.text 过长，最多 {MAX_TEXT_LEN} 个字符")));
+    }
+    if opts.scale.is_some_and(|s| s == 0 || s > MAX_SCALE) {
+        return Err(AppError::bad_request(format!("options.scale 须在 1 ~ {MAX_SCALE} 之间")));
+    }
+    if opts.margin.is_some_and(|m| m > 100) {
+        return Err(AppError::bad_request("options.margin 不能超过 100"));
+    }
+    let fg = parse_hex_color(&opts.foreground)
+        .ok_or_else(|| AppError::bad_request("options.foreground 须为 #rrggbb 格式"))?;
+    let bg = parse_hex_color(&opts.background)
+        .ok_or_else(|| AppError::bad_request("options.background 须为 #rrggbb 格式"))?;
+
+    match opts.kind {
+        CodeKind::Qr => render_qr(opts, logo, fg, bg, cfg),
+        CodeKind::Code128 | CodeKind::Ean13 => {
+            if logo.is_some() {
+                return Err(AppError::bad_request("logo 仅支持 QR 码"));
+            }
+            render_barcode(opts, fg, bg, cfg)
+        }
+    }
+}
+
+fn render_qr(
+    opts: &GenerateOptions,
+    logo: Option<&[u8]>,
+    fg: Rgba<u8>,
+    bg: Rgba<u8>,
+    cfg: &ImageConfig,
+) -> Result<Rendered, AppError> {
+    let level = opts
+        .ec_level
+        .unwrap_or(if logo.is_some() { EcLevel::H } else { EcLevel::M });
+    let code = qrcode::QrCode::with_error_correction_level(opts.text.as_bytes(), level.into()).map_err(|e| match e {
+        qrcode::types::QrError::DataTooLong => {
+            AppError::bad_request(format!("内容过长，超出 QR 码在纠错等级 {level:?} 下的容量"))
+        }
+        e => AppError::bad_request(format!("生成 QR 码失败: {e}")),
+    })?;
+    let modules = code.width() as u32;
+    let (scale, margin) = (opts.scale.unwrap_or(8), opts.margin.unwrap_or(4));
+    let side = (modules + margin * 2) * scale;
+    cfg.check_pixels(side, side, 1)?;
+
+    let colors = code.to_colors();
+    let mut image = RgbaImage::from_pixel(side, side, bg);
+    for (i, _) in colors.iter().enumerate().filter(|(_, c)| **c == qrcode::Color::Dark) {
+        let (mx, my) = (i as u32 % modules, i as u32 / modules);
+        let (x0, y0) = ((mx + margin) * scale, (my + margin) * scale);
+        for y in y0..y0 + scale {
+            for x in x0..x0 + scale {
+                image.put_pixel(x, y, fg);
+            }
+        }
+    }
+
+    if let Some(bytes) = logo {
+        if !(opts.logo_scale > 0.0 && opts.logo_scale <= 0.3) {
+            return Err(AppError::bad_request("options.logo_scale 须在 (0, 0.3] 之间"));
+        }
+        let logo = load_image(bytes, &SvgOptions::default(), cfg)?;
+        let target = ((modules * scale) as f32 * opts.logo_scale).round().max(1.0) as u32;
+        let logo = logo.resize(target, target, FilterType::Lanczos3).to_rgba8();
+        // logo 四周留一个模块的背景色衬底，避免与码点粘连
+        let (pw, ph) = (logo.width() + scale * 2, logo.height() + scale * 2);
+        let pad = RgbaImage::from_pixel(pw, ph, bg);
+        let (px, py) = (i64::from((side - pw) / 2), i64::from((side - ph) / 2));
+        image::imageops::overlay(&mut image, &pad, px, py);
+        image::imageops::overlay(&mut image, &logo, px + i64::from(scale), py + i64::from(scale));
+    }
+
+    let version = match code.version() {
+        qrcode::Version::Normal(v) | qrcode::Version::Micro(v) => v,
+    };
+    Ok(Rendered {
+        image,
+        headers: vec![("X-Version", version.to_string()), ("X-Ec-Level", format!("{level:?}"))],
+    })
+}
+
+fn render_barcode(opts: &GenerateOptions, fg: Rgba<u8>, bg: Rgba<u8>, cfg: &ImageConfig) -> Result<Rendered, AppError> {
+    let (bars, label) = match opts.kind {
+        CodeKind::Ean13 => barcode::encode_ean13(&opts.text).map_err(AppError::bad_request)?,
+        _ => (
+            barcode::encode_code128(&opts.text).map_err(AppError::bad_request)?,
+            opts.text.clone(),
+        ),
+    };
+    let (scale, margin) = (opts.scale.unwrap_or(2), opts.margin.unwrap_or(10));
+    let bar_height = opts.height.unwrap_or(scale * 60);
+    if bar_height == 0 || bar_height > 4096 {
+        return Err(AppError::bad_request("options.height 须在 1 ~ 4096 之间"));
+    }
+    let width = (bars.len() as u32 + margin * 2) * scale;
+    cfg.check_pixels(width, bar_height, 1)?;
+
+    let text = if opts.show_text {
+        let printable: String = label.chars().filter(|c| !c.is_control()).collect();
+        Some(render_text(&printable, (scale * 10) as f32, fg, None)?)
+    } else {
+        None
+    };
+    let gap = scale * 4;
+    let height = bar_height + text.as_ref().map_or(0, |t| t.height() + gap * 2);
+    cfg.check_pixels(width, height, 1)?;
+
+    let mut image = RgbaImage::from_pixel(width, height, bg);
+    for (i, _) in bars.iter().enumerate().filter(|(_, b)| **b) {
+        let x0 = (i as u32 + margin) * scale;
+        for y in 0..bar_height {
+            for x in x0..x0 + scale {
+                image.put_pixel(x, y, fg);
+            }
+        }
+    }
+    if let Some(text) = text {
+        let x = (i64::from(width) - i64::from(text.width())) / 2;
+        image::imageops::overlay(&mut image, &text, x, i64::from(bar_height + gap));
+    }
+    let headers = match opts.kind {
+        // EAN-13 回传补全校验位后的 13 位数字
+        CodeKind::Ean13 => vec![("X-Code-Text", label)],
+        _ => Vec::new(),
+    };
+    Ok(Rendered { image, headers })
+}
+
+#[derive(Serialize)]
+pub struct DecodeResponse {
+    pub width: u32,
+    pub height: u32,
+    pub results: Vec<DecodedCode>,
+}
+
+#[derive(Serialize)]
+pub struct DecodedCode {
+    /// qr / code128 / ean13
+    pub kind: &'static str,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ec_level: Option<&'static str>,
+    /// 纠正的码字数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corrected: Option<usize>,
+    /// QR 码为四角（左上、右上、右下、左下），条码为扫描线的起止点
+    pub points: Vec<[f64; 2]>,
+}
+
+/// 识别上传图片（`file` 字段）中的 QR 码与 Code128 / EAN-13 条码。
+pub async fn decode(State(cfg): State<ImageConfig>, mut mp: Multipart) -> Result<Json<DecodeResponse>, AppError> {
+    let mut bytes = None;
+    while let Some(field) = mp.next_field().await.map_err(AppError::multipart)? {
+        if field.name() == Some("file") {
+            bytes = Some(read_upload(field, &cfg).await?);
+        }
+    }
+    let bytes = bytes.ok_or_else(|| AppError::bad_request("缺少 file 字段"))?;
+
+    let out = cfg
+        .run(move |cfg| {
+            let img = load_image(&bytes, &SvgOptions::default(), cfg)?;
+            let (width, height) = img.dimensions();
+            Ok(DecodeResponse { width, height, results: scan(&img, cfg.max_pixels) })
+        })
+        .await?;
+    Ok(Json(out))
+}
+
+/// 依次尝试全局 / 局部阈值及反色（深色背景上的浅色码），找到 QR 码即停；条码逐行逐列扫描。
+fn scan(img: &DynamicImage, max_pixels: u64) -> Vec<DecodedCode> {
+    let (gray, factor) = grayscale(img, max_pixels);
+    let otsu = Binary::otsu(&gray);
+    let adaptive = Binary::adaptive(&gray);
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    let scale_point = |[x, y]: [f64; 2]| [round(x / factor), round(y / factor)];
+
+    let mut results = Vec::new();
+    let mut seen = HashSet::new();
+    for binary in [&otsu, &adaptive] {
+        for inverted in [false, true] {
+            let found = if inverted {
+                detect::find_qr_codes(&binary.inverted())
+            } else {
+                detect::find_qr_codes(binary)
+            };
+            for code in found {
+                if seen.insert(("qr", code.content.text.clone())) {
+                    results.push(DecodedCode {
+                        kind: "qr",
+                        text: code.content.text,
+                        version: Some(code.content.version),
+                        ec_level: Some(code.content.ec_level.as_str()),
+                        corrected: Some(code.content.corrected),
+                        points: code.corners.map(scale_point).to_vec(),
+                    });
+                }
+            }
+        }
+        if !results.is_empty() {
+            break;
+        }
+    }
+
+    let before = results.len();
+    for binary in [&otsu, &adaptive] {
+        scan_barcodes(binary, &mut seen, &mut results, &scale_point);
+        if results.len() > before {
+            break;
+        }
+    }
+    results
+}
+
+/// 转为灰度（透明像素按白底合成），过小的图片按整数倍放大（放大后不超过像素预算）；
+/// 返回灰度图与放大倍数。
+fn grayscale(img: &DynamicImage, max_pixels: u64) -> (GrayImage, f64) {
+    let rgba = img.to_rgba8();
+    let mut gray = GrayImage::new(rgba.width(), rgba.height());
+    for (x, y, p) in rgba.enumerate_pixels() {
+        let [r, g, b, a] = p.0.map(u32::from);
+        let luma = (r * 299 + g * 587 + b * 114) / 1000;
+        gray.put_pixel(x, y, Luma([((luma * a + 255 * (255 - a)) / 255) as u8]));
+    }
+    let short = gray.width().min(gray.height()).max(1);
+    let pixels = u64::from(gray.width()) * u64::from(gray.height());
+    let mut factor = MIN_DECODE_SIDE.div_ceil(short).clamp(1, 4);
+    while factor > 1 && pixels * u64::from(factor * factor) > max_pixels {
+        factor -= 1;
+    }
+    if factor > 1 {
+        let (w, h) = (gray.width() * factor, gray.height() * factor);
+        gray = image::imageops::resize(&gray, w, h, FilterType::Nearest);
+    }
+    (gray, f64::from(factor))
+}
+
+/// 按约 64 条水平线和 64 条竖直线正反两个方向扫描一维条码。
+fn scan_barcodes(
+    b: &Binary,
+    seen: &mut HashSet<(&'static str, String)>,
+    results: &mut Vec<DecodedCode>,
+    scale_point: &dyn Fn([f64; 2]) -> [f64; 2],
+) {
+    let lines = [(false, b.height, b.width), (true, b.width, b.height)];
+    for (vertical, count, len) in lines {
+        let step = (count / 64).max(1);
+        for index in (step / 2..count).step_by(step) {
+            let runs = if vertical { b.column_runs(index) } else { b.row_runs(index) };
+            for reversed in [false, true] {
+                let runs = if reversed { reverse_runs(&runs) } else { runs.clone() };
+                for hit in barcode::scan_row(&runs) {
+                    let kind = hit.kind.as_str();
+                    if !seen.insert((kind, hit.text.clone())) {
+                        continue;
+                    }
+                    let (start, end) = if reversed { (len - hit.start, len - hit.end) } else { (hit.start, hit.end) };
+                    let at = index as f64 + 0.5;
+                    let point = |p: usize| if vertical { [at, p as f64] } else { [p as f64, at] };
+                    results.push(DecodedCode {
+                        kind,
+                        text: hit.text,
+                        version: None,
+                        ec_level: None,
+                        corrected: None,
+                        points: vec![scale_point(point(start)), scale_point(point(end))],
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// 反向的游程序列，仍保持首个游程为浅色。
+fn reverse_runs(runs: &[usize]) -> Vec<usize> {
+    let mut out: Vec<usize> = runs.iter().rev().copied().collect();
+    if runs.len().is_multiple_of(2) {
+        out.insert(0, 0);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upscale_stays_within_pixel_budget() {
+        let img = DynamicImage::new_rgba8(1000, 100);
+        let (gray, factor) = grayscale(&img, 100_000_000);
+        assert_eq!((gray.dimensions(), factor), ((4000, 400), 4.0));
+        let (gray, factor) = grayscale(&img, 1_000_000);
+        assert_eq!((gray.dimensions(), factor), ((3000, 300), 3.0));
+        let (gray, factor) = grayscale(&img, 200_000);
+        assert_eq!((gray.dimensions(), factor), ((1000, 100), 1.0));
+    }
+}
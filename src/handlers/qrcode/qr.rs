@@ -0,0 +1,609 @@
+//! 从采样得到的模块矩阵解出 QR 码内容：格式 / 版本信息、去掩码、读码字、纠错与数据段解析。
+
+use super::reed_solomon;
+
+/// 每块纠错码字数，按 [L, M, Q, H][版本] 索引（下标 0 占位）。
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+/// 纠错块数，按 [L, M, Q, H][版本] 索引。
+const NUM_ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// 采样得到的正方形模块矩阵，true 为深色。
+#[derive(Clone)]
+pub(super) struct BitMatrix {
+    pub size: usize,
+    bits: Vec<bool>,
+}
+
+impl BitMatrix {
+    pub fn new(size: usize) -> Self {
+        Self { size, bits: vec![false; size * size] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.bits[y * self.size + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.bits[y * self.size + x] = dark;
+    }
+
+    fn transposed(&self) -> Self {
+        let mut out = Self::new(self.size);
+        for y in 0..self.size {
+            for x in 0..self.size {
+                out.set(y, x, self.get(x, y));
+            }
+        }
+        out
+    }
+}
+
+/// 纠错等级，顺序与码表一致。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EcLevel {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl EcLevel {
+    /// 格式信息中的 2 位编码：L=01, M=00, Q=11, H=10。
+    fn from_bits(bits: u32) -> Self {
+        match bits {
+            1 => Self::L,
+            0 => Self::M,
+            3 => Self::Q,
+            _ => Self::H,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::L => "L",
+            Self::M => "M",
+            Self::Q => "Q",
+            Self::H => "H",
+        }
+    }
+}
+
+pub(super) struct QrContent {
+    pub text: String,
+    pub version: u8,
+    pub ec_level: EcLevel,
+    /// 纠正的码字数
+    pub corrected: usize,
+}
+
+/// 解码模块矩阵；原方向失败时再按镜像（转置）尝试。
+pub(super) fn decode(matrix: &BitMatrix) -> Option<QrContent> {
+    decode_oriented(matrix).or_else(|| decode_oriented(&matrix.transposed()))
+}
+
+fn decode_oriented(m: &BitMatrix) -> Option<QrContent> {
+    let size = m.size;
+    if !(21..=177).contains(&size) || size % 4 != 1 {
+        return None;
+    }
+    let (ec_level, mask) = read_format(m)?;
+    let version = if size >= 45 {
+        read_version(m)?
+    } else {
+        ((size - 17) / 4) as u8
+    };
+    if usize::from(version) * 4 + 17 != size {
+        return None;
+    }
+
+    let function = function_modules(version, size);
+    let raw_len = num_raw_data_modules(version) / 8;
+    let mut raw = vec![0u8; raw_len];
+    let mut i = 0;
+    let mut right = size as isize - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        for vert in 0..size {
+            for j in 0..2 {
+                let x = (right - j) as usize;
+                let upward = ((right + 1) & 2) == 0;
+                let y = if upward { size - 1 - vert } else { vert };
+                if !function[y * size + x] && i < raw_len * 8 {
+                    if m.get(x, y) ^ mask_bit(mask, x, y) {
+                        raw[i >> 3] |= 1 << (7 - (i & 7));
+                    }
+                    i += 1;
+                }
+            }
+        }
+        right -= 2;
+    }
+
+    let (data, corrected) = deinterleave_and_correct(&raw, version, ec_level)?;
+    let text = parse_segments(&data, version)?;
+    Some(QrContent { text, version, ec_level, corrected })
+}
+
+/// 读取两处格式信息，取与合法码字汉明距离最小者（≤3）。
+fn read_format(m: &BitMatrix) -> Option<(EcLevel, u8)> {
+    let size = m.size;
+    let bit = |x: usize, y: usize| u32::from(m.get(x, y));
+    let mut first = 0u32;
+    for i in 0..=5 {
+        first |= bit(8, i) << i;
+    }
+    first |= bit(8, 7) << 6;
+    first |= bit(8, 8) << 7;
+    first |= bit(7, 8) << 8;
+    for i in 9..15 {
+        first |= bit(14 - i, 8) << i;
+    }
+    let mut second = 0u32;
+    for i in 0..8 {
+        second |= bit(size - 1 - i, 8) << i;
+    }
+    for i in 8..15 {
+        second |= bit(8, size - 15 + i) << i;
+    }
+
+    let mut best: Option<(u32, u32)> = None;
+    for data in 0..32u32 {
+        let code = format_code(data);
+        for read in [first, second] {
+            let dist = (code ^ read).count_ones();
+            if best.is_none_or(|(d, _)| dist < d) {
+                best = Some((dist, data));
+            }
+        }
+    }
+    let (dist, data) = best?;
+    (dist <= 3).then(|| (EcLevel::from_bits(data >> 3), (data & 7) as u8))
+}
+
+/// 5 位格式数据 → 带 BCH 校验并异或掩码的 15 位格式信息。
+fn format_code(data: u32) -> u32 {
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    ((data << 10) | rem) ^ 0x5412
+}
+
+/// 版本 ≥ 7 时读取两处版本信息（18 位），取汉明距离最小者（≤3）。
+fn read_version(m: &BitMatrix) -> Option<u8> {
+    let size = m.size;
+    let (mut a, mut b) = (0u32, 0u32);
+    for i in 0..18 {
+        let (p, q) = (size - 11 + i % 3, i / 3);
+        a |= u32::from(m.get(p, q)) << i;
+        b |= u32::from(m.get(q, p)) << i;
+    }
+    let mut best: Option<(u32, u8)> = None;
+    for version in 7..=40u8 {
+        let code = version_code(version);
+        for read in [a, b] {
+            let dist = (code ^ read).count_ones();
+            if best.is_none_or(|(d, _)| dist < d) {
+                best = Some((dist, version));
+            }
+        }
+    }
+    let (dist, version) = best?;
+    (dist <= 3).then_some(version)
+}
+
+fn version_code(version: u8) -> u32 {
+    let data = u32::from(version);
+    let mut rem = data;
+    for _ in 0..12 {
+        rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+    }
+    (data << 12) | rem
+}
+
+fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// 对齐图案中心坐标（每个轴相同）。
+pub(super) fn alignment_positions(version: u8) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let v = usize::from(version);
+    let size = v * 4 + 17;
+    let count = v / 7 + 2;
+    let step = if v == 32 { 26 } else { (v * 4 + count * 2 + 1) / (count * 2 - 2) * 2 };
+    let mut out: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    out.push(6);
+    out.reverse();
+    out
+}
+
+/// 标记功能图形（定位、分隔、格式、时序、对齐、版本信息）所占模块。
+fn function_modules(version: u8, size: usize) -> Vec<bool> {
+    let mut f = vec![false; size * size];
+    let mut fill = |x0: usize, y0: usize, w: usize, h: usize| {
+        for y in y0..(y0 + h).min(size) {
+            for x in x0..(x0 + w).min(size) {
+                f[y * size + x] = true;
+            }
+        }
+    };
+    fill(0, 0, 9, 9);
+    fill(size - 8, 0, 8, 9);
+    fill(0, size - 8, 9, 8);
+    fill(0, 6, size, 1);
+    fill(6, 0, 1, size);
+    let align = alignment_positions(version);
+    let last = align.len().saturating_sub(1);
+    for (i, &ay) in align.iter().enumerate() {
+        for (j, &ax) in align.iter().enumerate() {
+            // 与三个定位图案重叠的位置不放对齐图案
+            if [(0, 0), (0, last), (last, 0)].contains(&(i, j)) {
+                continue;
+            }
+            fill(ax - 2, ay - 2, 5, 5);
+        }
+    }
+    if version >= 7 {
+        fill(size - 11, 0, 3, 6);
+        fill(0, size - 11, 6, 3);
+    }
+    f
+}
+
+fn num_raw_data_modules(version: u8) -> usize {
+    let v = usize::from(version);
+    let mut result = (16 * v + 128) * v + 64;
+    if v >= 2 {
+        let align = v / 7 + 2;
+        result -= (25 * align - 10) * align - 55;
+        if v >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+/// 拆分交织的码字到各纠错块并逐块纠错，返回拼接后的数据码字与纠正数。
+fn deinterleave_and_correct(raw: &[u8], version: u8, ec: EcLevel) -> Option<(Vec<u8>, usize)> {
+    let (v, e) = (usize::from(version), ec as usize);
+    let blocks = usize::from(NUM_ERROR_CORRECTION_BLOCKS[e][v]);
+    let ecc_len = usize::from(ECC_CODEWORDS_PER_BLOCK[e][v]);
+    let short_blocks = blocks - raw.len() % blocks;
+    let short_len = raw.len() / blocks;
+    let data_short = short_len - ecc_len;
+
+    // 短块在数据末尾补一个占位字节，与长块等长后按列交织
+    let mut parts = vec![Vec::with_capacity(short_len + 1); blocks];
+    let mut iter = raw.iter();
+    for i in 0..=short_len {
+        for (j, part) in parts.iter_mut().enumerate() {
+            if i == data_short && j < short_blocks {
+                part.push(0);
+            } else {
+                part.push(*iter.next()?);
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    let mut corrected = 0;
+    for (j, mut part) in parts.into_iter().enumerate() {
+        if j < short_blocks {
+            part.remove(data_short);
+        }
+        corrected += reed_solomon::correct(&mut part, ecc_len)?;
+        data.extend_from_slice(&part[..part.len() - ecc_len]);
+    }
+    Some((data, corrected))
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn available(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, n: usize) -> Option<u32> {
+        if n > self.available() {
+            return None;
+        }
+        let mut v = 0u32;
+        for _ in 0..n {
+            let bit = (self.data[self.pos >> 3] >> (7 - (self.pos & 7))) & 1;
+            v = (v << 1) | u32::from(bit);
+            self.pos += 1;
+        }
+        Some(v)
+    }
+}
+
+/// 字符数指示符位数：按版本区间 1–9 / 10–26 / 27–40。
+fn count_bits(mode: u32, version: u8) -> usize {
+    let idx = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    match mode {
+        0b0001 => [10, 12, 14][idx],
+        0b0010 => [9, 11, 13][idx],
+        0b0100 => [8, 16, 16][idx],
+        _ => [8, 10, 12][idx],
+    }
+}
+
+/// 解析数据段：数字、字母数字、字节、汉字（Shift_JIS）、中文（GB2312）与 ECI。
+fn parse_segments(data: &[u8], version: u8) -> Option<String> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut out = String::new();
+    // 连续的字节段合并后再按字符集解码，避免多字节字符被段边界截断
+    let mut pending: Vec<u8> = Vec::new();
+    let mut eci: Option<u32> = None;
+
+    while r.available() >= 4 {
+        let mode = r.read(4)?;
+        if mode != 0b0100 && !pending.is_empty() {
+            out.push_str(&decode_bytes(&pending, eci));
+            pending.clear();
+        }
+        match mode {
+            0b0000 => break,
+            0b0001 => {
+                let mut n = r.read(count_bits(mode, version))? as usize;
+                while n >= 3 {
+                    let v = r.read(10)?;
+                    if v >= 1000 {
+                        return None;
+                    }
+                    out.push_str(&format!("{v:03}"));
+                    n -= 3;
+                }
+                match n {
+                    2 => out.push_str(&format!("{:02}", r.read(7).filter(|v| *v < 100)?)),
+                    1 => out.push_str(&format!("{}", r.read(4).filter(|v| *v < 10)?)),
+                    _ => {}
+                }
+            }
+            0b0010 => {
+                let mut n = r.read(count_bits(mode, version))? as usize;
+                while n >= 2 {
+                    let v = r.read(11)? as usize;
+                    out.push(char::from(*ALPHANUMERIC.get(v / 45)?));
+                    out.push(char::from(*ALPHANUMERIC.get(v % 45)?));
+                    n -= 2;
+                }
+                if n == 1 {
+                    out.push(char::from(*ALPHANUMERIC.get(r.read(6)? as usize)?));
+                }
+            }
+            0b0100 => {
+                let n = r.read(count_bits(mode, version))? as usize;
+                for _ in 0..n {
+                    pending.push(r.read(8)? as u8);
+                }
+            }
+            0b1000 => {
+                let n = r.read(count_bits(mode, version))? as usize;
+                let mut bytes = Vec::with_capacity(n * 2);
+                for _ in 0..n {
+                    let v = r.read(13)?;
+                    let code = (v / 0xC0) << 8 | (v % 0xC0);
+                    let code = if code < 0x1F00 { code + 0x8140 } else { code + 0xC140 };
+                    bytes.extend_from_slice(&[(code >> 8) as u8, code as u8]);
+                }
+                out.push_str(&encoding_rs::SHIFT_JIS.decode_without_bom_handling(&bytes).0);
+            }
+            0b1101 => {
+                // GB2312 子集指示符 0001 表示汉字
+                let subset = r.read(4)?;
+                let n = r.read(count_bits(0b1000, version))? as usize;
+                let mut bytes = Vec::with_capacity(n * 2);
+                for _ in 0..n {
+                    let v = r.read(13)?;
+                    let code = (v / 0x60) << 8 | (v % 0x60);
+                    let code = if code < 0x0A00 { code + 0xA1A1 } else { code + 0xA6A1 };
+                    bytes.extend_from_slice(&[(code >> 8) as u8, code as u8]);
+                }
+                if subset == 1 {
+                    out.push_str(&encoding_rs::GBK.decode_without_bom_handling(&bytes).0);
+                }
+            }
+            0b0111 => {
+                let first = r.read(8)?;
+                eci = Some(if first & 0x80 == 0 {
+                    first
+                } else if first & 0xC0 == 0x80 {
+                    ((first & 0x3F) << 8) | r.read(8)?
+                } else {
+                    ((first & 0x1F) << 16) | r.read(16)?
+                });
+            }
+            // 结构化追加：跳过序号与奇偶校验
+            0b0011 => {
+                r.read(16)?;
+            }
+            // FNC1（GS1 / AIM）
+            0b0101 => {}
+            0b1001 => {
+                r.read(8)?;
+            }
+            _ => return None,
+        }
+    }
+    if !pending.is_empty() {
+        out.push_str(&decode_bytes(&pending, eci));
+    }
+    Some(out)
+}
+
+/// 字节段解码：有 ECI 时按其字符集；否则依次尝试 UTF-8、GB18030，最后按 ISO-8859-1。
+fn decode_bytes(bytes: &[u8], eci: Option<u32>) -> String {
+    let encoding = match eci {
+        Some(20) => Some(encoding_rs::SHIFT_JIS),
+        Some(26) => Some(encoding_rs::UTF_8),
+        Some(29) => Some(encoding_rs::GB18030),
+        Some(30) => Some(encoding_rs::EUC_KR),
+        Some(24) => Some(encoding_rs::WINDOWS_1256),
+        Some(22) => Some(encoding_rs::WINDOWS_1251),
+        Some(28) => Some(encoding_rs::BIG5),
+        _ => None,
+    };
+    if let Some(encoding) = encoding {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+    let latin1 = || bytes.iter().map(|&b| char::from(b)).collect();
+    if matches!(eci, Some(1 | 3)) {
+        return latin1();
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    let (text, had_errors) = encoding_rs::GB18030.decode_without_bom_handling(bytes);
+    if !had_errors {
+        return text.into_owned();
+    }
+    latin1()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::{Color, QrCode, Version};
+
+    const LEVELS: [(qrcode::EcLevel, EcLevel); 4] = [
+        (qrcode::EcLevel::L, EcLevel::L),
+        (qrcode::EcLevel::M, EcLevel::M),
+        (qrcode::EcLevel::Q, EcLevel::Q),
+        (qrcode::EcLevel::H, EcLevel::H),
+    ];
+
+    fn matrix(code: &QrCode) -> BitMatrix {
+        let size = code.width();
+        let mut m = BitMatrix::new(size);
+        for (i, color) in code.to_colors().into_iter().enumerate() {
+            m.set(i % size, i / size, color == Color::Dark);
+        }
+        m
+    }
+
+    /// 按 `decode_oriented` 的读取顺序列出数据模块坐标。
+    fn data_modules(version: u8, size: usize) -> Vec<(usize, usize)> {
+        let function = function_modules(version, size);
+        let mut out = Vec::new();
+        let mut right = size as isize - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vert in 0..size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let y = if ((right + 1) & 2) == 0 { size - 1 - vert } else { vert };
+                    if !function[y * size + x] {
+                        out.push((x, y));
+                    }
+                }
+            }
+            right -= 2;
+        }
+        out
+    }
+
+    /// 每个纠错块破坏恰好达到纠错能力的码字数（一半在数据区、一半在纠错区），返回破坏总数。
+    fn corrupt(m: &mut BitMatrix, version: u8, ec: EcLevel) -> usize {
+        let (v, e) = (usize::from(version), ec as usize);
+        let blocks = usize::from(NUM_ERROR_CORRECTION_BLOCKS[e][v]);
+        let ecc_len = usize::from(ECC_CODEWORDS_PER_BLOCK[e][v]);
+        let raw_len = num_raw_data_modules(version) / 8;
+        let data_len = raw_len - blocks * ecc_len;
+        let capacity = ecc_len / 2;
+        let modules = data_modules(version, m.size);
+        let mut flip = |codeword: usize| {
+            for &(x, y) in &modules[codeword * 8..codeword * 8 + 8] {
+                m.set(x, y, !m.get(x, y));
+            }
+        };
+        for block in 0..blocks {
+            for i in 0..capacity {
+                if i % 2 == 0 {
+                    flip(i / 2 * blocks + block);
+                } else {
+                    flip(data_len + i / 2 * blocks + block);
+                }
+            }
+        }
+        blocks * capacity
+    }
+
+    #[test]
+    fn decodes_every_ec_level_up_to_capacity() {
+        for (level, expected) in LEVELS {
+            for version in [1, 5, 7, 12] {
+                let text = "DEV-TOOLS";
+                let code = QrCode::with_version(text, Version::Normal(version), level).unwrap();
+                let mut m = matrix(&code);
+                let clean = decode(&m).unwrap();
+                assert_eq!((clean.text.as_str(), clean.corrected), (text, 0));
+
+                let errors = corrupt(&mut m, version as u8, expected);
+                let content = decode(&m).unwrap_or_else(|| panic!("v{version}-{expected:?} 纠错失败"));
+                assert_eq!(content.text, text);
+                assert_eq!(content.version, version as u8);
+                assert_eq!(content.ec_level, expected);
+                assert_eq!(content.corrected, errors);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_mirrored_matrix() {
+        let code = QrCode::with_error_correction_level("https://example.com/镜像", qrcode::EcLevel::M).unwrap();
+        let content = decode(&matrix(&code).transposed()).unwrap();
+        assert_eq!(content.text, "https://example.com/镜像");
+    }
+
+    #[test]
+    fn rejects_corruption_beyond_capacity() {
+        let code = QrCode::with_version("DEV-TOOLS", Version::Normal(1), qrcode::EcLevel::H).unwrap();
+        let mut m = matrix(&code);
+        let modules = data_modules(1, m.size);
+        // v1-H 单块 17 个纠错码字，最多纠正 8 个
+        for codeword in 0..12 {
+            for &(x, y) in &modules[codeword * 8..codeword * 8 + 8] {
+                m.set(x, y, !m.get(x, y));
+            }
+        }
+        assert!(decode(&m).is_none_or(|c| c.text != "DEV-TOOLS"));
+    }
+}
@@ -0,0 +1,191 @@
+//! QR 码使用的 GF(256) Reed–Solomon 纠错（本原多项式 0x11D，生成元根 α^0..α^(n-1)）。
+
+struct Gf {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Gf {
+    const fn new() -> Self {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+        let mut x: u16 = 1;
+        let mut i = 0;
+        while i < 255 {
+            exp[i] = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= 0x11D;
+            }
+            i += 1;
+        }
+        while i < 512 {
+            exp[i] = exp[i - 255];
+            i += 1;
+        }
+        Self { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        debug_assert!(b != 0);
+        if a == 0 {
+            return 0;
+        }
+        self.exp[(self.log[a as usize] as usize + 255 - self.log[b as usize] as usize) % 255]
+    }
+
+    fn pow(&self, a: u8, n: usize) -> u8 {
+        if a == 0 {
+            return 0;
+        }
+        self.exp[(self.log[a as usize] as usize * n) % 255]
+    }
+
+    fn inverse(&self, a: u8) -> u8 {
+        self.exp[255 - self.log[a as usize] as usize]
+    }
+
+    /// 多项式求值，系数按最高次在前排列。
+    fn eval(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().fold(0, |acc, &c| self.mul(acc, x) ^ c)
+    }
+}
+
+static GF: Gf = Gf::new();
+
+/// 就地纠正一个码块（数据 + 纠错码字，`ecc_len` 为纠错码字数）。
+/// 成功返回纠正的码字数，错误超出纠错能力时返回 None。
+pub(super) fn correct(block: &mut [u8], ecc_len: usize) -> Option<usize> {
+    let gf = &GF;
+    let syndromes: Vec<u8> = (0..ecc_len).map(|i| gf.eval(block, gf.pow(2, i))).collect();
+    if syndromes.iter().all(|&s| s == 0) {
+        return Some(0);
+    }
+
+    // Berlekamp–Massey 求错误定位多项式（系数按最低次在前）
+    let mut locator = vec![1u8];
+    let mut prev = vec![1u8];
+    for (i, _) in syndromes.iter().enumerate() {
+        prev.insert(0, 0);
+        let mut delta = syndromes[i];
+        for j in 1..locator.len() {
+            delta ^= gf.mul(locator[j], syndromes[i - j]);
+        }
+        if delta != 0 {
+            if prev.len() > locator.len() {
+                let next: Vec<u8> = prev.iter().map(|&c| gf.mul(c, delta)).collect();
+                prev = locator.iter().map(|&c| gf.div(c, delta)).collect();
+                locator = next;
+            }
+            for (j, &c) in prev.iter().enumerate() {
+                if j >= locator.len() {
+                    locator.push(0);
+                }
+                locator[j] ^= gf.mul(delta, c);
+            }
+        }
+    }
+    while locator.last() == Some(&0) {
+        locator.pop();
+    }
+    let errors = locator.len() - 1;
+    if errors * 2 > ecc_len {
+        return None;
+    }
+
+    // Chien 搜索：定位多项式的根 X^-1 对应错误位置
+    let n = block.len();
+    let highest_first: Vec<u8> = locator.iter().rev().copied().collect();
+    let positions: Vec<usize> = (0..n)
+        .filter(|&i| gf.eval(&highest_first, gf.inverse(gf.pow(2, n - 1 - i))) == 0)
+        .collect();
+    if positions.len() != errors {
+        return None;
+    }
+
+    // Forney 算法求错误值：Ω(x) = S(x)·Λ(x) mod x^ecc_len
+    let mut omega = vec![0u8; ecc_len];
+    for (i, &s) in syndromes.iter().enumerate() {
+        for (j, &l) in locator.iter().enumerate() {
+            if i + j < ecc_len {
+                omega[i + j] ^= gf.mul(s, l);
+            }
+        }
+    }
+    for &pos in &positions {
+        let x = gf.pow(2, n - 1 - pos);
+        let x_inv = gf.inverse(x);
+        let omega_val = omega
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, &c)| acc ^ gf.mul(c, gf.pow(x_inv, i)));
+        // Λ'(x) 只保留奇次项
+        let deriv = locator
+            .iter()
+            .enumerate()
+            .skip(1)
+            .step_by(2)
+            .fold(0, |acc, (i, &c)| acc ^ gf.mul(c, gf.pow(x_inv, i - 1)));
+        if deriv == 0 {
+            return None;
+        }
+        block[pos] ^= gf.mul(x, gf.div(omega_val, deriv));
+    }
+
+    let ok = (0..ecc_len).all(|i| gf.eval(block, gf.pow(2, i)) == 0);
+    ok.then_some(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::ec::create_error_correction_code;
+
+    fn block(data: &[u8], ecc_len: usize) -> Vec<u8> {
+        let mut block = data.to_vec();
+        block.extend(create_error_correction_code(data, ecc_len));
+        block
+    }
+
+    #[test]
+    fn clean_block_needs_no_correction() {
+        let mut b = block(b"dev-tools", 10);
+        assert_eq!(correct(&mut b, 10), Some(0));
+    }
+
+    #[test]
+    fn corrects_up_to_half_the_ecc_codewords() {
+        let data: Vec<u8> = (0..40u8).map(|i| i.wrapping_mul(37) ^ 0x5A).collect();
+        for ecc_len in [7, 10, 16, 22, 30] {
+            let clean = block(&data, ecc_len);
+            for errors in 1..=ecc_len / 2 {
+                let mut b = clean.clone();
+                // 错误分散在数据区与纠错区
+                for k in 0..errors {
+                    let pos = (k * 13 + 3) % b.len();
+                    b[pos] ^= 0xA5 ^ k as u8;
+                }
+                assert_eq!(correct(&mut b, ecc_len), Some(errors), "ecc={ecc_len} errors={errors}");
+                assert_eq!(b, clean);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_errors() {
+        let clean = block(b"0123456789abcdef", 10);
+        let mut b = clean.clone();
+        for pos in [0, 2, 4, 6, 8, 10, 12] {
+            b[pos] ^= 0xFF;
+        }
+        assert_eq!(correct(&mut b, 10), None);
+    }
+}
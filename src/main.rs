@@ -21,7 +21,7 @@ async fn main() -> anyhow::Result<()> {
     let (listener, addr) = bind(&args.host, args.port).await?;
     let url = format!("http://{addr}");
 
-    let app = server::build_router();
+    let app = server::build_router(server::AppState::from_cli(&args));
 
     tracing::info!("dev-tools 已启动：{url}");
     eprintln!("Listening on {url}");
@@ -1,19 +1,91 @@
+use axum::extract::{DefaultBodyLimit, FromRef};
 use axum::routing::{get, post};
 use axum::Router;
+use std::sync::Arc;
+use std::time::Duration;
 use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
+use crate::cli::Cli;
+use crate::handlers::dns::{ClientPool, DnsConfig};
+use crate::handlers::image::{ImageConfig, WorkerPool};
 use crate::{assets, handlers};
 
-pub fn build_router() -> Router {
+/// 各处理器共享的运行时配置。
+#[derive(Clone)]
+pub struct AppState {
+    pub image: ImageConfig,
+    pub dns: DnsConfig,
+    /// 上传接口的请求体大小上限（字节）
+    pub max_body_bytes: usize,
+}
+
+impl AppState {
+    pub fn from_cli(args: &Cli) -> Self {
+        let workers = match args.image_workers {
+            0 => std::thread::available_parallelism().map_or(4, |n| n.get()),
+            n => n,
+        };
+        Self {
+            image: ImageConfig {
+                max_upload_bytes: args.max_upload_mb.saturating_mul(1024 * 1024),
+                max_pixels: args.max_megapixels.saturating_mul(1_000_000),
+                pool: Arc::new(WorkerPool::new(
+                    workers,
+                    args.image_queue,
+                    Duration::from_secs(args.image_timeout_secs.max(1)),
+                )),
+            },
+            dns: DnsConfig {
+                clients: Arc::new(ClientPool::new(args.dns_cache_entries)),
+            },
+            max_body_bytes: args.max_body_mb.saturating_mul(1024 * 1024),
+        }
+    }
+}
+
+impl FromRef<AppState> for ImageConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.image.clone()
+    }
+}
+
+impl FromRef<AppState> for DnsConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.dns.clone()
+    }
+}
+
+pub fn build_router(state: AppState) -> Router {
+    // multipart 上传在读取时逐块校验单个文件大小，这里只放宽上传接口（含 data URI 解码）的请求体上限；
+    // 其余 JSON 接口保持 axum 默认的 2MB
+    let uploads = Router::new()
+        .route("/image/convert", post(handlers::image::convert))
+        .route("/image/info", post(handlers::image::info))
+        .route("/image/animate", post(handlers::image::animate))
+        .route("/image/diff", post(handlers::image::diff))
+        .route("/image/hash", post(handlers::image::hash))
+        .route("/image/sprite", post(handlers::image::sprite))
+        .route("/image/data-uri", post(handlers::image::to_data_uri))
+        .route("/image/data-uri/decode", post(handlers::image::from_data_uri))
+        .route("/qrcode/generate", post(handlers::qrcode::generate))
+        .route("/qrcode/decode", post(handlers::qrcode::decode))
+        .layer(DefaultBodyLimit::max(state.max_body_bytes));
+
     let api = Router::new()
         .route("/healthz", get(|| async { "ok" }))
         .route("/json/convert", post(handlers::json::convert))
         .route("/json/schema", post(handlers::json::schema))
         .route("/json/query", post(handlers::json::query))
         .route("/dns/resolve", post(handlers::dns::resolve))
-        .route("/image/convert", post(handlers::image::convert));
+        .route("/dns/idn", post(handlers::dns::idn))
+        .route("/dns/propagation", post(handlers::dns::propagation))
+        .route("/dns/trace", post(handlers::dns::trace))
+        .route("/dns/mail", post(handlers::dns::mail))
+        .route("/dns/cache/flush", post(handlers::dns::flush_cache))
+        .merge(uploads)
+        .with_state(state);
 
     Router::new()
         .nest("/api", api)
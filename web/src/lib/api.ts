@@ -1,5 +1,16 @@
 const DEFAULT_TIMEOUT = 15000;
 
+/** 错误响应体可能是纯文本，也可能是 `{ error, message, ... }` 结构（如 413）。 */
+function errorMessage(text: string): string {
+  try {
+    const body = JSON.parse(text);
+    if (body && typeof body.message === "string") return body.message;
+  } catch {
+    // 纯文本错误
+  }
+  return text;
+}
+
 async function withTimeout(
   run: (signal: AbortSignal) => Promise<Response>,
   timeout = DEFAULT_TIMEOUT,
@@ -10,7 +21,7 @@ async function withTimeout(
     const res = await run(controller.signal);
     if (!res.ok) {
       const text = await res.text().catch(() => "");
-      throw new Error(text || `请求失败（HTTP ${res.status}）`);
+      throw new Error(errorMessage(text) || `请求失败（HTTP ${res.status}）`);
     }
     return res;
   } catch (e) {